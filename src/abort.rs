@@ -0,0 +1,65 @@
+use std::error::Error;
+use std::fmt;
+use std::os::windows::io::{AsRawHandle, RawHandle};
+use std::thread::JoinHandle;
+
+use crate::JoinError;
+
+/// The `JoinError` of a thread stopped by `force_abort`, in place of a panic payload
+///
+/// Tell the two apart with e.g. `error.is::<ForceAborted>()`. The thread's
+/// destructors and any other cleanup never ran.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ForceAborted;
+
+impl fmt::Display for ForceAborted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "thread was forcefully aborted")
+    }
+}
+
+impl Error for ForceAborted {}
+
+/// Exit code the OS reports for an aborted thread
+const ABORTED_EXIT_CODE: u32 = 1;
+
+/// `INFINITE`, waiting without a timeout
+const INFINITE: u32 = u32::MAX;
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn TerminateThread(thread: RawHandle, exit_code: u32) -> i32;
+    fn WaitForSingleObject(handle: RawHandle, milliseconds: u32) -> u32;
+}
+
+/// Join `thread`, first stopping it with `TerminateThread` if it is still running
+///
+/// A thread that finished on its own, even just before being stopped, is
+/// joined as usual. If the OS refuses to stop it, it is joined normally,
+/// blocking until it finishes.
+///
+/// # Safety
+///
+/// As for `TerminableThreadGroup::force_abort`
+pub(crate) unsafe fn join_or_abort<T>(thread: JoinHandle<T>) -> Result<T, JoinError> {
+    if !thread.is_finished() {
+        let handle = thread.as_raw_handle();
+
+        // SAFETY: the join handle keeps the thread handle open, and the caller
+        // accepts the consequences of stopping the thread wherever it is
+        if unsafe { TerminateThread(handle, ABORTED_EXIT_CODE) } == 0 {
+            return thread.join();
+        }
+
+        // SAFETY: as above. The wait can only fail for an invalid handle.
+        unsafe { WaitForSingleObject(handle, INFINITE) };
+
+        // A thread stopped before handing over its result still holds a share
+        // of it, so is never finished, and joining it would panic
+        if !thread.is_finished() {
+            return Err(Box::new(ForceAborted));
+        }
+    }
+
+    thread.join()
+}
@@ -0,0 +1,332 @@
+use std::io;
+use std::thread::JoinHandle;
+
+/// Which CPU cores the threads of a group are allowed to run on
+///
+/// Indices refer to the OS's numbering of logical cores.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum CorePinning {
+    /// Pin the thread at index `i` to the `i`th core the process may run on, wrapping around
+    #[default]
+    RoundRobin,
+    /// Pin the thread at index `i` to `cores[i % cores.len()]`
+    Cores(Vec<usize>),
+    /// Let every thread run on any of the given cores, but no others
+    Mask(Vec<usize>),
+    /// Let the thread at index `i` run on any core of NUMA node `i % nodes`
+    SpreadNodes,
+    /// Let every thread run on any core of the given NUMA node, but no others
+    Node(usize),
+}
+
+impl CorePinning {
+    /// The cores each of `count` threads may run on, by index
+    ///
+    /// # Errors
+    ///
+    /// The OS error if the available cores or NUMA nodes couldn't be listed, or
+    /// `InvalidInput` if there are no cores to pin to or a node doesn't exist
+    pub(crate) fn cores_for(&self, count: usize) -> io::Result<Vec<Vec<usize>>> {
+        let sets = match self {
+            CorePinning::RoundRobin => os::available_cores()?
+                .into_iter()
+                .map(|core| vec![core])
+                .collect(),
+            CorePinning::Cores(cores) => cores.iter().map(|&core| vec![core]).collect(),
+            CorePinning::Mask(cores) => vec![cores.clone()],
+            CorePinning::SpreadNodes => numa_nodes()?,
+            CorePinning::Node(node) => {
+                let mut nodes = numa_nodes()?;
+
+                if *node >= nodes.len() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("NUMA node {node} is out of range"),
+                    ));
+                }
+
+                vec![nodes.swap_remove(*node)]
+            }
+        };
+
+        if sets.is_empty() || sets.iter().any(Vec::is_empty) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no cores to pin threads to",
+            ));
+        }
+
+        Ok((0..count)
+            .map(|index| sets[index % sets.len()].clone())
+            .collect())
+    }
+
+    /// Pin each of `threads` according to its index
+    ///
+    /// # Errors
+    ///
+    /// As for `cores_for`, or the OS error if a thread couldn't be pinned
+    pub(crate) fn apply<T>(&self, threads: &[JoinHandle<T>]) -> io::Result<()> {
+        for (thread, cores) in threads.iter().zip(self.cores_for(threads.len())?) {
+            os::pin(os::native(thread), &cores)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Pin the calling thread to `cores`
+pub(crate) fn pin_current(cores: &[usize]) -> io::Result<()> {
+    os::pin(os::current(), cores)
+}
+
+/// The cores of each NUMA node, by node number
+///
+/// A machine without NUMA support is reported as a single node holding every
+/// core.
+///
+/// # Errors
+///
+/// The OS error if the nodes couldn't be listed
+pub fn numa_nodes() -> io::Result<Vec<Vec<usize>>> {
+    os::numa_nodes()
+}
+
+fn no_such_core(core: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("core {core} is out of range"),
+    )
+}
+
+/// CPU sets, through `pthread_setaffinity_np`, and NUMA nodes from sysfs
+#[cfg(all(feature = "unix", target_os = "linux"))]
+mod os {
+    use std::fs;
+    use std::io;
+    use std::mem;
+    use std::os::raw::c_int;
+    use std::os::unix::thread::{JoinHandleExt, RawPthread};
+    use std::thread::JoinHandle;
+
+    /// Where the kernel lists NUMA nodes, as `node0`, `node1`, ...
+    const NODES: &str = "/sys/devices/system/node";
+
+    /// `cpu_set_t`, a bitmask of 1024 cores
+    #[repr(C)]
+    #[derive(Default)]
+    struct CpuSet {
+        bits: [u64; 16],
+    }
+
+    impl CpuSet {
+        const CAPACITY: usize = 1024;
+
+        fn contains(&self, core: usize) -> bool {
+            self.bits[core / 64] & (1 << (core % 64)) != 0
+        }
+
+        fn insert(&mut self, core: usize) {
+            self.bits[core / 64] |= 1 << (core % 64);
+        }
+    }
+
+    extern "C" {
+        fn pthread_self() -> RawPthread;
+        fn pthread_setaffinity_np(
+            thread: RawPthread,
+            cpusetsize: usize,
+            cpuset: *const CpuSet,
+        ) -> c_int;
+        fn sched_getaffinity(pid: c_int, cpusetsize: usize, cpuset: *mut CpuSet) -> c_int;
+    }
+
+    pub(super) fn native<T>(thread: &JoinHandle<T>) -> RawPthread {
+        thread.as_pthread_t()
+    }
+
+    pub(super) fn current() -> RawPthread {
+        // SAFETY: `pthread_self` has no preconditions
+        unsafe { pthread_self() }
+    }
+
+    pub(super) fn available_cores() -> io::Result<Vec<usize>> {
+        let mut set = CpuSet::default();
+
+        // SAFETY: `set` is a valid `cpu_set_t` of the size given
+        if unsafe { sched_getaffinity(0, mem::size_of::<CpuSet>(), &mut set) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok((0..CpuSet::CAPACITY)
+            .filter(|&core| set.contains(core))
+            .collect())
+    }
+
+    /// `thread` must be running, i.e. not yet joined
+    pub(super) fn pin(thread: RawPthread, cores: &[usize]) -> io::Result<()> {
+        let mut set = CpuSet::default();
+
+        for &core in cores {
+            if core >= CpuSet::CAPACITY {
+                return Err(super::no_such_core(core));
+            }
+
+            set.insert(core);
+        }
+
+        // SAFETY: callers pass a running thread, and `set` is a valid
+        // `cpu_set_t` of the size given
+        match unsafe { pthread_setaffinity_np(thread, mem::size_of::<CpuSet>(), &set) } {
+            0 => Ok(()),
+            error => Err(io::Error::from_raw_os_error(error)),
+        }
+    }
+
+    pub(super) fn numa_nodes() -> io::Result<Vec<Vec<usize>>> {
+        let entries = match fs::read_dir(NODES) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Ok(vec![available_cores()?]);
+            }
+            Err(error) => return Err(error),
+        };
+
+        let mut nodes = Vec::new();
+
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+
+            let Some(node) = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("node"))
+                .and_then(|number| number.parse::<usize>().ok())
+            else {
+                continue;
+            };
+
+            let cores = parse_core_list(&fs::read_to_string(entry.path().join("cpulist"))?)?;
+            nodes.push((node, cores));
+        }
+
+        nodes.sort_unstable_by_key(|&(node, _)| node);
+
+        Ok(nodes.into_iter().map(|(_, cores)| cores).collect())
+    }
+
+    /// Parse a kernel core list, e.g. `0-3,8-11`
+    fn parse_core_list(list: &str) -> io::Result<Vec<usize>> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed core list");
+        let mut cores = Vec::new();
+
+        for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+            let (first, last) = range.split_once('-').unwrap_or((range, range));
+            let first: usize = first.parse().map_err(|_| invalid())?;
+            let last: usize = last.parse().map_err(|_| invalid())?;
+
+            cores.extend(first..=last);
+        }
+
+        Ok(cores)
+    }
+}
+
+/// Affinity masks, through `SetThreadAffinityMask`, and NUMA nodes
+#[cfg(all(feature = "windows", windows))]
+mod os {
+    use std::io;
+    use std::os::windows::io::{AsRawHandle, RawHandle};
+    use std::thread::JoinHandle;
+
+    type Bool = i32;
+
+    const FALSE: Bool = 0;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcess() -> RawHandle;
+        fn GetCurrentThread() -> RawHandle;
+        fn GetProcessAffinityMask(
+            process: RawHandle,
+            process_mask: *mut usize,
+            system_mask: *mut usize,
+        ) -> Bool;
+        fn SetThreadAffinityMask(thread: RawHandle, mask: usize) -> usize;
+        fn GetNumaHighestNodeNumber(highest: *mut u32) -> Bool;
+        fn GetNumaNodeProcessorMask(node: u8, mask: *mut u64) -> Bool;
+    }
+
+    pub(super) fn native<T>(thread: &JoinHandle<T>) -> RawHandle {
+        thread.as_raw_handle()
+    }
+
+    pub(super) fn current() -> RawHandle {
+        // SAFETY: `GetCurrentThread` has no preconditions
+        unsafe { GetCurrentThread() }
+    }
+
+    pub(super) fn available_cores() -> io::Result<Vec<usize>> {
+        let (mut process_mask, mut system_mask) = (0, 0);
+
+        // SAFETY: the current process's pseudo handle is always valid, and both
+        // masks are valid to write to
+        if unsafe {
+            GetProcessAffinityMask(GetCurrentProcess(), &mut process_mask, &mut system_mask)
+        } == FALSE
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(cores_in(process_mask as u64))
+    }
+
+    /// `thread` must be an open handle
+    pub(super) fn pin(thread: RawHandle, cores: &[usize]) -> io::Result<()> {
+        let mut mask = 0usize;
+
+        for &core in cores {
+            if core >= usize::BITS as usize {
+                return Err(super::no_such_core(core));
+            }
+
+            mask |= 1 << core;
+        }
+
+        // SAFETY: callers pass an open thread handle
+        if unsafe { SetThreadAffinityMask(thread, mask) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn numa_nodes() -> io::Result<Vec<Vec<usize>>> {
+        let mut highest = 0;
+
+        // SAFETY: `highest` is valid to write to
+        if unsafe { GetNumaHighestNodeNumber(&mut highest) } == FALSE {
+            return Err(io::Error::last_os_error());
+        }
+
+        (0..=highest)
+            .map(|node| {
+                let mut mask = 0;
+
+                // SAFETY: `mask` is valid to write to. Node numbers past the
+                // highest are rejected by the OS rather than misread.
+                if unsafe { GetNumaNodeProcessorMask(node as u8, &mut mask) } == FALSE {
+                    return Err(io::Error::last_os_error());
+                }
+
+                Ok(cores_in(mask))
+            })
+            .collect()
+    }
+
+    fn cores_in(mask: u64) -> Vec<usize> {
+        (0..u64::BITS as usize)
+            .filter(|&core| mask & (1 << core) != 0)
+            .collect()
+    }
+}
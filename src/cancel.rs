@@ -0,0 +1,307 @@
+#[cfg(all(feature = "unix", target_os = "linux"))]
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+#[cfg(all(feature = "unix", target_os = "linux"))]
+use std::os::fd::AsFd;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{self, AtomicBool, AtomicU64, AtomicUsize};
+use std::sync::{Arc, Mutex};
+
+use crate::pool::lock;
+
+/// Something blocking I/O can be cancelled through, so a worker blocked on it wakes up
+///
+/// Register it with `Token::cancel_io_on_terminate`, and it is cancelled as
+/// soon as the worker's flag is raised. Implemented for sockets, which are
+/// shut down, listeners, which are woken by a connection to themselves, and
+/// `CancelPipe`, to wait on anything else.
+pub trait CancelIo: Send + Sync {
+    /// Make any blocking call on this return straight away
+    ///
+    /// Called at most once per registration, from whichever thread raised the
+    /// flag, so it shouldn't block.
+    fn cancel_io(&self);
+}
+
+/// Shuts the stream down, so blocked reads return `0` and writes fail
+impl CancelIo for TcpStream {
+    fn cancel_io(&self) {
+        // Fails only if the stream is already disconnected
+        let _ = self.shutdown(Shutdown::Both);
+    }
+}
+
+/// Shuts the stream down, so blocked reads return `0` and writes fail
+#[cfg(unix)]
+impl CancelIo for UnixStream {
+    fn cancel_io(&self) {
+        // Fails only if the stream is already disconnected
+        let _ = self.shutdown(Shutdown::Both);
+    }
+}
+
+/// Connects to the listener, so a blocked `accept` returns
+///
+/// The worker should check its flag after every accepted connection, and drop
+/// the one made here.
+impl CancelIo for TcpListener {
+    fn cancel_io(&self) {
+        let Ok(mut address) = self.local_addr() else {
+            return;
+        };
+
+        if address.ip().is_unspecified() {
+            address = match address {
+                SocketAddr::V4(v4) => SocketAddr::from(([127, 0, 0, 1], v4.port())),
+                SocketAddr::V6(v6) => SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], v6.port())),
+            };
+        }
+
+        // Nothing more can be done if the listener can't be reached
+        let _ = TcpStream::connect(address);
+    }
+}
+
+impl<T: CancelIo + ?Sized> CancelIo for Arc<T> {
+    fn cancel_io(&self) {
+        T::cancel_io(self);
+    }
+}
+
+/// A self-pipe to wait on alongside another file descriptor, which cancelling wakes
+///
+/// For blocking I/O without a way to cancel it directly, e.g. pipes, terminals
+/// or anything else read through a raw file descriptor. Instead of blocking in
+/// `read`, the worker blocks in `wait_readable` and only reads once it
+/// returns `true`. Clones share the same pipe, so one can be registered while
+/// the worker waits on another.
+///
+/// Only available on Linux, with the `unix` feature enabled.
+#[cfg(all(feature = "unix", target_os = "linux"))]
+#[derive(Debug, Clone)]
+pub struct CancelPipe {
+    inner: Arc<PipeInner>,
+}
+
+#[cfg(all(feature = "unix", target_os = "linux"))]
+#[derive(Debug)]
+struct PipeInner {
+    reader: io::PipeReader,
+    writer: io::PipeWriter,
+    cancelled: AtomicBool,
+}
+
+#[cfg(all(feature = "unix", target_os = "linux"))]
+impl CancelPipe {
+    /// Create a pipe that hasn't been cancelled
+    ///
+    /// # Errors
+    ///
+    /// The OS error if the pipe couldn't be created
+    pub fn new() -> io::Result<Self> {
+        let (reader, writer) = io::pipe()?;
+
+        Ok(Self {
+            inner: Arc::new(PipeInner {
+                reader,
+                writer,
+                cancelled: AtomicBool::new(false),
+            }),
+        })
+    }
+
+    /// Block until `fd` can be read from without blocking, or the pipe is cancelled
+    ///
+    /// A closed or failed `fd` counts as readable, as reading it returns
+    /// straight away.
+    ///
+    /// # Returns
+    ///
+    /// `true` if `fd` is readable, `false` if the pipe was cancelled
+    ///
+    /// # Errors
+    ///
+    /// The OS error if waiting failed
+    pub fn wait_readable(&self, fd: impl AsFd) -> io::Result<bool> {
+        if self.is_cancelled() {
+            return Ok(false);
+        }
+
+        let mut fds = [
+            poll::PollFd::readable(fd.as_fd()),
+            poll::PollFd::readable(self.inner.reader.as_fd()),
+        ];
+
+        poll::wait(&mut fds)?;
+
+        Ok(fds[0].is_ready() && !self.is_cancelled())
+    }
+
+    /// Check whether the pipe has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(atomic::Ordering::SeqCst)
+    }
+}
+
+/// Wakes every `wait_readable` on the pipe, now and from then on
+#[cfg(all(feature = "unix", target_os = "linux"))]
+impl CancelIo for CancelPipe {
+    fn cancel_io(&self) {
+        if !self.inner.cancelled.swap(true, atomic::Ordering::SeqCst) {
+            // The byte is never read, so the pipe stays readable. A failed
+            // write can only mean it's already full, i.e. readable.
+            let _ = (&self.inner.writer).write(&[1]);
+        }
+    }
+}
+
+/// Keeps blocking I/O registered with `Token::cancel_io_on_terminate` until dropped
+///
+/// Borrows the flag it was registered with, so it can't outlive it.
+#[derive(Debug)]
+#[must_use = "the I/O is only cancelled while the registration is kept"]
+pub struct IoRegistration<'a> {
+    id: u64,
+    flag: PhantomData<&'a AtomicBool>,
+}
+
+impl Drop for IoRegistration<'_> {
+    fn drop(&mut self) {
+        let mut registry = lock(&REGISTRY);
+        registry.retain(|entry| entry.id != self.id);
+        REGISTERED.store(registry.len(), atomic::Ordering::SeqCst);
+    }
+}
+
+struct Entry {
+    id: u64,
+    /// Address of the flag, only compared while the registration borrows it
+    flag: usize,
+    io: Box<dyn CancelIo>,
+}
+
+/// Every registered piece of I/O along with its flag
+static REGISTRY: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+
+/// Number of registrations, so raising a flag with none doesn't take the lock
+static REGISTERED: AtomicUsize = AtomicUsize::new(0);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Register `io` to be cancelled once `flag` is raised, straight away if it already is
+pub(crate) fn register(flag: &AtomicBool, io: Box<dyn CancelIo>) -> IoRegistration<'_> {
+    let id = NEXT_ID.fetch_add(1, atomic::Ordering::SeqCst);
+
+    {
+        let mut registry = lock(&REGISTRY);
+        registry.push(Entry {
+            id,
+            flag: address(flag),
+            io,
+        });
+        REGISTERED.store(registry.len(), atomic::Ordering::SeqCst);
+    }
+
+    // Checked after registering, so a concurrent raise either sees the entry
+    // or has already set the flag
+    if flag.load(atomic::Ordering::SeqCst) {
+        raised(flag);
+    }
+
+    IoRegistration {
+        id,
+        flag: PhantomData,
+    }
+}
+
+/// Cancel the I/O registered for `flag`, which has just been raised
+pub(crate) fn raised(flag: &AtomicBool) {
+    if REGISTERED.load(atomic::Ordering::SeqCst) == 0 {
+        return;
+    }
+
+    let flag = address(flag);
+
+    let cancelled: Vec<Entry> = {
+        let mut registry = lock(&REGISTRY);
+        let (cancelled, rest) = registry.drain(..).partition(|entry| entry.flag == flag);
+
+        *registry = rest;
+        REGISTERED.store(registry.len(), atomic::Ordering::SeqCst);
+        cancelled
+    };
+
+    // Outside the lock, as cancelling may take a moment. The entries own their
+    // I/O, so it stays open however the registrations are dropped meanwhile.
+    for entry in cancelled {
+        entry.io.cancel_io();
+    }
+}
+
+fn address(flag: &AtomicBool) -> usize {
+    flag as *const AtomicBool as usize
+}
+
+/// Waiting on several file descriptors at once, through `poll`
+#[cfg(all(feature = "unix", target_os = "linux"))]
+mod poll {
+    use std::io;
+    use std::marker::PhantomData;
+    use std::os::fd::{AsRawFd, BorrowedFd};
+    use std::os::raw::{c_int, c_short, c_ulong};
+
+    const POLLIN: c_short = 0x1;
+
+    /// Wait without a timeout
+    const INFINITE: c_int = -1;
+
+    /// `struct pollfd`, borrowing the descriptor for as long as it is polled
+    #[repr(C)]
+    pub(super) struct PollFd<'fd> {
+        fd: c_int,
+        events: c_short,
+        revents: c_short,
+        borrowed: PhantomData<BorrowedFd<'fd>>,
+    }
+
+    impl<'fd> PollFd<'fd> {
+        pub(super) fn readable(fd: BorrowedFd<'fd>) -> Self {
+            Self {
+                fd: fd.as_raw_fd(),
+                events: POLLIN,
+                revents: 0,
+                borrowed: PhantomData,
+            }
+        }
+
+        /// Whether the descriptor is readable, closed or failed
+        pub(super) fn is_ready(&self) -> bool {
+            self.revents != 0
+        }
+    }
+
+    extern "C" {
+        fn poll(fds: *mut PollFd<'_>, nfds: c_ulong, timeout: c_int) -> c_int;
+    }
+
+    /// Block until at least one of `fds` is ready, retrying if interrupted
+    pub(super) fn wait(fds: &mut [PollFd<'_>]) -> io::Result<()> {
+        loop {
+            // SAFETY: `fds` is a valid array of `pollfd` of the length given,
+            // whose descriptors are borrowed and so stay open
+            let result = unsafe { poll(fds.as_mut_ptr(), fds.len() as c_ulong, INFINITE) };
+
+            if result >= 0 {
+                return Ok(());
+            }
+
+            let error = io::Error::last_os_error();
+
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+    }
+}
@@ -0,0 +1,595 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::iter;
+use std::ptr;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::mpsc::{self, Receiver, SendError, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, Thread};
+
+use crate::cancel::{self, CancelIo};
+use crate::pool::lock;
+use crate::TerminableCondvar;
+
+/// Create a channel whose receiving end gives up once `flag` is raised
+///
+/// Works like `std::sync::mpsc::channel`, except that the worker reading from
+/// the receiver can't get stuck waiting on it once termination is signalled.
+pub fn terminable_channel<T>(
+    flag: Arc<AtomicBool>,
+) -> (TerminableSender<T>, TerminableReceiver<T>) {
+    let (sender, receiver) = mpsc::channel();
+    let receiving = Arc::new(Receiving::default());
+
+    let sender = TerminableSender {
+        sender,
+        wake: WakeOnDrop(Arc::clone(&receiving)),
+    };
+
+    let receiver = TerminableReceiver {
+        receiver,
+        receiving,
+        flag,
+    };
+
+    (sender, receiver)
+}
+
+/// Receive from `receiver`, giving up once `flag` is raised
+///
+/// A drop-in replacement for `Receiver::recv` in workers, which otherwise
+/// stay blocked through termination until a message or disconnect arrives.
+/// Wakes as soon as a message is sent, the last sender is dropped or `flag`
+/// is raised through a `FlagSet`.
+///
+/// ## Note
+///
+/// Messages already waiting are received even if the flag is raised. A flag
+/// stored to directly, rather than raised through a `FlagSet`, is only
+/// noticed once something else wakes the receiver.
+///
+/// # Errors
+///
+/// `Disconnected` if every sender has been dropped, `Terminated` if `flag`
+/// was raised before a message arrived
+pub fn recv_or_terminated<T>(
+    receiver: &TerminableReceiver<T>,
+    flag: &AtomicBool,
+) -> Result<T, RecvError> {
+    *lock(&receiver.receiving.thread) = Some(thread::current());
+    let _registration = cancel::register(flag, Box::new(Arc::clone(&receiver.receiving)));
+
+    loop {
+        match receiver.receiver.try_recv() {
+            Ok(message) => return Ok(message),
+            Err(TryRecvError::Disconnected) => return Err(RecvError::Disconnected),
+            Err(TryRecvError::Empty) => {}
+        }
+
+        if flag.load(atomic::Ordering::SeqCst) {
+            return Err(RecvError::Terminated);
+        }
+
+        // Senders and the flag unpark the thread recorded above, and an unpark
+        // that comes before this returns it straight away
+        thread::park();
+    }
+}
+
+/// The thread currently receiving, for senders and termination to wake
+#[derive(Debug, Default)]
+struct Receiving {
+    thread: Mutex<Option<Thread>>,
+}
+
+impl Receiving {
+    fn wake(&self) {
+        if let Some(thread) = lock(&self.thread).as_ref() {
+            thread.unpark();
+        }
+    }
+}
+
+impl CancelIo for Receiving {
+    fn cancel_io(&self) {
+        self.wake();
+    }
+}
+
+/// Wakes the receiver once dropped, so it notices the sender is gone
+#[derive(Debug)]
+struct WakeOnDrop(Arc<Receiving>);
+
+impl Drop for WakeOnDrop {
+    fn drop(&mut self) {
+        self.0.wake();
+    }
+}
+
+/// Error returned when a termination-aware receive ends without a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecvError {
+    /// Every sender has been dropped and the channel is empty
+    Disconnected,
+    /// Termination was signalled while waiting
+    Terminated,
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvError::Disconnected => write!(f, "receiving on a closed channel"),
+            RecvError::Terminated => write!(f, "termination signalled while receiving"),
+        }
+    }
+}
+
+impl Error for RecvError {}
+
+/// The sending half of a `terminable_channel`
+///
+/// Cheap to clone, like `std::sync::mpsc::Sender`. Every send wakes the
+/// receiver, as does dropping the last sender.
+#[derive(Debug)]
+pub struct TerminableSender<T> {
+    sender: Sender<T>,
+    // Dropped after `sender`, so the receiver is woken once it has disconnected
+    wake: WakeOnDrop,
+}
+
+impl<T> TerminableSender<T> {
+    /// Send `message` to the receiver, waking it up
+    ///
+    /// # Errors
+    ///
+    /// Hands `message` back if the receiver has been dropped
+    pub fn send(&self, message: T) -> Result<(), SendError<T>> {
+        self.sender.send(message)?;
+        self.wake.0.wake();
+
+        Ok(())
+    }
+}
+
+impl<T> Clone for TerminableSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            wake: WakeOnDrop(Arc::clone(&self.wake.0)),
+        }
+    }
+}
+
+/// The receiving half of a `terminable_channel`, paired with a termination flag
+///
+/// Every receive uses `recv_or_terminated`, so the worker can't get stuck
+/// waiting on the channel once termination is signalled.
+#[derive(Debug)]
+pub struct TerminableReceiver<T> {
+    receiver: Receiver<T>,
+    receiving: Arc<Receiving>,
+    flag: Arc<AtomicBool>,
+}
+
+impl<T> TerminableReceiver<T> {
+    /// Block until a message arrives, the channel disconnects or termination is signalled
+    ///
+    /// # Errors
+    ///
+    /// See `recv_or_terminated`
+    pub fn recv(&self) -> Result<T, RecvError> {
+        recv_or_terminated(self, &self.flag)
+    }
+
+    /// Iterate over messages until the channel disconnects or termination is signalled
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        iter::from_fn(|| self.recv().ok())
+    }
+
+    /// Unwrap the underlying `Receiver`
+    pub fn into_inner(self) -> Receiver<T> {
+        self.receiver
+    }
+}
+
+/// What happens to queued items once a `WorkChannel`'s termination flag is raised
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ClosePolicy {
+    /// Workers keep popping until the queue is empty, then stop
+    #[default]
+    Drain,
+    /// Workers stop popping straight away, leaving queued items unprocessed
+    Bail,
+}
+
+/// A multi-producer, multi-consumer work queue that closes when termination is signalled
+///
+/// Cheap to clone; producers and workers all hold clones of the same channel.
+/// Once the flag it was created with is raised, e.g. by terminating the group
+/// running the workers, no more items are accepted and workers blocked in
+/// `pop` wake up, draining the queue first or not depending on the
+/// `ClosePolicy`.
+///
+/// The channel can also be closed by hand with `close`, in which case workers
+/// always drain the remaining items.
+///
+/// A channel created with `bounded` holds a limited number of items, making
+/// producers wait in `push` while it is full. Termination wakes them too, so
+/// producers can't deadlock on a queue nobody is popping from anymore.
+///
+/// ## Note
+///
+/// Blocked workers and producers wake as soon as the flag is raised through a
+/// `FlagSet`. A flag stored to directly is only noticed at the next `push`,
+/// `pop` or `close`.
+#[derive(Debug)]
+pub struct WorkChannel<T> {
+    shared: Arc<Shared<T>>,
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    available: TerminableCondvar,
+    space: TerminableCondvar,
+    capacity: Option<usize>,
+    flag: Arc<AtomicBool>,
+    policy: ClosePolicy,
+}
+
+#[derive(Debug)]
+struct State<T> {
+    items: VecDeque<T>,
+    closed: bool,
+}
+
+impl<T> WorkChannel<T> {
+    /// Create an empty, unbounded channel that closes once `flag` is raised
+    pub fn new(flag: Arc<AtomicBool>, policy: ClosePolicy) -> Self {
+        Self::with_capacity(None, flag, policy)
+    }
+
+    /// Create an empty channel holding at most `capacity` items, closing once `flag` is raised
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is zero
+    pub fn bounded(capacity: usize, flag: Arc<AtomicBool>, policy: ClosePolicy) -> Self {
+        assert!(
+            capacity > 0,
+            "bounded work channel needs a non-zero capacity"
+        );
+
+        Self::with_capacity(Some(capacity), flag, policy)
+    }
+
+    fn with_capacity(capacity: Option<usize>, flag: Arc<AtomicBool>, policy: ClosePolicy) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    items: VecDeque::new(),
+                    closed: false,
+                }),
+                available: TerminableCondvar::new(),
+                space: TerminableCondvar::new(),
+                capacity,
+                flag,
+                policy,
+            }),
+        }
+    }
+
+    /// Queue `item` for the next free worker
+    ///
+    /// On a bounded channel, blocks while the channel is full.
+    ///
+    /// # Errors
+    ///
+    /// Hands `item` back if the channel is closed or termination has been
+    /// signalled, including while waiting for space
+    pub fn push(&self, item: T) -> Result<(), PushError<T>> {
+        let (mut state, _) =
+            self.shared
+                .space
+                .wait_while(&self.shared.state, &self.shared.flag, |state| {
+                    !state.closed && self.is_full(state)
+                });
+
+        if self.is_terminated() {
+            return Err(PushError::Terminated(item));
+        }
+
+        if state.closed {
+            return Err(PushError::Closed(item));
+        }
+
+        state.items.push_back(item);
+        self.shared.available.notify_one();
+
+        Ok(())
+    }
+
+    /// Take the next item, blocking while the queue is empty
+    ///
+    /// # Returns
+    ///
+    /// `None` once the channel is closed and drained, or once termination is
+    /// signalled and, with `ClosePolicy::Drain`, the queue is empty
+    pub fn pop(&self) -> Option<T> {
+        self.pop_or_terminated(&self.shared.flag)
+    }
+
+    /// Take the next item like `pop`, also giving up once `flag` is raised
+    ///
+    /// For consumers whose own termination flag isn't the channel's, e.g. a
+    /// worker forwarding items from a channel owned by another group.
+    ///
+    /// # Returns
+    ///
+    /// `None` in the same cases as `pop`, or if `flag` was raised while the
+    /// queue was empty
+    pub fn pop_or_terminated(&self, flag: &AtomicBool) -> Option<T> {
+        let channel_flag = self.shared.flag.as_ref();
+
+        // Also woken when the channel itself closes on termination
+        let flags: &[&AtomicBool] = if ptr::eq(flag, channel_flag) {
+            &[flag]
+        } else {
+            &[flag, channel_flag]
+        };
+
+        let (mut state, _) =
+            self.shared
+                .available
+                .wait_while_any(&self.shared.state, flags, |state| {
+                    state.items.is_empty() && !state.closed
+                });
+
+        if self.is_terminated() && self.shared.policy == ClosePolicy::Bail {
+            return None;
+        }
+
+        let item = state.items.pop_front()?;
+        self.shared.space.notify_one();
+
+        Some(item)
+    }
+
+    /// Iterate over items as `pop` returns them, until the channel is done
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        iter::from_fn(|| self.pop())
+    }
+
+    /// Stop accepting items, letting workers drain the queue and stop
+    pub fn close(&self) {
+        lock(&self.shared.state).closed = true;
+        self.shared.available.notify_all();
+        self.shared.space.notify_all();
+    }
+
+    /// Check whether the channel has been closed or termination signalled
+    pub fn is_closed(&self) -> bool {
+        self.is_terminated() || lock(&self.shared.state).closed
+    }
+
+    /// Number of queued items
+    pub fn len(&self) -> usize {
+        lock(&self.shared.state).items.len()
+    }
+
+    /// Check whether no items are queued
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Maximum number of queued items, `None` if unbounded
+    pub fn capacity(&self) -> Option<usize> {
+        self.shared.capacity
+    }
+
+    fn is_full(&self, state: &State<T>) -> bool {
+        self.shared
+            .capacity
+            .is_some_and(|capacity| state.items.len() >= capacity)
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.shared.flag.load(atomic::Ordering::SeqCst)
+    }
+}
+
+impl<T> Clone for WorkChannel<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/// Error returned when an item can't be pushed onto a `WorkChannel`, holding the item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PushError<T> {
+    /// The channel was closed with `close`
+    Closed(T),
+    /// Termination was signalled
+    Terminated(T),
+}
+
+impl<T> PushError<T> {
+    /// Take back the item that couldn't be pushed
+    pub fn into_inner(self) -> T {
+        match self {
+            PushError::Closed(item) | PushError::Terminated(item) => item,
+        }
+    }
+}
+
+impl<T> fmt::Display for PushError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PushError::Closed(_) => write!(f, "pushing onto a closed channel"),
+            PushError::Terminated(_) => write!(f, "termination signalled while pushing"),
+        }
+    }
+}
+
+impl<T: fmt::Debug> Error for PushError<T> {}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::FlagSet;
+
+    /// Far longer than any wakeup should take, so a missed one fails the test
+    const PATIENCE: Duration = Duration::from_secs(10);
+
+    /// Let a spawned thread get as far as blocking
+    fn settle() {
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    /// Run `f` on another thread, handing its result back through a channel
+    fn spawn<R: Send + 'static>(f: impl FnOnce() -> R + Send + 'static) -> mpsc::Receiver<R> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || sender.send(f()).unwrap());
+
+        receiver
+    }
+
+    #[test]
+    fn receiver_wakes_for_messages_and_disconnect() {
+        let flags = FlagSet::with_len(1);
+        let (sender, receiver) = terminable_channel(Arc::clone(flags.flag(0).unwrap()));
+
+        let received = spawn(move || receiver.iter().collect::<Vec<_>>());
+
+        settle();
+        sender.send(1).unwrap();
+        sender.clone().send(2).unwrap();
+        drop(sender);
+
+        assert_eq!(received.recv_timeout(PATIENCE), Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn terminate_wakes_blocked_recv() {
+        let flags = FlagSet::with_len(1);
+        let (sender, receiver) = terminable_channel::<u32>(Arc::clone(flags.flag(0).unwrap()));
+
+        let received = spawn(move || receiver.recv());
+
+        settle();
+        flags.terminate();
+
+        assert_eq!(
+            received.recv_timeout(PATIENCE),
+            Ok(Err(RecvError::Terminated))
+        );
+        drop(sender);
+    }
+
+    #[test]
+    fn waiting_messages_are_received_after_terminate() {
+        let flags = FlagSet::with_len(1);
+        let (sender, receiver) = terminable_channel(Arc::clone(flags.flag(0).unwrap()));
+        sender.send(1).unwrap();
+
+        flags.terminate();
+
+        assert_eq!(receiver.recv(), Ok(1));
+        assert_eq!(receiver.recv(), Err(RecvError::Terminated));
+    }
+
+    #[test]
+    fn terminate_wakes_blocked_pop() {
+        let flags = FlagSet::with_len(1);
+        let channel = WorkChannel::<u32>::new(Arc::clone(flags.group_flag()), ClosePolicy::Drain);
+
+        let popped = spawn({
+            let channel = channel.clone();
+            move || channel.pop()
+        });
+
+        settle();
+        flags.terminate();
+
+        assert_eq!(popped.recv_timeout(PATIENCE), Ok(None));
+        assert!(channel.is_closed());
+    }
+
+    #[test]
+    fn close_policy_decides_what_happens_to_queued_items() {
+        for (policy, expected) in [
+            (ClosePolicy::Drain, vec![1, 2]),
+            (ClosePolicy::Bail, vec![]),
+        ] {
+            let flags = FlagSet::with_len(1);
+            let channel = WorkChannel::new(Arc::clone(flags.group_flag()), policy);
+            channel.push(1).unwrap();
+            channel.push(2).unwrap();
+
+            flags.terminate();
+
+            assert_eq!(channel.iter().collect::<Vec<_>>(), expected);
+            assert!(matches!(channel.push(3), Err(PushError::Terminated(3))));
+        }
+    }
+
+    #[test]
+    fn channel_termination_wakes_pop_on_another_flag() {
+        let channel_flags = FlagSet::with_len(1);
+        let worker_flags = FlagSet::with_len(1);
+        let channel =
+            WorkChannel::<u32>::new(Arc::clone(channel_flags.group_flag()), ClosePolicy::Drain);
+
+        let popped = spawn({
+            let channel = channel.clone();
+            let flag = Arc::clone(worker_flags.flag(0).unwrap());
+            move || channel.pop_or_terminated(&flag)
+        });
+
+        settle();
+        channel_flags.terminate();
+
+        assert_eq!(popped.recv_timeout(PATIENCE), Ok(None));
+    }
+
+    #[test]
+    fn terminate_wakes_blocked_push() {
+        let flags = FlagSet::with_len(1);
+        let channel = WorkChannel::bounded(1, Arc::clone(flags.group_flag()), ClosePolicy::Drain);
+        channel.push(1).unwrap();
+
+        let pushed = spawn({
+            let channel = channel.clone();
+            move || channel.push(2).map_err(PushError::into_inner)
+        });
+
+        settle();
+        flags.terminate();
+
+        assert_eq!(pushed.recv_timeout(PATIENCE), Ok(Err(2)));
+        assert_eq!(channel.len(), 1);
+    }
+
+    #[test]
+    fn bounded_push_waits_for_space() {
+        let flags = FlagSet::with_len(1);
+        let channel = WorkChannel::bounded(1, Arc::clone(flags.group_flag()), ClosePolicy::Drain);
+        channel.push(1).unwrap();
+
+        let pushed = spawn({
+            let channel = channel.clone();
+            move || channel.push(2).is_ok()
+        });
+
+        settle();
+        assert_eq!(channel.pop(), Some(1));
+
+        assert_eq!(pushed.recv_timeout(PATIENCE), Ok(true));
+        assert_eq!(channel.pop(), Some(2));
+    }
+}
@@ -0,0 +1,188 @@
+use std::sync::atomic::AtomicBool;
+
+use crate::{spawn_terminable, TerminableThreadHandle, Token};
+
+/// Processes items in fixed size chunks, checkpointing after each one
+///
+/// After every completed chunk, its index is passed to the checkpoint sink,
+/// e.g. to be written to disk. A job terminated part way can then be started
+/// again with `resume_after`, skipping the chunks that were already done
+/// instead of starting over.
+///
+/// Termination is checked between chunks, so a chunk that has started is
+/// always completed and checkpointed.
+#[derive(Debug, Clone)]
+pub struct ChunkedRunner<S> {
+    chunk_size: usize,
+    start: usize,
+    sink: S,
+}
+
+impl ChunkedRunner<fn(usize)> {
+    /// Create a runner splitting items into chunks of `chunk_size`, without a checkpoint sink
+    ///
+    /// # Panics
+    ///
+    /// If `chunk_size` is zero
+    pub fn new(chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "chunked runner needs a non-zero chunk size");
+
+        Self {
+            chunk_size,
+            start: 0,
+            sink: |_| {},
+        }
+    }
+
+    /// Create a runner continuing a job from its saved state
+    ///
+    /// # Panics
+    ///
+    /// If the state's chunk size is zero
+    pub fn from_state(state: &JobState) -> Self {
+        let runner = Self::new(state.chunk_size);
+
+        match state.last_completed {
+            Some(last_completed) => runner.resume_after(last_completed),
+            None => runner,
+        }
+    }
+}
+
+impl<S> ChunkedRunner<S>
+where
+    S: FnMut(usize),
+{
+    /// Pass the index of every completed chunk to `sink`
+    pub fn checkpoint<C>(self, sink: C) -> ChunkedRunner<C>
+    where
+        C: FnMut(usize),
+    {
+        ChunkedRunner {
+            chunk_size: self.chunk_size,
+            start: self.start,
+            sink,
+        }
+    }
+
+    /// Skip every chunk up to and including `last_completed`, as read back from a checkpoint
+    pub fn resume_after(mut self, last_completed: usize) -> Self {
+        // No slice has `usize::MAX + 1` chunks, so saturating skips them all
+        self.start = last_completed.saturating_add(1);
+        self
+    }
+
+    /// Run `f` on each remaining chunk with its index, until done or `flag` is raised
+    pub fn run<T, F>(mut self, flag: &AtomicBool, items: &[T], mut f: F) -> ChunkedReport
+    where
+        F: FnMut(usize, &[T]),
+    {
+        let chunks = items.len().div_ceil(self.chunk_size);
+        let mut report = ChunkedReport {
+            chunk_size: self.chunk_size,
+            last_completed: self.start.checked_sub(1),
+            chunks,
+            terminated: false,
+        };
+
+        for (index, chunk) in items.chunks(self.chunk_size).enumerate().skip(self.start) {
+            if flag.should_stop() {
+                report.terminated = true;
+                break;
+            }
+
+            f(index, chunk);
+            (self.sink)(index);
+            report.last_completed = Some(index);
+        }
+
+        report
+    }
+
+    /// Spawn a thread doing `run` over `items`, terminated through the returned handle
+    pub fn spawn<T, F>(self, items: Vec<T>, f: F) -> TerminableThreadHandle<ChunkedReport>
+    where
+        S: Send + 'static,
+        T: Send + 'static,
+        F: FnMut(usize, &[T]) + Send + 'static,
+    {
+        spawn_terminable(move |flag| self.run(&flag, &items, f))
+    }
+}
+
+/// Summary of a `ChunkedRunner` run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkedReport {
+    /// Number of items per chunk
+    pub chunk_size: usize,
+    /// Index of the last completed chunk, including ones skipped by `resume_after`
+    pub last_completed: Option<usize>,
+    /// Total number of chunks
+    pub chunks: usize,
+    /// Whether the run stopped early because termination was signalled
+    pub terminated: bool,
+}
+
+impl ChunkedReport {
+    /// Check whether every chunk has been completed
+    pub fn is_complete(&self) -> bool {
+        self.last_completed.map_or(0, |last| last.saturating_add(1)) >= self.chunks
+    }
+
+    /// The state needed to resume the job later with `ChunkedRunner::from_state`
+    pub fn job_state(&self) -> JobState {
+        JobState {
+            chunk_size: self.chunk_size,
+            last_completed: self.last_completed,
+        }
+    }
+}
+
+/// Saved progress of a chunked job, for resuming it in a later run or process
+///
+/// Both fields are plain numbers, so the state can be stored however the
+/// application stores the rest of its data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JobState {
+    /// Number of items per chunk, which must match between runs
+    pub chunk_size: usize,
+    /// Index of the last completed chunk, `None` if none has completed yet
+    pub last_completed: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use super::*;
+
+    #[test]
+    fn resume_skips_completed_chunks() {
+        let items: Vec<u32> = (0..10).collect();
+        let mut seen = Vec::new();
+
+        let report = ChunkedRunner::new(3).resume_after(1).run(
+            &AtomicBool::new(false),
+            &items,
+            |index, chunk| seen.push((index, chunk.to_vec())),
+        );
+
+        assert_eq!(seen, [(2, vec![6, 7, 8]), (3, vec![9])]);
+        assert_eq!(report.last_completed, Some(3));
+        assert!(report.is_complete());
+    }
+
+    #[test]
+    fn resume_after_last_index_runs_nothing() {
+        let mut runs = 0;
+
+        let report = ChunkedRunner::new(1).resume_after(usize::MAX).run(
+            &AtomicBool::new(false),
+            &[1, 2, 3],
+            |_, _| runs += 1,
+        );
+
+        assert_eq!(runs, 0);
+        assert!(report.is_complete());
+    }
+}
@@ -0,0 +1,120 @@
+use std::io::{self, BufRead, BufReader, Read};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use crate::process::Waited;
+use crate::{spawn_terminable, TerminableThreadHandle};
+
+/// A worker running a command, streaming its output line by line
+///
+/// Created along with the receiving ends of its output by `new`, then run
+/// with `run` from inside any container's thread, or on a thread of its own
+/// with `spawn`. The child is killed as soon as the worker's flag is raised,
+/// e.g. by `terminate` on its group, and the worker returns its exit status
+/// either way.
+#[derive(Debug)]
+pub struct CommandWorker {
+    command: Command,
+    stdout: Sender<String>,
+    stderr: Sender<String>,
+}
+
+/// The lines a `CommandWorker`'s child writes, without their trailing newline
+///
+/// Invalid UTF-8 is replaced. Each channel is closed once the stream reaches
+/// its end, so iterating a receiver collects the whole stream.
+#[derive(Debug)]
+pub struct CommandOutput {
+    /// Lines written to stdout
+    pub stdout: Receiver<String>,
+    /// Lines written to stderr
+    pub stderr: Receiver<String>,
+}
+
+impl CommandWorker {
+    /// Create a worker running `command`, along with the channels its output is sent down
+    ///
+    /// The command's stdout and stderr are replaced with pipes, the rest of
+    /// its configuration is kept.
+    pub fn new(command: Command) -> (Self, CommandOutput) {
+        let (stdout, stdout_receiver) = mpsc::channel();
+        let (stderr, stderr_receiver) = mpsc::channel();
+
+        let worker = Self {
+            command,
+            stdout,
+            stderr,
+        };
+
+        let output = CommandOutput {
+            stdout: stdout_receiver,
+            stderr: stderr_receiver,
+        };
+
+        (worker, output)
+    }
+
+    /// Run the command until it exits, killing it once `flag` is raised
+    ///
+    /// Output is read on two helper threads, which end once the child's
+    /// output is closed. Lines written just before the child exited may
+    /// still be arriving after this returns.
+    ///
+    /// # Errors
+    ///
+    /// The OS error if the command or a helper thread couldn't be spawned, in
+    /// which case the child is killed, or if waiting on or killing it failed
+    pub fn run(mut self, flag: &AtomicBool) -> io::Result<ExitStatus> {
+        let child = self
+            .command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let mut child = Waited { child };
+
+        if let Some(stdout) = child.child.stdout.take() {
+            forward_lines(stdout, self.stdout)?;
+        }
+
+        if let Some(stderr) = child.child.stderr.take() {
+            forward_lines(stderr, self.stderr)?;
+        }
+
+        child.wait_or_kill(flag)
+    }
+
+    /// Spawn a thread running the command, like `run`
+    ///
+    /// Terminating the handle kills the child, and joining it gives the
+    /// child's exit status.
+    pub fn spawn(self) -> TerminableThreadHandle<io::Result<ExitStatus>> {
+        spawn_terminable(move |flag| self.run(&flag))
+    }
+}
+
+/// Spawn a thread sending each line read from `stream` down `sender`, until it ends
+fn forward_lines<R>(stream: R, sender: Sender<String>) -> io::Result<()>
+where
+    R: Read + Send + 'static,
+{
+    thread::Builder::new().spawn(move || {
+        for line in BufReader::new(stream).split(b'\n') {
+            let Ok(mut line) = line else {
+                break;
+            };
+
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+
+            // Lines are still read once the receiver hangs up, so the child
+            // never blocks on a full pipe
+            let _ = sender.send(String::from_utf8_lossy(&line).into_owned());
+        }
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use std::thread;
+use std::time::Duration;
+
+/// Settings applied to every thread a builder spawns
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ThreadConfig {
+    pub(crate) name_pattern: Option<String>,
+    pub(crate) stack_size: Option<usize>,
+    pub(crate) stagger: Option<Duration>,
+}
+
+impl ThreadConfig {
+    /// Create a `std::thread::Builder` for the thread at `index`
+    ///
+    /// Any `{i}` in the name pattern is replaced by the index.
+    pub(crate) fn builder(&self, index: usize) -> thread::Builder {
+        let mut builder = thread::Builder::new();
+
+        if let Some(pattern) = &self.name_pattern {
+            builder = builder.name(pattern.replace("{i}", &index.to_string()));
+        }
+
+        if let Some(size) = self.stack_size {
+            builder = builder.stack_size(size);
+        }
+
+        builder
+    }
+
+    /// How long the thread at `index` waits before starting its work
+    pub(crate) fn start_delay(&self, index: usize) -> Duration {
+        self.stagger.map_or(Duration::ZERO, |stagger| {
+            stagger.saturating_mul(u32::try_from(index).unwrap_or(u32::MAX))
+        })
+    }
+}
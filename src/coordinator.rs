@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::{Join, Terminate};
+
+/// A registered container along with what to do with its output, type-erased
+trait Registered: Send {
+    fn terminate(&self);
+
+    fn join(self: Box<Self>);
+}
+
+struct Entry<C, F> {
+    container: C,
+    on_joined: F,
+}
+
+impl<C, F> Registered for Entry<C, F>
+where
+    C: Terminate + Join + Send,
+    F: FnOnce(C::Output) + Send,
+{
+    fn terminate(&self) {
+        self.container.terminate();
+    }
+
+    fn join(self: Box<Self>) {
+        (self.on_joined)(self.container.join());
+    }
+}
+
+/// Tears an application down in ordered phases
+///
+/// Containers are registered with a phase number, and `shutdown` goes through
+/// the phases in ascending order: every container in a phase is signalled to
+/// terminate, then all of them are joined, before the next phase starts. This
+/// allows e.g. stopping intake in phase 1, draining workers in phase 2 and
+/// stopping flushers in phase 3.
+#[derive(Default)]
+pub struct ShutdownCoordinator {
+    phases: BTreeMap<u32, Vec<Box<dyn Registered>>>,
+}
+
+impl ShutdownCoordinator {
+    /// Create a coordinator with no containers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `container` to be shut down in `phase`, discarding what joining it produces
+    pub fn register<C>(&mut self, phase: u32, container: C)
+    where
+        C: Terminate + Join + Send + 'static,
+    {
+        self.register_with(phase, container, drop);
+    }
+
+    /// Register `container` to be shut down in `phase`, passing what joining it produces to `f`
+    ///
+    /// `f` is called during `shutdown`, e.g. to report threads that panicked.
+    pub fn register_with<C, F>(&mut self, phase: u32, container: C, f: F)
+    where
+        C: Terminate + Join + Send + 'static,
+        F: FnOnce(C::Output) + Send + 'static,
+    {
+        self.phases.entry(phase).or_default().push(Box::new(Entry {
+            container,
+            on_joined: f,
+        }));
+    }
+
+    /// Number of phases with at least one container
+    pub fn phases(&self) -> usize {
+        self.phases.len()
+    }
+
+    /// Terminate and join every container, one phase at a time in ascending order
+    ///
+    /// # Returns
+    ///
+    /// A report for each phase, in the order they were shut down
+    pub fn shutdown(self) -> Vec<PhaseReport> {
+        self.phases
+            .into_iter()
+            .map(|(phase, containers)| {
+                let start = Instant::now();
+
+                for container in &containers {
+                    container.terminate();
+                }
+
+                let count = containers.len();
+
+                for container in containers {
+                    container.join();
+                }
+
+                PhaseReport {
+                    phase,
+                    containers: count,
+                    elapsed: start.elapsed(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for ShutdownCoordinator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let containers: BTreeMap<_, _> = self
+            .phases
+            .iter()
+            .map(|(phase, containers)| (phase, containers.len()))
+            .collect();
+
+        f.debug_struct("ShutdownCoordinator")
+            .field("containers", &containers)
+            .finish()
+    }
+}
+
+/// How one phase of a `ShutdownCoordinator::shutdown` went
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PhaseReport {
+    /// The phase number
+    pub phase: u32,
+    /// Number of containers terminated and joined in the phase
+    pub containers: usize,
+    /// Time from signalling the phase to terminate until all its containers were joined
+    pub elapsed: Duration,
+}
@@ -0,0 +1,59 @@
+use std::io::{self, Read, Write};
+use std::sync::atomic::AtomicBool;
+
+use crate::Token;
+
+/// Size of the chunks copied between checks of the flag, the same as `std::io::copy`'s buffer
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Summary of a `terminable_copy`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CopyReport {
+    /// Number of bytes written to the writer
+    pub copied: u64,
+    /// Whether copying stopped early because termination was signalled
+    pub terminated: bool,
+}
+
+/// Copy everything from `reader` to `writer` like `std::io::copy`, stopping early on termination
+///
+/// The data is copied in chunks, and `flag` is checked before each one, so a
+/// large transfer can be stopped part way. A chunk that has been read is
+/// always written out in full, so `copied` counts every byte taken from the
+/// reader. A read interrupted by a signal, e.g. from
+/// `FlagSet::set_interrupt_on_terminate`, is retried unless termination has
+/// been signalled.
+///
+/// # Errors
+///
+/// Any error from reading or writing other than `Interrupted`, in which case
+/// the number of bytes copied before it isn't reported
+pub fn terminable_copy<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    flag: &AtomicBool,
+) -> io::Result<CopyReport>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+{
+    let mut buffer = [0; CHUNK_SIZE];
+    let mut report = CopyReport::default();
+
+    loop {
+        if flag.should_stop() {
+            report.terminated = true;
+            return Ok(report);
+        }
+
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(report),
+            Ok(read) => read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+
+        writer.write_all(&buffer[..read])?;
+        report.copied += read as u64;
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::pool::lock;
+
+/// Number of events kept per container before the oldest are dropped
+const EVENT_LOG_CAPACITY: usize = 256;
+
+/// Something that happened in the life of a container's threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LifecycleEvent {
+    /// When it happened
+    pub at: Instant,
+    /// What happened
+    pub kind: EventKind,
+    /// Index of the thread it happened to, `None` for the whole container
+    pub index: Option<usize>,
+}
+
+/// The kind of a `LifecycleEvent`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    /// The thread was spawned
+    Spawned,
+    /// Termination was signalled, to one thread or to the whole container
+    TerminateSignalled,
+    /// The thread's function returned
+    Finished,
+    /// The thread panicked
+    Panicked,
+}
+
+/// Ring buffer of the most recent events of a container
+#[derive(Debug, Default)]
+pub(crate) struct EventLog {
+    events: Mutex<VecDeque<LifecycleEvent>>,
+}
+
+impl EventLog {
+    /// Record that `kind` just happened, dropping the oldest event if full
+    pub(crate) fn record(&self, kind: EventKind, index: Option<usize>) {
+        let mut events = lock(&self.events);
+
+        if events.len() == EVENT_LOG_CAPACITY {
+            events.pop_front();
+        }
+
+        events.push_back(LifecycleEvent {
+            at: Instant::now(),
+            kind,
+            index,
+        });
+    }
+
+    /// The recorded events, oldest first
+    pub(crate) fn events(&self) -> Vec<LifecycleEvent> {
+        lock(&self.events).iter().copied().collect()
+    }
+}
@@ -0,0 +1,97 @@
+use std::process::ExitStatus;
+
+use crate::JoinError;
+
+/// Exit code given to a thread that panicked, the same as a Rust process that panics
+pub const PANIC_EXIT_CODE: i32 = 101;
+
+/// Exit code to use for a failure without a code of its own, e.g. an `Err` or a signal
+const FAILURE_EXIT_CODE: i32 = 1;
+
+/// A worker's return value that can be turned into a process exit code
+///
+/// `0` means success, anything else failure.
+pub trait ToExitCode {
+    /// The exit code this value stands for
+    fn exit_code(&self) -> i32;
+}
+
+impl ToExitCode for () {
+    fn exit_code(&self) -> i32 {
+        0
+    }
+}
+
+impl ToExitCode for i32 {
+    fn exit_code(&self) -> i32 {
+        *self
+    }
+}
+
+impl ToExitCode for u8 {
+    fn exit_code(&self) -> i32 {
+        i32::from(*self)
+    }
+}
+
+impl ToExitCode for bool {
+    /// `true` for success
+    fn exit_code(&self) -> i32 {
+        if *self {
+            0
+        } else {
+            FAILURE_EXIT_CODE
+        }
+    }
+}
+
+impl ToExitCode for ExitStatus {
+    /// The status's code, or failure if it has none, e.g. after a signal
+    fn exit_code(&self) -> i32 {
+        self.code().unwrap_or(FAILURE_EXIT_CODE)
+    }
+}
+
+impl<T: ToExitCode, E> ToExitCode for Result<T, E> {
+    /// The code of an `Ok` value, and failure for any `Err`
+    fn exit_code(&self) -> i32 {
+        match self {
+            Ok(value) => value.exit_code(),
+            Err(_) => FAILURE_EXIT_CODE,
+        }
+    }
+}
+
+/// How the exit codes of several workers are folded into one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ExitCodeFold {
+    /// The first non-zero code, by thread index
+    #[default]
+    FirstNonZero,
+    /// The last non-zero code, by thread index
+    LastNonZero,
+    /// The highest code, e.g. when codes are ordered by severity
+    Highest,
+}
+
+impl ExitCodeFold {
+    /// Fold the outcome of joining each thread into one exit code, `0` if there are none
+    ///
+    /// Threads that panicked count as `PANIC_EXIT_CODE`.
+    pub(crate) fn fold<T, I>(self, results: I) -> i32
+    where
+        T: ToExitCode,
+        I: IntoIterator<Item = Result<T, JoinError>>,
+    {
+        let mut codes = results.into_iter().map(|result| match result {
+            Ok(value) => value.exit_code(),
+            Err(_) => PANIC_EXIT_CODE,
+        });
+
+        match self {
+            ExitCodeFold::FirstNonZero => codes.find(|&code| code != 0).unwrap_or(0),
+            ExitCodeFold::LastNonZero => codes.filter(|&code| code != 0).last().unwrap_or(0),
+            ExitCodeFold::Highest => codes.max().unwrap_or(0),
+        }
+    }
+}
@@ -0,0 +1,816 @@
+use std::fmt;
+use std::iter;
+use std::mem;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::{self, JoinHandle, Thread};
+use std::time::{Duration, Instant};
+
+use crate::cancel;
+use crate::events::EventLog;
+#[cfg(all(feature = "unix", target_os = "linux"))]
+use crate::interrupt;
+use crate::metrics::{Timeline, Tracking};
+use crate::pool::lock;
+use crate::{EventKind, Heartbeat, LifecycleEvent, ReadySignal, ThreadMetrics, ThreadStatus};
+
+/// A group termination flag layered over one flag per member thread
+///
+/// Each worker is handed its own member flag, which counts as raised when
+/// either it or the group flag is. `terminate` raises the group flag along
+/// with every member flag, so workers only ever need to check the one flag
+/// they were given, while `terminate_one` and `terminate_each` stop a subset.
+///
+/// Each member also has a `ReadySignal`, for its worker to report once it has
+/// finished setting up, and a `Heartbeat`, for it to show it is still making
+/// progress.
+///
+/// Sets split from one another with `split_off` keep sharing the group flag,
+/// so terminating either of them raises the members of both. Separate sets
+/// can be chained together with `link`.
+#[derive(Debug)]
+pub struct FlagSet {
+    group: Arc<Group>,
+    members: Vec<Member>,
+}
+
+/// A member's own termination flag, along with its readiness latch, heartbeat and timeline
+#[derive(Debug)]
+struct Member {
+    flag: Arc<AtomicBool>,
+    ready: ReadySignal,
+    timed_out: Arc<AtomicBool>,
+    heartbeat: Heartbeat,
+    timeline: Arc<Timeline>,
+}
+
+/// The group flag, along with every member flag it has to raise
+///
+/// Members are held weakly, as they are owned by whichever `FlagSet` they
+/// ended up in after splitting and merging, and so are linked groups.
+#[derive(Debug)]
+pub(crate) struct Group {
+    flag: Arc<AtomicBool>,
+    raised: AtomicBool,
+    raised_at: Mutex<Option<Instant>>,
+    terminate_on_panic: AtomicBool,
+    #[cfg(all(feature = "unix", target_os = "linux"))]
+    interrupt_on_terminate: AtomicBool,
+    events: EventLog,
+    members: Mutex<Vec<Registered>>,
+    links: Mutex<Vec<Weak<Group>>>,
+    hooks: Mutex<Hooks>,
+}
+
+/// Run once when a group is raised, for containers that have more to do than raise flags
+type Hook = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+struct Hooks(Vec<Hook>);
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} hooks", self.0.len())
+    }
+}
+
+/// A member as the group sees it, to raise its flag and wake its thread
+#[derive(Debug)]
+struct Registered {
+    flag: Weak<AtomicBool>,
+    timeline: Weak<Timeline>,
+}
+
+impl Group {
+    /// Record that `kind` just happened to the member at `index`, or the whole group
+    pub(crate) fn record(&self, kind: EventKind, index: Option<usize>) {
+        self.events.record(kind, index);
+    }
+
+    /// Raise the group if a member's thread panicking should take its siblings down
+    pub(crate) fn member_panicked(&self) {
+        if self.terminate_on_panic.load(atomic::Ordering::SeqCst) {
+            self.raise();
+        }
+    }
+
+    fn register(&self, member: &Member) {
+        let mut members = lock(&self.members);
+
+        // Checked under the lock, so a concurrent `raise` either sees the new
+        // member or has already raised the group flag
+        if self.flag.load(atomic::Ordering::SeqCst) {
+            member.flag.store(true, atomic::Ordering::SeqCst);
+        }
+
+        members.retain(|member| member.flag.strong_count() > 0);
+        members.push(Registered {
+            flag: Arc::downgrade(&member.flag),
+            timeline: Arc::downgrade(&member.timeline),
+        });
+    }
+
+    fn link(&self, other: &Arc<Group>) {
+        let mut links = lock(&self.links);
+
+        if self.raised.load(atomic::Ordering::SeqCst) {
+            drop(links);
+            other.raise();
+            return;
+        }
+
+        links.retain(|link| link.strong_count() > 0);
+        links.push(Arc::downgrade(other));
+    }
+
+    fn on_raise(&self, hook: Hook) {
+        let mut hooks = lock(&self.hooks);
+
+        if self.raised.load(atomic::Ordering::SeqCst) {
+            drop(hooks);
+            hook();
+            return;
+        }
+
+        hooks.0.push(hook);
+    }
+
+    fn raise(&self) {
+        self.flag.store(true, atomic::Ordering::SeqCst);
+
+        // Only the first raise propagates, which also stops cycles of links
+        if self.raised.swap(true, atomic::Ordering::SeqCst) {
+            return;
+        }
+
+        *lock(&self.raised_at) = Some(Instant::now());
+        self.events.record(EventKind::TerminateSignalled, None);
+
+        let members: Vec<_> = lock(&self.members)
+            .iter()
+            .filter_map(|member| Some((member.flag.upgrade()?, member.timeline.upgrade()?)))
+            .collect();
+
+        for (flag, _) in &members {
+            flag.store(true, atomic::Ordering::SeqCst);
+        }
+
+        cancel::raised(&self.flag);
+
+        // Woken however the group was raised, e.g. through a link or a signal,
+        // so members parked in `Token::sleep` or similar notice straight away
+        for (flag, timeline) in &members {
+            cancel::raised(flag);
+            timeline.unpark();
+        }
+
+        // Taken under the lock `on_raise` checks `raised` under, so each hook
+        // runs exactly once
+        let hooks = mem::take(&mut *lock(&self.hooks));
+
+        for hook in hooks.0 {
+            hook();
+        }
+
+        let links: Vec<_> = lock(&self.links).iter().filter_map(Weak::upgrade).collect();
+
+        for link in links {
+            link.raise();
+        }
+    }
+}
+
+impl FlagSet {
+    /// Create a set with no members
+    pub fn new() -> Self {
+        Self {
+            group: Arc::new(Group {
+                flag: Arc::new(AtomicBool::new(false)),
+                raised: AtomicBool::new(false),
+                raised_at: Mutex::new(None),
+                terminate_on_panic: AtomicBool::new(false),
+                #[cfg(all(feature = "unix", target_os = "linux"))]
+                interrupt_on_terminate: AtomicBool::new(false),
+                events: EventLog::default(),
+                members: Mutex::new(Vec::new()),
+                links: Mutex::new(Vec::new()),
+                hooks: Mutex::default(),
+            }),
+            members: Vec::new(),
+        }
+    }
+
+    /// Create a set with `len` members
+    pub fn with_len(len: usize) -> Self {
+        let mut flags = Self::new();
+
+        for _ in 0..len {
+            flags.push();
+        }
+
+        flags
+    }
+
+    /// Add a member, returning its flag to hand to the worker
+    ///
+    /// If the group has already been terminated, the new flag starts raised.
+    pub fn push(&mut self) -> Arc<AtomicBool> {
+        let member = Member {
+            flag: Arc::new(AtomicBool::new(false)),
+            ready: ReadySignal::new(),
+            timed_out: Arc::new(AtomicBool::new(false)),
+            heartbeat: Heartbeat::new(),
+            timeline: Arc::default(),
+        };
+
+        self.group.register(&member);
+        let flag = Arc::clone(&member.flag);
+        self.members.push(member);
+        flag
+    }
+
+    /// Move every member of `other` into this set, after this set's own members
+    ///
+    /// The moved members are raised from then on by this set's `terminate`. If
+    /// this set has already been terminated, they are raised immediately.
+    pub fn append(&mut self, other: FlagSet) {
+        for member in &other.members {
+            self.group.register(member);
+        }
+
+        self.members.extend(other.members);
+        self.reassign();
+    }
+
+    /// Split the set in two at `at`, returning the members from `at` onwards
+    ///
+    /// Both sets keep sharing the group flag, so `terminate` on either one
+    /// raises the members of both.
+    ///
+    /// # Panics
+    ///
+    /// If `at` is greater than the number of members
+    pub fn split_off(&mut self, at: usize) -> FlagSet {
+        let split = FlagSet {
+            group: Arc::clone(&self.group),
+            members: self.members.split_off(at),
+        };
+
+        split.reassign();
+        split
+    }
+
+    /// Split the set into one set per member, all sharing the group flag
+    pub(crate) fn split_each(self) -> impl Iterator<Item = FlagSet> {
+        let group = self.group;
+
+        self.members.into_iter().map(move |member| {
+            let split = FlagSet {
+                group: Arc::clone(&group),
+                members: vec![member],
+            };
+
+            split.reassign();
+            split
+        })
+    }
+
+    /// Split the set in two by `mask`, like `split_off` but taking the members marked `true`
+    ///
+    /// Members beyond the end of `mask` are kept.
+    pub(crate) fn partition(self, mask: impl IntoIterator<Item = bool>) -> (FlagSet, FlagSet) {
+        let (taken, kept): (Vec<_>, Vec<_>) = self
+            .members
+            .into_iter()
+            .zip(mask.into_iter().chain(iter::repeat(false)))
+            .partition(|(_, take)| *take);
+
+        let unzip = |members: Vec<(Member, bool)>| {
+            let split = FlagSet {
+                group: Arc::clone(&self.group),
+                members: members.into_iter().map(|(member, _)| member).collect(),
+            };
+
+            split.reassign();
+            split
+        };
+
+        (unzip(taken), unzip(kept))
+    }
+
+    /// Point the tracked threads of the members at this set and their current indices
+    fn reassign(&self) {
+        for (index, member) in self.members.iter().enumerate() {
+            member.timeline.reassign(&self.group, index);
+        }
+    }
+
+    /// Make terminating this set also terminate `other`
+    ///
+    /// The link is one way, call `link` again with the sets swapped to make
+    /// them terminate together. If this set has already been terminated,
+    /// `other` is terminated immediately.
+    pub fn link(&self, other: &FlagSet) {
+        self.group.link(&other.group);
+    }
+
+    /// Run `hook` once the group is raised, however that happens, straight away if it already was
+    ///
+    /// For containers whose termination does more than raise flags, e.g.
+    /// waking workers blocked on a queue, so linking them, registering them
+    /// or terminating them on a signal has the same effect as calling their
+    /// own `terminate`.
+    pub(crate) fn on_terminate<F>(&self, hook: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.group.on_raise(Box::new(hook));
+    }
+
+    /// The group flag, raised only by `terminate`
+    ///
+    /// Can be handed to threads that should stop with the whole group but
+    /// never individually.
+    pub fn group_flag(&self) -> &Arc<AtomicBool> {
+        &self.group.flag
+    }
+
+    /// Flag of the member at `index`
+    pub fn flag(&self, index: usize) -> Option<&Arc<AtomicBool>> {
+        Some(&self.members.get(index)?.flag)
+    }
+
+    /// Readiness latch of the member at `index`, for the worker to raise once set up
+    pub fn ready_signal(&self, index: usize) -> Option<&ReadySignal> {
+        Some(&self.members.get(index)?.ready)
+    }
+
+    /// Heartbeat of the member at `index`, for the worker to refresh while making progress
+    pub fn heartbeat(&self, index: usize) -> Option<&Heartbeat> {
+        Some(&self.members.get(index)?.heartbeat)
+    }
+
+    /// Indices of the members whose last heartbeat is older than `max_age`
+    pub fn stale_members(&self, max_age: Duration) -> Vec<usize> {
+        self.members
+            .iter()
+            .enumerate()
+            .filter(|(_, member)| member.heartbeat.is_stale(max_age))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Milestones recorded for the member at `index`
+    pub fn metrics(&self, index: usize) -> Option<ThreadMetrics> {
+        let raised_at = *lock(&self.group.raised_at);
+
+        Some(self.members.get(index)?.timeline.snapshot(raised_at))
+    }
+
+    /// OS id of the member at `index`'s thread, e.g. to find it in `top -H` or a profiler
+    ///
+    /// Only known for threads spawned by the crate itself, once they have
+    /// started, not ones passed in already running.
+    ///
+    /// Only available on Linux with the `unix` feature enabled, or on Windows
+    /// with the `windows` feature enabled.
+    #[cfg(any(
+        all(feature = "unix", target_os = "linux"),
+        all(feature = "windows", windows)
+    ))]
+    pub fn os_thread_id(&self, index: usize) -> Option<u64> {
+        self.members.get(index)?.timeline.os_id()
+    }
+
+    /// Status of the member at `index`, given whether its thread has finished
+    pub(crate) fn status(&self, index: usize, finished: bool) -> Option<ThreadStatus> {
+        let member = self.members.get(index)?;
+        let status = member
+            .timeline
+            .refine(ThreadStatus::of(finished, &member.flag));
+
+        Some(status.or_timed_out(member.timed_out.load(atomic::Ordering::SeqCst)))
+    }
+
+    /// Record `thread`, already running as the member at `index`, so raising its flag wakes it
+    ///
+    /// Threads spawned through `track` are recorded on their own.
+    pub(crate) fn attach(&self, index: usize, thread: Thread) {
+        if let Some(member) = self.members.get(index) {
+            member.timeline.attach(thread);
+        }
+    }
+
+    /// Start tracking the thread about to be spawned for the member at `index`
+    ///
+    /// The returned guard is moved into the new thread, see `Tracking`.
+    pub(crate) fn track(&self, index: usize) -> Option<Tracking> {
+        let timeline = &self.members.get(index)?.timeline;
+
+        Some(timeline.track(Arc::clone(&self.group), index))
+    }
+
+    /// Check whether the thread tracked for the member at `index` has returned or panicked
+    ///
+    /// Known as soon as its function ends, slightly before its `JoinHandle`
+    /// reports it finished. `false` for members whose thread isn't tracked.
+    pub(crate) fn is_member_finished(&self, index: usize) -> bool {
+        self.members
+            .get(index)
+            .is_some_and(|member| member.timeline.is_finished())
+    }
+
+    /// Arrange for the current thread to be unparked once the member at `index`'s thread finishes
+    ///
+    /// # Returns
+    ///
+    /// `false` if the member's thread isn't tracked, e.g. one passed in
+    /// already running, in which case its finishing has to be polled for
+    pub(crate) fn unpark_on_finish(&self, index: usize) -> bool {
+        self.members
+            .get(index)
+            .is_some_and(|member| member.timeline.unpark_on_finish(thread::current()))
+    }
+
+    /// The most recent lifecycle events of the set's threads, oldest first
+    ///
+    /// Only a bounded number of events is kept, older ones are dropped.
+    /// Indices are those the threads had when the events happened.
+    pub fn events(&self) -> Vec<LifecycleEvent> {
+        self.group.events.events()
+    }
+
+    /// Marker raised along with the member's flag when it exceeds its runtime or CPU limit
+    pub(crate) fn timed_out(&self, index: usize) -> Option<&Arc<AtomicBool>> {
+        Some(&self.members.get(index)?.timed_out)
+    }
+
+    /// Block until every member has signalled it is ready, or `timeout` passes
+    ///
+    /// # Returns
+    ///
+    /// `true` if every member became ready in time
+    pub fn wait_ready(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+
+        self.members
+            .iter()
+            .all(|member| member.ready.wait_until(None, Some(deadline)))
+    }
+
+    /// Number of members
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Check whether the set has no members
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Raise the group flag and every member flag
+    ///
+    /// The members' threads are also unparked, as are those of linked sets, so
+    /// workers waiting in `Token::sleep` or `std::thread::park` notice
+    /// straight away. Only threads spawned by or passed to a container are
+    /// known to the set, not ones a bare set's flags were handed to by hand.
+    pub fn terminate(&self) {
+        self.group.raise();
+    }
+
+    /// Raise only the flag of the member at `index`
+    ///
+    /// # Returns
+    ///
+    /// `false` if `index` is out of bounds
+    pub fn terminate_one(&self, index: usize) -> bool {
+        match self.members.get(index) {
+            Some(member) => {
+                member.flag.store(true, atomic::Ordering::SeqCst);
+                cancel::raised(&member.flag);
+                member.timeline.signalled();
+                member.timeline.unpark();
+                self.group
+                    .events
+                    .record(EventKind::TerminateSignalled, Some(index));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Raise the flag of each member whose position in `mask` is `true`
+    ///
+    /// Members beyond the end of `mask` are left untouched.
+    pub fn terminate_each(&self, mask: impl IntoIterator<Item = bool>) {
+        for (index, (member, terminate)) in self.members.iter().zip(mask).enumerate() {
+            if terminate {
+                member.flag.store(true, atomic::Ordering::SeqCst);
+                cancel::raised(&member.flag);
+                member.timeline.signalled();
+                member.timeline.unpark();
+                self.group
+                    .events
+                    .record(EventKind::TerminateSignalled, Some(index));
+            }
+        }
+    }
+
+    /// Make a panic in any member's thread terminate the whole set, or stop doing so
+    ///
+    /// The group flag is raised while the panicking thread unwinds, before the
+    /// panic reaches whoever joins it, so its siblings don't keep running
+    /// without it. Sets split from one another share the setting.
+    ///
+    /// ## Note
+    ///
+    /// Only threads spawned by a container, rather than passed to one already
+    /// running, are covered.
+    pub fn set_terminate_on_panic(&self, enabled: bool) {
+        self.group
+            .terminate_on_panic
+            .store(enabled, atomic::Ordering::SeqCst);
+    }
+
+    /// Check whether a panic in any member's thread terminates the whole set
+    pub fn terminates_on_panic(&self) -> bool {
+        self.group.terminate_on_panic.load(atomic::Ordering::SeqCst)
+    }
+
+    /// Make terminating a thread also interrupt the blocking syscall it's in, or stop doing so
+    ///
+    /// A no-op `SIGURG` is sent to each thread being terminated, so a `read`,
+    /// `recv` or similar call it's blocked in fails with `EINTR`, and the
+    /// thread can check its flag instead of blocking until the call completes.
+    /// Sets split from one another share the setting.
+    ///
+    /// Only available on Linux, with the `unix` feature enabled. No signal is
+    /// sent on architectures other than x86, x86-64, ARM, AArch64 and RISC-V,
+    /// whose signal numbers and `sigaction` layout differ.
+    ///
+    /// ## Note
+    ///
+    /// Only threads terminated through a container's own `terminate` methods are
+    /// interrupted, not those terminated through a linked set. A thread may
+    /// still miss the signal if it arrives just before the syscall starts, so
+    /// workers should bound their blocking calls with a timeout as well. If
+    /// the application has its own `SIGURG` handler, no signal is sent.
+    ///
+    /// Calls that std retries on `EINTR`, e.g. `TcpListener::accept` or
+    /// `Write::write_all`, keep blocking. Cancel those with
+    /// `Token::cancel_io_on_terminate` instead.
+    #[cfg(all(feature = "unix", target_os = "linux"))]
+    pub fn set_interrupt_on_terminate(&self, enabled: bool) {
+        self.group
+            .interrupt_on_terminate
+            .store(enabled, atomic::Ordering::SeqCst);
+    }
+
+    /// Check whether terminating a thread also interrupts the blocking syscall it's in
+    #[cfg(all(feature = "unix", target_os = "linux"))]
+    pub fn interrupts_on_terminate(&self) -> bool {
+        self.group
+            .interrupt_on_terminate
+            .load(atomic::Ordering::SeqCst)
+    }
+
+    /// Wake `thread` once its flag has been raised, so it notices straight away
+    ///
+    /// The thread is unparked, and interrupted if the set is configured to.
+    pub(crate) fn wake<T>(&self, thread: &JoinHandle<T>) {
+        thread.thread().unpark();
+
+        #[cfg(all(feature = "unix", target_os = "linux"))]
+        if self.interrupts_on_terminate() {
+            interrupt::interrupt(thread);
+        }
+    }
+
+    /// Check whether the group flag has been raised
+    pub fn is_terminated(&self) -> bool {
+        self.group.flag.load(atomic::Ordering::SeqCst)
+    }
+
+    /// When the group flag was raised, if it has been
+    pub(crate) fn terminated_at(&self) -> Option<Instant> {
+        *lock(&self.group.raised_at)
+    }
+
+    /// Check whether the member at `index` was terminated for exceeding its runtime or CPU limit
+    pub fn is_member_timed_out(&self, index: usize) -> bool {
+        self.timed_out(index)
+            .is_some_and(|timed_out| timed_out.load(atomic::Ordering::SeqCst))
+    }
+
+    /// Check whether the member at `index` has been signalled, by its own flag or the group's
+    ///
+    /// Out of bounds members are reported by the group flag alone.
+    pub fn is_member_terminated(&self, index: usize) -> bool {
+        self.is_terminated()
+            || self
+                .flag(index)
+                .is_some_and(|flag| flag.load(atomic::Ordering::SeqCst))
+    }
+}
+
+impl Default for FlagSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AsRef<FlagSet> for FlagSet {
+    fn as_ref(&self) -> &FlagSet {
+        self
+    }
+}
+
+/// Make terminating container `a` also terminate container `b`
+///
+/// Works across any containers built on a `FlagSet`, such as
+/// `TerminableThreads`, `TerminableThreadGroup`, `TerminablePool` and
+/// `Supervisor`. See `FlagSet::link`.
+pub fn link<A, B>(a: &A, b: &B)
+where
+    A: AsRef<FlagSet> + ?Sized,
+    B: AsRef<FlagSet> + ?Sized,
+{
+    a.as_ref().link(b.as_ref());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::{SleepOutcome, ThreadOutcome, Token};
+
+    /// Far longer than any wakeup should take, so a missed one fails the test
+    const PATIENCE: Duration = Duration::from_secs(10);
+
+    /// Spawn a thread as the member at `index`, sleeping on its flag until woken
+    fn sleeper(flags: &FlagSet, index: usize) -> thread::JoinHandle<SleepOutcome> {
+        let flag = Arc::clone(flags.flag(index).unwrap());
+        let tracking = flags.track(index).unwrap();
+
+        thread::spawn(move || {
+            let _tracking = tracking.enter();
+            flag.sleep(PATIENCE * 6)
+        })
+    }
+
+    /// Join `thread`, checking it was woken by termination in good time
+    fn assert_woken(thread: thread::JoinHandle<SleepOutcome>, since: Instant) {
+        assert_eq!(thread.join().unwrap(), SleepOutcome::Terminated);
+        assert!(since.elapsed() < PATIENCE);
+    }
+
+    #[test]
+    fn terminate_wakes_every_member() {
+        let flags = FlagSet::with_len(3);
+        let sleepers: Vec<_> = (0..3).map(|index| sleeper(&flags, index)).collect();
+
+        let start = Instant::now();
+        flags.terminate();
+
+        for sleeper in sleepers {
+            assert_woken(sleeper, start);
+        }
+    }
+
+    #[test]
+    fn terminate_one_leaves_the_rest() {
+        let flags = FlagSet::with_len(2);
+        let first = sleeper(&flags, 0);
+
+        let start = Instant::now();
+        assert!(flags.terminate_one(0));
+        assert!(!flags.terminate_one(2));
+
+        assert_woken(first, start);
+        assert!(!flags.is_terminated());
+        assert!(!flags.is_member_terminated(1));
+    }
+
+    #[test]
+    fn link_wakes_the_linked_set() {
+        let source = FlagSet::new();
+        let linked = FlagSet::with_len(1);
+        source.link(&linked);
+
+        let sleeper = sleeper(&linked, 0);
+
+        let start = Instant::now();
+        source.terminate();
+
+        assert_woken(sleeper, start);
+        assert!(linked.is_terminated());
+    }
+
+    #[test]
+    fn link_is_one_way() {
+        let source = FlagSet::new();
+        let linked = FlagSet::with_len(1);
+        source.link(&linked);
+
+        linked.terminate();
+
+        assert!(!source.is_terminated());
+    }
+
+    #[test]
+    fn linking_a_terminated_set_terminates_straight_away() {
+        let source = FlagSet::new();
+        source.terminate();
+
+        let linked = FlagSet::with_len(1);
+        source.link(&linked);
+
+        assert!(linked.is_member_terminated(0));
+    }
+
+    #[test]
+    fn split_sets_share_termination() {
+        let mut flags = FlagSet::with_len(2);
+        let split = flags.split_off(1);
+        let sleeper = sleeper(&split, 0);
+
+        let start = Instant::now();
+        flags.terminate();
+
+        assert_woken(sleeper, start);
+        assert!(split.is_terminated());
+    }
+
+    #[test]
+    fn hooks_run_once_however_the_set_is_raised() {
+        let source = FlagSet::new();
+        let linked = FlagSet::new();
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        source.link(&linked);
+
+        linked.on_terminate({
+            let runs = Arc::clone(&runs);
+            move || {
+                runs.fetch_add(1, atomic::Ordering::SeqCst);
+            }
+        });
+
+        source.terminate();
+        linked.terminate();
+
+        assert_eq!(runs.load(atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn finished_members_report_a_panic_straight_away() {
+        let flags = FlagSet::with_len(1);
+        let tracking = flags.track(0).unwrap();
+
+        assert!(flags.unpark_on_finish(0));
+
+        let thread = thread::spawn(move || {
+            let _tracking = tracking.enter().started();
+            panic!("expected in this test");
+        });
+
+        while !flags.is_member_finished(0) {
+            thread::park();
+        }
+
+        // Checked before the join, while the waiter may have only just been woken
+        assert_eq!(
+            flags.status(0, true),
+            Some(ThreadStatus::Finished(ThreadOutcome::Panicked))
+        );
+        assert!(thread.join().is_err());
+    }
+
+    #[test]
+    fn merged_members_report_to_their_new_set() {
+        let mut flags = FlagSet::with_len(1);
+        flags.set_terminate_on_panic(true);
+
+        let other = FlagSet::with_len(1);
+        let tracking = other.track(0).unwrap();
+        let (release, gate) = mpsc::channel::<()>();
+
+        let thread = thread::spawn(move || {
+            let _tracking = tracking.enter().started();
+            let _ = gate.recv();
+            panic!("expected in this test");
+        });
+
+        flags.append(other);
+        drop(release);
+
+        assert!(thread.join().is_err());
+        assert!(flags.is_terminated());
+        assert!(flags
+            .events()
+            .iter()
+            .any(|event| event.kind == EventKind::Panicked && event.index == Some(1)));
+    }
+}
@@ -0,0 +1,76 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::Arc;
+
+use crate::Token;
+
+/// An iteration budget that signals termination once used up
+///
+/// The worker calls `tick` once per loop iteration, e.g. `fuel.tick()?`.
+/// After `limit` ticks the limiter raises the termination flag it was
+/// created with, bounding runaway search or generation loops by a fixed
+/// amount of work rather than by time.
+#[derive(Debug)]
+pub struct FuelLimiter {
+    flag: Arc<AtomicBool>,
+    remaining: u64,
+}
+
+impl FuelLimiter {
+    /// Create a limiter allowing `limit` ticks before raising `flag`
+    pub fn new(flag: Arc<AtomicBool>, limit: u64) -> Self {
+        Self {
+            flag,
+            remaining: limit,
+        }
+    }
+
+    /// Use up one unit of fuel
+    ///
+    /// # Errors
+    ///
+    /// `Exhausted` once the fuel has run out, which raises the flag, or
+    /// `Terminated` if termination was signalled some other way
+    pub fn tick(&mut self) -> Result<(), FuelError> {
+        if self.flag.should_stop() {
+            return Err(match self.remaining {
+                0 => FuelError::Exhausted,
+                _ => FuelError::Terminated,
+            });
+        }
+
+        if self.remaining == 0 {
+            self.flag.store(true, atomic::Ordering::SeqCst);
+            return Err(FuelError::Exhausted);
+        }
+
+        self.remaining -= 1;
+        Ok(())
+    }
+
+    /// Number of ticks left before the fuel runs out
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+}
+
+/// Error returned by `FuelLimiter::tick` once the worker should stop
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FuelError {
+    /// The iteration budget has been used up
+    Exhausted,
+    /// Termination was signalled before the budget ran out
+    Terminated,
+}
+
+impl fmt::Display for FuelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FuelError::Exhausted => write!(f, "iteration budget exhausted"),
+            FuelError::Terminated => write!(f, "termination signalled"),
+        }
+    }
+}
+
+impl Error for FuelError {}
@@ -0,0 +1,1030 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+#[cfg(all(feature = "unix", target_os = "linux"))]
+use std::os::unix::thread::{JoinHandleExt, RawPthread};
+#[cfg(all(feature = "windows", windows))]
+use std::os::windows::io::{AsRawHandle, RawHandle};
+use std::sync::atomic::{self, AtomicBool};
+#[cfg(any(
+    all(feature = "unix", target_os = "linux"),
+    all(feature = "windows", windows)
+))]
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle, Scope, ScopedJoinHandle};
+use std::time::Duration;
+
+#[cfg(all(feature = "windows", windows))]
+use crate::abort;
+use crate::cancel;
+use crate::status;
+#[cfg(any(
+    all(feature = "unix", target_os = "linux"),
+    all(feature = "windows", windows)
+))]
+use crate::{affinity, CorePinning};
+use crate::{
+    ExitCodeFold, FlagSet, GroupStats, HangDetection, HealthSource, Heartbeat, Join, JoinAllError,
+    JoinError, LifecycleEvent, ReadySignal, Snapshot, TerminableThreadHandle, TerminableThreads,
+    Terminate, ThreadMetrics, ThreadStatus, ToExitCode, UnfinishedThread,
+};
+
+/// A growable group of terminable threads sharing one termination flag
+///
+/// The `Vec`-backed counterpart of `TerminableThreads`, for when the number of
+/// threads isn't known at compile time.
+///
+/// Each thread is given its own flag from a `FlagSet`, which is raised either
+/// for that thread alone (e.g. by `terminate_one`) or for every thread at once
+/// by `terminate`.
+pub struct TerminableThreadGroup<T> {
+    pub(crate) _threads: Vec<JoinHandle<T>>,
+    pub(crate) _flags: FlagSet,
+}
+
+impl<T: Send + 'static> TerminableThreadGroup<T> {
+    /// Spawn one thread per function, each receiving its termination flag
+    ///
+    /// The functions are cloned out of the slice, to spawn functions that
+    /// aren't `Clone`, collect an iterator of them into a group instead.
+    pub fn new<F>(funcs: &[F]) -> Self
+    where
+        F: FnOnce(Arc<AtomicBool>) -> T + Clone + Send + 'static,
+    {
+        let mut group = Self::empty();
+
+        for func in funcs.iter().cloned() {
+            group.spawn(thread::Builder::new(), func);
+        }
+
+        group
+    }
+
+    /// Spawn one thread per function, each receiving its termination flag and its index
+    ///
+    /// The index is the thread's position in the group, e.g. to pick the shard
+    /// or partition of the work it owns.
+    pub fn new_indexed<F, I>(funcs: I) -> Self
+    where
+        I: IntoIterator<Item = F>,
+        F: FnOnce(Arc<AtomicBool>, usize) -> T + Send + 'static,
+    {
+        let mut group = Self::empty();
+
+        for (index, func) in funcs.into_iter().enumerate() {
+            group.spawn(thread::Builder::new(), move |flag| func(flag, index));
+        }
+
+        group
+    }
+
+    /// Spawn one thread per function, each receiving its termination flag and its `ReadySignal`
+    ///
+    /// Each function should signal readiness once it has finished setting up,
+    /// so the caller can wait for the whole group with `wait_ready`.
+    pub fn new_with_ready<F, I>(funcs: I) -> Self
+    where
+        I: IntoIterator<Item = F>,
+        F: FnOnce(Arc<AtomicBool>, ReadySignal) -> T + Send + 'static,
+    {
+        let mut group = Self::empty();
+
+        for func in funcs {
+            group
+                .try_spawn(thread::Builder::new(), |flag, ready, _| func(flag, ready))
+                .expect("failed to spawn thread");
+        }
+
+        group
+    }
+
+    /// Spawn one thread per function, each receiving its termination flag and its `Heartbeat`
+    ///
+    /// Each function should beat regularly while making progress, so the
+    /// caller can spot wedged threads with `stale_threads`.
+    pub fn new_with_heartbeat<F, I>(funcs: I) -> Self
+    where
+        I: IntoIterator<Item = F>,
+        F: FnOnce(Arc<AtomicBool>, Heartbeat) -> T + Send + 'static,
+    {
+        let mut group = Self::empty();
+
+        for func in funcs {
+            group
+                .try_spawn(thread::Builder::new(), |flag, _, heartbeat| {
+                    func(flag, heartbeat)
+                })
+                .expect("failed to spawn thread");
+        }
+
+        group
+    }
+
+    /// Spawn `n` threads running copies of `func`
+    ///
+    /// Each thread receives its termination flag and its worker index, from
+    /// `0` to `n - 1`.
+    pub fn spawn_replicated<F>(n: usize, func: F) -> Self
+    where
+        F: FnOnce(Arc<AtomicBool>, usize) -> T + Clone + Send + 'static,
+    {
+        let mut group = Self::empty();
+
+        for index in 0..n {
+            let func = func.clone();
+            group.spawn(thread::Builder::new(), move |flag| func(flag, index));
+        }
+
+        group
+    }
+
+    /// Spawn `n` threads pinned to cores by `pinning`, each building its own state first
+    ///
+    /// Each thread pins itself before anything else, then calls `init` with
+    /// its worker index and passes the result to `func` along with its
+    /// termination flag and index. As memory is placed on the NUMA node of the
+    /// thread that first touches it, state built in `init` is local to the
+    /// cores the thread runs on, e.g. with `CorePinning::SpreadNodes`.
+    ///
+    /// Only available on Linux with the `unix` feature enabled, or on Windows
+    /// with the `windows` feature enabled.
+    ///
+    /// # Errors
+    ///
+    /// As for `pin_to_cores`, or the OS error if a thread couldn't be spawned.
+    /// The threads spawned so far are then signalled to terminate and joined,
+    /// having been started with their flags raised.
+    #[cfg(any(
+        all(feature = "unix", target_os = "linux"),
+        all(feature = "windows", windows)
+    ))]
+    pub fn spawn_pinned<S, Init, F>(
+        n: usize,
+        pinning: &CorePinning,
+        init: Init,
+        func: F,
+    ) -> io::Result<Self>
+    where
+        Init: FnOnce(usize) -> S + Clone + Send + 'static,
+        F: FnOnce(Arc<AtomicBool>, usize, S) -> T + Clone + Send + 'static,
+    {
+        let mut group = Self::empty();
+
+        for (index, cores) in pinning.cores_for(n)?.into_iter().enumerate() {
+            let (init, func) = (init.clone(), func.clone());
+            let (pinned_tx, pinned_rx) = mpsc::sync_channel(1);
+
+            let spawned = group.try_spawn(thread::Builder::new(), move |flag, _, _| {
+                let pinned = affinity::pin_current(&cores);
+
+                if pinned.is_err() {
+                    flag.store(true, atomic::Ordering::SeqCst);
+                }
+
+                // The spawner only hangs up once it has given up on the group
+                let _ = pinned_tx.send(pinned);
+
+                let state = init(index);
+                func(flag, index, state)
+            });
+
+            let pinned = spawned.and_then(|_| {
+                pinned_rx
+                    .recv()
+                    .expect("the thread reports before doing anything else")
+            });
+
+            if let Err(error) = pinned {
+                group.join(true);
+                return Err(error);
+            }
+        }
+
+        Ok(group)
+    }
+
+    /// Initialise and spawn workers one by one, stopping everything if any initialisation fails
+    ///
+    /// Each initialiser runs on the calling thread and returns the worker
+    /// function to spawn, or an error. On the first error, the workers spawned
+    /// so far are signalled to terminate and joined, and the error is returned,
+    /// so no partially started group is left behind.
+    ///
+    /// # Errors
+    ///
+    /// The error of the first initialiser that failed
+    pub fn try_new<I, Init, W, E>(inits: I) -> Result<Self, E>
+    where
+        I: IntoIterator<Item = Init>,
+        Init: FnOnce() -> Result<W, E>,
+        W: FnOnce(Arc<AtomicBool>) -> T + Send + 'static,
+    {
+        let mut group = Self::empty();
+
+        for init in inits {
+            match init() {
+                Ok(func) => group.spawn(thread::Builder::new(), func),
+                Err(error) => {
+                    group.join(true);
+                    return Err(error);
+                }
+            }
+        }
+
+        Ok(group)
+    }
+
+    /// Spawn one named thread per `(name, function)` pair
+    ///
+    /// The name is given to the OS thread, so it also shows up in panic
+    /// messages, and can be used to address the thread within the group.
+    pub fn new_named<S, F, I>(funcs: I) -> Self
+    where
+        I: IntoIterator<Item = (S, F)>,
+        S: Into<String>,
+        F: FnOnce(Arc<AtomicBool>) -> T + Send + 'static,
+    {
+        let mut group = Self::empty();
+
+        for (name, func) in funcs {
+            group.spawn(thread::Builder::new().name(name.into()), func);
+        }
+
+        group
+    }
+
+    /// Spawn one clone of `func` per available CPU core
+    ///
+    /// The core count comes from `std::thread::available_parallelism`, falling
+    /// back to a single thread if it can't be determined. Each thread receives
+    /// its termination flag and its worker index.
+    pub fn spawn_per_core<F>(func: F) -> Self
+    where
+        F: FnOnce(Arc<AtomicBool>, usize) -> T + Clone + Send + 'static,
+    {
+        let cores = thread::available_parallelism().map_or(1, usize::from);
+        Self::spawn_replicated(cores, func)
+    }
+
+    /// Spawn another thread into the running group
+    ///
+    /// The thread receives its own flag like the rest of the group. If the
+    /// group has already been terminated, the flag starts raised.
+    pub fn push<F>(&mut self, func: F)
+    where
+        F: FnOnce(Arc<AtomicBool>) -> T + Send + 'static,
+    {
+        self.spawn(thread::Builder::new(), func);
+    }
+
+    /// Spawn another thread into the running group, like `push`, without panicking
+    ///
+    /// # Returns
+    ///
+    /// The index of the new thread within the group
+    ///
+    /// # Errors
+    ///
+    /// Returns the OS error if the thread could not be spawned, in which case
+    /// the group is left unchanged.
+    pub fn spawn_additional<F>(&mut self, func: F) -> io::Result<usize>
+    where
+        F: FnOnce(Arc<AtomicBool>) -> T + Send + 'static,
+    {
+        self.try_spawn(thread::Builder::new(), |flag, _, _| func(flag))
+    }
+
+    /// Pin the group's threads to CPU cores, e.g. right after spawning one per core
+    ///
+    /// Keeps cache-sensitive workers on the same core instead of letting the
+    /// OS move them around. Threads added to the group afterwards aren't
+    /// pinned.
+    ///
+    /// Only available on Linux with the `unix` feature enabled, or on Windows
+    /// with the `windows` feature enabled.
+    ///
+    /// # Errors
+    ///
+    /// The OS error if a thread couldn't be pinned, or `InvalidInput` if no
+    /// cores were given or a core doesn't exist. Threads before the one that
+    /// failed stay pinned.
+    #[cfg(any(
+        all(feature = "unix", target_os = "linux"),
+        all(feature = "windows", windows)
+    ))]
+    pub fn pin_to_cores(&self, pinning: &CorePinning) -> io::Result<()> {
+        pinning.apply(&self._threads)
+    }
+
+    pub(crate) fn spawn<F>(&mut self, builder: thread::Builder, func: F)
+    where
+        F: FnOnce(Arc<AtomicBool>) -> T + Send + 'static,
+    {
+        self.try_spawn(builder, |flag, _, _| func(flag))
+            .expect("failed to spawn thread");
+    }
+
+    fn try_spawn<F>(&mut self, builder: thread::Builder, func: F) -> io::Result<usize>
+    where
+        F: FnOnce(Arc<AtomicBool>, ReadySignal, Heartbeat) -> T + Send + 'static,
+    {
+        let index = self._threads.len();
+        let flag = self._flags.push();
+        let ready = self._flags.ready_signal(index).cloned();
+        let ready = ready.expect("a member was just added for the thread");
+        let heartbeat = self._flags.heartbeat(index).cloned();
+        let heartbeat = heartbeat.expect("a member was just added for the thread");
+        let tracking = self._flags.track(index);
+        let tracking = tracking.expect("a member was just added for the thread");
+
+        match builder.spawn(move || {
+            let _tracking = tracking.enter().started();
+            func(flag, ready, heartbeat)
+        }) {
+            Ok(thread) => {
+                self._threads.push(thread);
+                Ok(index)
+            }
+            Err(error) => {
+                self._flags.split_off(index);
+                Err(error)
+            }
+        }
+    }
+}
+
+impl<T> TerminableThreadGroup<T> {
+    fn empty() -> Self {
+        Self {
+            _threads: Vec::new(),
+            _flags: FlagSet::new(),
+        }
+    }
+
+    /// Spawn one scoped thread per function inside `scope`, sharing a termination flag
+    ///
+    /// Scoped threads may borrow from the enclosing stack frame, so the
+    /// functions don't need to be `'static`.
+    pub fn scoped<'scope, 'env, F, I>(
+        scope: &'scope Scope<'scope, 'env>,
+        funcs: I,
+    ) -> ScopedThreadGroup<'scope, T>
+    where
+        I: IntoIterator<Item = F>,
+        F: FnOnce(Arc<AtomicBool>) -> T + Send + 'scope,
+        T: Send + 'scope,
+    {
+        let flag = Arc::new(AtomicBool::new(false));
+
+        let threads = funcs
+            .into_iter()
+            .map(|func| {
+                let flag = Arc::clone(&flag);
+                scope.spawn(move || func(flag))
+            })
+            .collect();
+
+        ScopedThreadGroup {
+            _threads: threads,
+            _terminate_flag: flag,
+        }
+    }
+
+    /// Combine two groups into one, whose `terminate` stops the threads of both
+    ///
+    /// The threads of `other` come after those of `self`, so indices into
+    /// `self` stay valid. If `self` has already been terminated, the threads of
+    /// `other` are signalled to terminate as well. From then on their events,
+    /// and panics with `set_terminate_on_panic`, count towards the merged group.
+    pub fn merge(mut self, other: Self) -> Self {
+        self._threads.extend(other._threads);
+        self._flags.append(other._flags);
+        self
+    }
+
+    /// Split the group in two at `at`, returning the threads from `at` onwards as a new group
+    ///
+    /// Both groups can be joined independently, but keep sharing termination,
+    /// so `terminate` on either one signals the threads of both.
+    ///
+    /// # Panics
+    ///
+    /// If `at` is greater than the number of threads
+    pub fn split_at(mut self, at: usize) -> (Self, Self) {
+        let other = Self {
+            _threads: self._threads.split_off(at),
+            _flags: self._flags.split_off(at),
+        };
+
+        (self, other)
+    }
+
+    /// Split the group in two, the first holding the threads for which `predicate` returns `true`
+    ///
+    /// Like `split_at`, both groups keep sharing termination. The threads keep
+    /// their relative order within each group.
+    pub fn split_by<P>(self, mut predicate: P) -> (Self, Self)
+    where
+        P: FnMut(&JoinHandle<T>) -> bool,
+    {
+        let mask: Vec<bool> = self._threads.iter().map(&mut predicate).collect();
+
+        let (matched, rest): (Vec<_>, Vec<_>) = self
+            ._threads
+            .into_iter()
+            .zip(&mask)
+            .partition(|(_, matched)| **matched);
+
+        let (matched_flags, rest_flags) = self._flags.partition(mask.iter().copied());
+
+        let unzip = |threads: Vec<(JoinHandle<T>, &bool)>, flags| Self {
+            _threads: threads.into_iter().map(|(thread, _)| thread).collect(),
+            _flags: flags,
+        };
+
+        (unzip(matched, matched_flags), unzip(rest, rest_flags))
+    }
+
+    /// Convert into a fixed size `TerminableThreads<T, N>`, keeping each thread's flag
+    ///
+    /// # Returns
+    ///
+    /// `Err` with the group unchanged if it doesn't hold exactly `N` threads
+    pub fn into_fixed<const N: usize>(self) -> Result<TerminableThreads<T, N>, Self> {
+        if self._threads.len() != N {
+            return Err(self);
+        }
+
+        match self._threads.try_into() {
+            Ok(threads) => Ok(TerminableThreads {
+                _threads: threads,
+                _flags: self._flags,
+            }),
+            Err(_) => unreachable!("the number of threads was checked to be N"),
+        }
+    }
+
+    /// Split the group into one handle per thread, in spawn order
+    ///
+    /// The handles keep sharing termination, so `terminate` on any of them
+    /// signals all the threads that were in the group.
+    pub fn into_handles(self) -> Vec<TerminableThreadHandle<T>> {
+        self._threads
+            .into_iter()
+            .zip(self._flags.split_each())
+            .map(|(thread, flags)| TerminableThreadHandle {
+                _thread: thread,
+                _flags: flags,
+            })
+            .collect()
+    }
+
+    /// Signal termination, then wait up to `timeout` for every thread to acknowledge it
+    ///
+    /// A thread acknowledges termination by returning from its function, so
+    /// this tells whether the workers actually listened to the flag.
+    ///
+    /// # Returns
+    ///
+    /// `true` if every thread finished within `timeout`
+    pub fn terminate_and_wait_ack(&self, timeout: Duration) -> bool {
+        self.terminate();
+        status::wait_finished(&self._threads, &self._flags, timeout)
+    }
+
+    /// Block until every thread has signalled it is ready, or `timeout` passes
+    ///
+    /// Threads signal readiness through the `ReadySignal` they were given by
+    /// `new_with_ready`. Threads spawned any other way never become ready.
+    ///
+    /// # Returns
+    ///
+    /// `true` if every thread became ready in time, otherwise the caller may
+    /// want to `terminate` the group
+    pub fn wait_ready(&self, timeout: Duration) -> bool {
+        self._flags.wait_ready(timeout)
+    }
+
+    /// Signal all threads to terminate and cease operation
+    ///
+    /// The threads are also unparked, so those waiting in `std::thread::park`
+    /// (or `park_timeout`) can notice the flag straight away.
+    ///
+    /// ## Note
+    ///
+    /// Threads will only terminate if the underlying function checks the flag passed to it.
+    pub fn terminate(&self) {
+        self._flags.terminate();
+
+        for thread in &self._threads {
+            self._flags.wake(thread);
+        }
+    }
+
+    /// Terminate every thread as soon as any thread spawned by the group panics, or stop doing so
+    ///
+    /// The shared flag is raised while the panicking thread unwinds, so its
+    /// siblings stop instead of running on without it. The panic still
+    /// reaches `join` as an `Err` for that thread.
+    ///
+    /// ## Note
+    ///
+    /// Threads handed to the group already running aren't covered. Parked
+    /// threads aren't unparked.
+    pub fn set_terminate_on_panic(&self, enabled: bool) {
+        self._flags.set_terminate_on_panic(enabled);
+    }
+
+    /// Interrupt the blocking syscall a thread is in when it is terminated, or stop doing so
+    ///
+    /// A no-op signal is sent to the thread, so e.g. a blocking `read` fails
+    /// with `EINTR` and the thread can check its flag. See
+    /// `FlagSet::set_interrupt_on_terminate` for the caveats.
+    ///
+    /// Only available on Linux, with the `unix` feature enabled.
+    #[cfg(all(feature = "unix", target_os = "linux"))]
+    pub fn set_interrupt_on_terminate(&self, enabled: bool) {
+        self._flags.set_interrupt_on_terminate(enabled);
+    }
+
+    /// Signal only the thread at `index` to terminate, leaving the rest running
+    ///
+    /// # Returns
+    ///
+    /// `false` if `index` is out of bounds
+    pub fn terminate_one(&self, index: usize) -> bool {
+        if !self._flags.terminate_one(index) {
+            return false;
+        }
+
+        self._flags.wake(&self._threads[index]);
+        true
+    }
+
+    /// Signal each thread whose position in `mask` is `true` to terminate
+    ///
+    /// Threads beyond the end of `mask` are left running.
+    pub fn terminate_each(&self, mask: impl IntoIterator<Item = bool>) {
+        let mask: Vec<bool> = mask.into_iter().collect();
+        self._flags.terminate_each(mask.iter().copied());
+
+        for (thread, _) in self._threads.iter().zip(mask).filter(|(_, raise)| *raise) {
+            self._flags.wake(thread);
+        }
+    }
+
+    /// Signal only the thread with the given name to terminate
+    ///
+    /// # Returns
+    ///
+    /// `false` if no thread in the group has that name
+    pub fn terminate_named(&self, name: &str) -> bool {
+        self.position(name)
+            .is_some_and(|index| self.terminate_one(index))
+    }
+
+    /// Look up the status of the thread with the given name
+    pub fn status_of(&self, name: &str) -> Option<ThreadStatus> {
+        let index = self.position(name)?;
+
+        self._flags
+            .status(index, self._threads[index].is_finished())
+    }
+
+    /// Snapshot of every thread's status, in spawn order
+    pub fn statuses(&self) -> Vec<ThreadStatus> {
+        status::snapshot(&self._threads, &self._flags)
+    }
+
+    /// Take a snapshot of how many threads are running, finished and panicked
+    pub fn stats(&self) -> GroupStats {
+        status::stats(&self._threads, &self._flags)
+    }
+
+    /// Indices of the running threads whose last heartbeat is older than `max_age`
+    ///
+    /// Threads refresh their heartbeat through the handle given to them by
+    /// `new_with_heartbeat`. Threads that don't use it count as having last
+    /// beaten when they were spawned, and finished threads are never listed.
+    pub fn stale_threads(&self, max_age: Duration) -> Vec<usize> {
+        status::stale_threads(&self._threads, &self._flags, max_age)
+    }
+
+    /// Milestones recorded for each thread, in spawn order
+    pub fn metrics(&self) -> Vec<ThreadMetrics> {
+        (0..self._threads.len())
+            .map(|index| self._flags.metrics(index).unwrap_or_default())
+            .collect()
+    }
+
+    /// OS id of each thread, e.g. to find it in `top -H`, eBPF traces or a profiler
+    ///
+    /// Only known for threads spawned by the group, once they have
+    /// started. `std::thread::ThreadId` has no relation to the OS id.
+    ///
+    /// Only available on Linux with the `unix` feature enabled, or on Windows
+    /// with the `windows` feature enabled.
+    #[cfg(any(
+        all(feature = "unix", target_os = "linux"),
+        all(feature = "windows", windows)
+    ))]
+    pub fn os_thread_ids(&self) -> Vec<Option<u64>> {
+        (0..self._threads.len())
+            .map(|index| self._flags.os_thread_id(index))
+            .collect()
+    }
+
+    /// Snapshot of the container and every thread, e.g. to expose over an admin endpoint
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::of(&self._threads, &self._flags)
+    }
+
+    /// The most recent lifecycle events of the threads, oldest first
+    ///
+    /// Records when threads were spawned, signalled to terminate, and finished
+    /// or panicked, to reconstruct the order of a shutdown after the fact.
+    /// Only the most recent few hundred events are kept.
+    pub fn events(&self) -> Vec<LifecycleEvent> {
+        self._flags.events()
+    }
+
+    /// The `pthread_t` of the thread at `index`, `None` if out of bounds
+    ///
+    /// For OS-specific operations the crate doesn't wrap. The group still
+    /// owns the thread: it must not be joined or detached through the id, and
+    /// the id is only valid until the group is joined.
+    ///
+    /// Only available on Linux, with the `unix` feature enabled.
+    #[cfg(all(feature = "unix", target_os = "linux"))]
+    pub fn as_pthread_t(&self, index: usize) -> Option<RawPthread> {
+        Some(self._threads.get(index)?.as_pthread_t())
+    }
+
+    /// The Windows `HANDLE` of the thread at `index`, `None` if out of bounds
+    ///
+    /// For OS-specific operations the crate doesn't wrap. The group still
+    /// owns the handle: it must not be closed, and is only valid until the
+    /// group is joined.
+    ///
+    /// Only available on Windows, with the `windows` feature enabled.
+    #[cfg(all(feature = "windows", windows))]
+    pub fn as_raw_handle(&self, index: usize) -> Option<RawHandle> {
+        Some(self._threads.get(index)?.as_raw_handle())
+    }
+
+    /// Name of the thread at `index`, if it has one
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self._threads.get(index)?.thread().name()
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self._threads
+            .iter()
+            .position(|thread| thread.thread().name() == Some(name))
+    }
+
+    /// Number of threads in the group
+    pub fn len(&self) -> usize {
+        self._threads.len()
+    }
+
+    /// Check whether the group has no threads
+    pub fn is_empty(&self) -> bool {
+        self._threads.is_empty()
+    }
+
+    /// Join all threads, optionally signalling termination
+    ///
+    /// # Returns
+    ///
+    /// A `Vec` containing the result of joining each thread, in spawn order
+    pub fn join(self, signal_terminate: bool) -> Vec<Result<T, JoinError>> {
+        if signal_terminate {
+            self.terminate();
+        }
+
+        self._threads.into_iter().map(JoinHandle::join).collect()
+    }
+
+    /// Signal termination and join all threads, reporting any that ignore it
+    ///
+    /// If some threads are still running `patience` after termination was
+    /// signalled, they are passed to `report` before joining carries on, so
+    /// threads that never check their flag don't make the join hang silently.
+    pub fn terminate_with_watchdog<R>(
+        self,
+        patience: Duration,
+        report: R,
+    ) -> Vec<Result<T, JoinError>>
+    where
+        R: FnOnce(Vec<UnfinishedThread>),
+    {
+        self.terminate();
+        status::report_unfinished(&self._threads, &self._flags, patience, report);
+
+        self.join(false)
+    }
+
+    /// Signal termination and join all threads, giving them `grace` to finish
+    ///
+    /// Threads still inside a `Token::uninterruptible` section once `grace`
+    /// has passed aren't counted as hung, and are waited for until they leave it.
+    ///
+    /// # Errors
+    ///
+    /// Gives the group back, without joining, if some threads were still
+    /// running outside a critical section after `grace`
+    pub fn shutdown(self, grace: Duration) -> Result<Vec<Result<T, JoinError>>, Self> {
+        self.terminate();
+
+        if !status::wait_shutdown(&self._threads, &self._flags, grace) {
+            return Err(self);
+        }
+
+        Ok(self.join(false))
+    }
+
+    /// Signal termination, then forcefully stop the threads still running and join them all
+    ///
+    /// A last resort for threads that ignore termination, e.g. inside
+    /// third-party code, typically after `shutdown` gave the group back.
+    /// Threads are stopped with `TerminateThread` and joined with a
+    /// `ForceAborted` error, while threads that already finished are joined
+    /// as usual.
+    ///
+    /// Only available on Windows, with the `windows` feature enabled.
+    ///
+    /// # Safety
+    ///
+    /// The thread is stopped wherever it is, without unwinding: no destructors
+    /// run, locks it holds are never released and memory it owns is leaked.
+    /// This includes locks inside the allocator, `std::io::stdout` and any
+    /// other library code, so the rest of the process may deadlock or see
+    /// half-updated state afterwards. The caller must be sure the thread can't
+    /// be in such a section, e.g. because it only runs a known computation.
+    /// Prefer `shutdown` with a grace period, and only use this when the
+    /// process is exiting anyway.
+    #[cfg(all(feature = "windows", windows))]
+    pub unsafe fn force_abort(self) -> Vec<Result<T, JoinError>> {
+        self.terminate();
+
+        self._threads
+            .into_iter()
+            // SAFETY: upheld by the caller
+            .map(|thread| unsafe { abort::join_or_abort(thread) })
+            .collect()
+    }
+
+    /// Join all threads like `join`, succeeding only if none of them panicked
+    ///
+    /// # Errors
+    ///
+    /// A `JoinAllError` describing every thread that panicked
+    pub fn join_all_ok(self, signal_terminate: bool) -> Result<Vec<T>, JoinAllError> {
+        let names = (0..self.len())
+            .map(|index| self.name(index).map(str::to_owned))
+            .collect();
+
+        JoinAllError::collect(names, self.join(signal_terminate))
+    }
+
+    /// Join all threads like `join`, reporting threads that stall along the way
+    ///
+    /// Rather than blocking opaquely, the join samples the threads' heartbeats
+    /// as configured by `detection`, so a slow thread that keeps beating can be
+    /// told apart from one that is stuck.
+    pub fn join_with_hang_detection(
+        self,
+        signal_terminate: bool,
+        detection: HangDetection,
+    ) -> Vec<Result<T, JoinError>> {
+        if signal_terminate {
+            self.terminate();
+        }
+
+        detection.wait(&self._threads, &self._flags);
+
+        self.join(false)
+    }
+
+    /// Join all threads like `join`, folding their return values into a process exit code
+    ///
+    /// Threads that panicked count as `PANIC_EXIT_CODE`. The result can be
+    /// passed to `std::process::exit` to surface worker failure to the shell.
+    pub fn join_exit_code(self, signal_terminate: bool, fold: ExitCodeFold) -> i32
+    where
+        T: ToExitCode,
+    {
+        fold.fold(self.join(signal_terminate))
+    }
+
+    /// Join all threads like `join`, keying each result by its thread's name
+    ///
+    /// Threads without a name are keyed by their index in the group.
+    pub fn join_named(self, signal_terminate: bool) -> HashMap<String, Result<T, JoinError>> {
+        if signal_terminate {
+            self.terminate();
+        }
+
+        self._threads
+            .into_iter()
+            .enumerate()
+            .map(|(index, thread)| {
+                let name = thread
+                    .thread()
+                    .name()
+                    .map_or_else(|| index.to_string(), str::to_owned);
+
+                (name, thread.join())
+            })
+            .collect()
+    }
+}
+
+impl<T> Default for TerminableThreadGroup<T> {
+    /// An empty group, to which threads can be added with `push`
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T, F> FromIterator<F> for TerminableThreadGroup<T>
+where
+    T: Send + 'static,
+    F: FnOnce(Arc<AtomicBool>) -> T + Send + 'static,
+{
+    /// Spawn one thread per function, each receiving its termination flag
+    fn from_iter<I: IntoIterator<Item = F>>(funcs: I) -> Self {
+        let mut group = Self::empty();
+        group.extend(funcs);
+        group
+    }
+}
+
+impl<T, F> Extend<F> for TerminableThreadGroup<T>
+where
+    T: Send + 'static,
+    F: FnOnce(Arc<AtomicBool>) -> T + Send + 'static,
+{
+    /// Spawn one more thread per function, see `push`
+    fn extend<I: IntoIterator<Item = F>>(&mut self, funcs: I) {
+        for func in funcs {
+            self.push(func);
+        }
+    }
+}
+
+impl<T, const N: usize> From<TerminableThreads<T, N>> for TerminableThreadGroup<T> {
+    fn from(threads: TerminableThreads<T, N>) -> Self {
+        Self {
+            _threads: threads._threads.into(),
+            _flags: threads._flags,
+        }
+    }
+}
+
+impl<T> From<TerminableThreadGroup<T>> for Vec<TerminableThreadHandle<T>> {
+    fn from(group: TerminableThreadGroup<T>) -> Self {
+        group.into_handles()
+    }
+}
+
+impl<T> FromIterator<TerminableThreadHandle<T>> for TerminableThreadGroup<T> {
+    /// Gather handles into a group, whose `terminate` signals all of them
+    ///
+    /// Handles that came from the same group keep sharing termination with
+    /// any of its threads left outside the new group.
+    fn from_iter<I: IntoIterator<Item = TerminableThreadHandle<T>>>(handles: I) -> Self {
+        let mut group = Self::empty();
+
+        for handle in handles {
+            group._threads.push(handle._thread);
+            group._flags.append(handle._flags);
+        }
+
+        group
+    }
+}
+
+impl<T> Terminate for TerminableThreadGroup<T> {
+    fn terminate(&self) {
+        TerminableThreadGroup::terminate(self);
+    }
+}
+
+impl<T> fmt::Debug for TerminableThreadGroup<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.snapshot().debug_container(f, "TerminableThreadGroup")
+    }
+}
+
+impl<T> fmt::Display for TerminableThreadGroup<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TerminableThreadGroup ({})", self.snapshot())
+    }
+}
+
+impl<T> AsRef<FlagSet> for TerminableThreadGroup<T> {
+    fn as_ref(&self) -> &FlagSet {
+        &self._flags
+    }
+}
+
+impl<T> Join for TerminableThreadGroup<T> {
+    type Output = Vec<Result<T, JoinError>>;
+
+    fn join(self) -> Self::Output {
+        TerminableThreadGroup::join(self, false)
+    }
+}
+
+impl<T> HealthSource for TerminableThreadGroup<T> {
+    fn healthy(&self) -> bool {
+        !self.shutting_down() && !self._threads.iter().any(JoinHandle::is_finished)
+    }
+
+    fn shutting_down(&self) -> bool {
+        self._flags.is_terminated()
+    }
+}
+
+/// Spawn each closure into a new `TerminableThreadGroup`, returning the group
+///
+/// Each closure receives its termination flag, e.g.
+/// `terminable!(|stop| work(stop), |stop| more_work(stop))`. The closures may
+/// be of different types, but must all return the same type.
+#[macro_export]
+macro_rules! terminable {
+    ($($func:expr),+ $(,)?) => {{
+        let mut group = $crate::TerminableThreadGroup::default();
+        $(group.push($func);)+
+        group
+    }};
+}
+
+/// A group of scoped terminable threads, created by `TerminableThreadGroup::scoped`
+///
+/// Any threads not joined explicitly are joined when the enclosing scope ends.
+#[derive(Debug)]
+pub struct ScopedThreadGroup<'scope, T> {
+    pub(crate) _threads: Vec<ScopedJoinHandle<'scope, T>>,
+    pub(crate) _terminate_flag: Arc<AtomicBool>,
+}
+
+impl<T> ScopedThreadGroup<'_, T> {
+    /// Signal all threads to terminate and cease operation
+    ///
+    /// The threads are also unparked, so those waiting in `std::thread::park`
+    /// (or `park_timeout`) can notice the flag straight away.
+    ///
+    /// ## Note
+    ///
+    /// Threads will only terminate if the underlying function checks the flag passed to it.
+    pub fn terminate(&self) {
+        self._terminate_flag
+            .as_ref()
+            .store(true, atomic::Ordering::SeqCst);
+        cancel::raised(&self._terminate_flag);
+
+        for thread in &self._threads {
+            thread.thread().unpark();
+        }
+    }
+
+    /// Join all threads, optionally signalling termination
+    pub fn join(self, signal_terminate: bool) -> Vec<Result<T, JoinError>> {
+        if signal_terminate {
+            self.terminate();
+        }
+
+        self._threads
+            .into_iter()
+            .map(ScopedJoinHandle::join)
+            .collect()
+    }
+}
+
+impl<T> Terminate for ScopedThreadGroup<'_, T> {
+    fn terminate(&self) {
+        ScopedThreadGroup::terminate(self);
+    }
+}
+
+impl<T> Join for ScopedThreadGroup<'_, T> {
+    type Output = Vec<Result<T, JoinError>>;
+
+    fn join(self) -> Self::Output {
+        ScopedThreadGroup::join(self, false)
+    }
+}
+
+impl<T> HealthSource for ScopedThreadGroup<'_, T> {
+    fn healthy(&self) -> bool {
+        !self.shutting_down() && !self._threads.iter().any(ScopedJoinHandle::is_finished)
+    }
+
+    fn shutting_down(&self) -> bool {
+        self._terminate_flag.load(atomic::Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt;
+#[cfg(all(feature = "unix", target_os = "linux"))]
+use std::os::unix::thread::{JoinHandleExt, RawPthread};
+#[cfg(all(feature = "windows", windows))]
+use std::os::windows::io::{AsRawHandle, IntoRawHandle, RawHandle};
+use std::slice;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle, Thread};
+use std::time::{Duration, Instant};
+
+#[cfg(all(feature = "windows", windows))]
+use crate::abort;
+use crate::interval::park_until;
+use crate::status;
+use crate::{
+    FlagSet, HealthSource, Join, JoinError, LifecycleEvent, Snapshot, Terminate, ThreadMetrics,
+    ThreadStatus,
+};
+
+/// Spawn a single terminable thread running `f`, passing it its termination flag
+///
+/// The terminable counterpart of `std::thread::spawn`.
+///
+/// # Panics
+///
+/// If the OS fails to create the thread, like `std::thread::spawn`
+pub fn spawn_terminable<F, T>(f: F) -> TerminableThreadHandle<T>
+where
+    F: FnOnce(Arc<AtomicBool>) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let mut flags = FlagSet::new();
+    let flag = flags.push();
+    let tracking = flags.track(0).expect("a member was just added");
+
+    TerminableThreadHandle {
+        _thread: thread::spawn(move || {
+            let _tracking = tracking.enter().started();
+            f(flag)
+        }),
+        _flags: flags,
+    }
+}
+
+/// Spawn a thread that starts running `f` once `delay` has passed
+///
+/// If the handle is terminated before then, `f` never runs and joining the
+/// thread gives `None`.
+pub fn spawn_after<F, T>(delay: Duration, f: F) -> TerminableThreadHandle<Option<T>>
+where
+    F: FnOnce(Arc<AtomicBool>) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let start = Instant::now() + delay;
+
+    spawn_terminable(move |flag| {
+        if park_until(&flag, start) {
+            return None;
+        }
+
+        Some(f(flag))
+    })
+}
+
+/// A single terminable thread
+///
+/// Handles taken out of a `TerminableThreadGroup` keep sharing termination
+/// with the rest of the group, so `terminate` on any one of them signals all.
+pub struct TerminableThreadHandle<T> {
+    pub(crate) _thread: JoinHandle<T>,
+    pub(crate) _flags: FlagSet,
+}
+
+impl<T> TerminableThreadHandle<T> {
+    /// Signal the thread to terminate and cease operation
+    ///
+    /// The thread is also unparked, so one waiting in `std::thread::park` (or
+    /// `park_timeout`) can notice the flag straight away.
+    ///
+    /// ## Note
+    ///
+    /// The thread will only terminate if the underlying function checks the flag passed to it.
+    pub fn terminate(&self) {
+        self._flags.terminate();
+        self._flags.wake(&self._thread);
+    }
+
+    /// Signal termination, then wait up to `timeout` for the thread to acknowledge it
+    ///
+    /// The thread acknowledges termination by returning from its function.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the thread finished within `timeout`
+    pub fn terminate_and_wait_ack(&self, timeout: Duration) -> bool {
+        self.terminate();
+        status::wait_finished(slice::from_ref(&self._thread), &self._flags, timeout)
+    }
+
+    /// Signal termination and join the thread, giving it `grace` to finish
+    ///
+    /// A thread still inside a `Token::uninterruptible` section once `grace`
+    /// has passed isn't counted as hung, and is waited for until it leaves it.
+    ///
+    /// # Errors
+    ///
+    /// Gives the handle back, without joining, if the thread was still running
+    /// outside a critical section after `grace`
+    pub fn shutdown(self, grace: Duration) -> Result<Result<T, JoinError>, Self> {
+        self.terminate();
+
+        if !status::wait_shutdown(slice::from_ref(&self._thread), &self._flags, grace) {
+            return Err(self);
+        }
+
+        Ok(self.join(false))
+    }
+
+    /// Check whether the thread has finished running
+    pub fn is_finished(&self) -> bool {
+        self._thread.is_finished()
+    }
+
+    /// Current status of the thread
+    pub fn status(&self) -> ThreadStatus {
+        self._flags
+            .status(0, self.is_finished())
+            .expect("the handle's thread is the set's only member")
+    }
+
+    /// Milestones recorded for the thread, such as when it was signalled and when it finished
+    pub fn metrics(&self) -> ThreadMetrics {
+        self._flags.metrics(0).unwrap_or_default()
+    }
+
+    /// OS id of the thread, e.g. to find it in `top -H`, eBPF traces or a profiler
+    ///
+    /// `None` until the thread has started. `std::thread::ThreadId` has no
+    /// relation to the OS id.
+    ///
+    /// Only available on Linux with the `unix` feature enabled, or on Windows
+    /// with the `windows` feature enabled.
+    #[cfg(any(
+        all(feature = "unix", target_os = "linux"),
+        all(feature = "windows", windows)
+    ))]
+    pub fn os_thread_id(&self) -> Option<u64> {
+        self._flags.os_thread_id(0)
+    }
+
+    /// The most recent lifecycle events of the thread, oldest first
+    ///
+    /// Records when the thread was spawned, signalled to terminate, and finished
+    /// or panicked, to reconstruct the order of a shutdown after the fact.
+    /// Only the most recent few hundred events are kept.
+    pub fn events(&self) -> Vec<LifecycleEvent> {
+        self._flags.events()
+    }
+
+    /// Snapshot of the thread, e.g. to expose over an admin endpoint
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::of(slice::from_ref(&self._thread), &self._flags)
+    }
+
+    /// The underlying `std` thread, e.g. to read its name or unpark it
+    pub fn thread(&self) -> &Thread {
+        self._thread.thread()
+    }
+
+    /// Signal termination, then forcefully stop the thread if it is still running and join it
+    ///
+    /// A last resort for a thread that ignores termination, e.g. inside
+    /// third-party code, typically after `shutdown` gave the handle back. The
+    /// thread is stopped with `TerminateThread` and joined with a
+    /// `ForceAborted` error, unless it already finished.
+    ///
+    /// Only available on Windows, with the `windows` feature enabled.
+    ///
+    /// # Safety
+    ///
+    /// The thread is stopped wherever it is, without unwinding: no destructors
+    /// run, locks it holds are never released and memory it owns is leaked.
+    /// This includes locks inside the allocator, `std::io::stdout` and any
+    /// other library code, so the rest of the process may deadlock or see
+    /// half-updated state afterwards. The caller must be sure the thread can't
+    /// be in such a section, e.g. because it only runs a known computation.
+    /// Prefer `shutdown` with a grace period, and only use this when the
+    /// process is exiting anyway.
+    #[cfg(all(feature = "windows", windows))]
+    pub unsafe fn force_abort(self) -> Result<T, JoinError> {
+        self.terminate();
+
+        // SAFETY: upheld by the caller
+        unsafe { abort::join_or_abort(self._thread) }
+    }
+
+    /// Join the thread, optionally signalling termination
+    pub fn join(self, signal_terminate: bool) -> Result<T, JoinError> {
+        if signal_terminate {
+            self.terminate();
+        }
+
+        self._thread.join()
+    }
+}
+
+impl<T> Terminate for TerminableThreadHandle<T> {
+    fn terminate(&self) {
+        TerminableThreadHandle::terminate(self);
+    }
+}
+
+impl<T> fmt::Debug for TerminableThreadHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.snapshot().debug_container(f, "TerminableThreadHandle")
+    }
+}
+
+impl<T> fmt::Display for TerminableThreadHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.snapshot().threads.first() {
+            Some(thread) => write!(f, "TerminableThreadHandle ({thread})"),
+            None => write!(f, "TerminableThreadHandle"),
+        }
+    }
+}
+
+impl<T> AsRef<FlagSet> for TerminableThreadHandle<T> {
+    fn as_ref(&self) -> &FlagSet {
+        &self._flags
+    }
+}
+
+impl<T> Join for TerminableThreadHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn join(self) -> Self::Output {
+        TerminableThreadHandle::join(self, false)
+    }
+}
+
+/// Gives access to the thread's `pthread_t`, for OS-specific operations the crate doesn't wrap
+///
+/// Only available on Linux, with the `unix` feature enabled.
+#[cfg(all(feature = "unix", target_os = "linux"))]
+impl<T> JoinHandleExt for TerminableThreadHandle<T> {
+    fn as_pthread_t(&self) -> RawPthread {
+        self._thread.as_pthread_t()
+    }
+
+    /// Consume the handle without joining the thread, which can no longer be terminated through it
+    fn into_pthread_t(self) -> RawPthread {
+        self._thread.into_pthread_t()
+    }
+}
+
+/// Gives access to the thread's `HANDLE`, for OS-specific operations the crate doesn't wrap
+///
+/// Only available on Windows, with the `windows` feature enabled.
+#[cfg(all(feature = "windows", windows))]
+impl<T> AsRawHandle for TerminableThreadHandle<T> {
+    fn as_raw_handle(&self) -> RawHandle {
+        self._thread.as_raw_handle()
+    }
+}
+
+#[cfg(all(feature = "windows", windows))]
+impl<T> IntoRawHandle for TerminableThreadHandle<T> {
+    /// Consume the handle without joining the thread, which can no longer be terminated through it
+    fn into_raw_handle(self) -> RawHandle {
+        self._thread.into_raw_handle()
+    }
+}
+
+impl<T> HealthSource for TerminableThreadHandle<T> {
+    fn healthy(&self) -> bool {
+        !self.shutting_down() && !self.is_finished()
+    }
+
+    fn shutting_down(&self) -> bool {
+        self._flags.is_terminated()
+    }
+}
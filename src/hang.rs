@@ -0,0 +1,107 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::{status, FlagSet};
+
+type StallCallback = Box<dyn FnMut(&[StalledThread])>;
+
+/// Settings for `join_with_hang_detection`, telling slow threads from stuck ones
+///
+/// While joining, every unfinished thread's heartbeat is sampled once per
+/// interval. A thread whose heartbeat is older than the stall threshold is
+/// reported to the callback, once per stall: it is reported again only after
+/// it has beaten in between.
+pub struct HangDetection {
+    stall_after: Duration,
+    interval: Duration,
+    on_stall: StallCallback,
+}
+
+impl HangDetection {
+    /// Report threads whose heartbeat has been quiet for longer than `stall_after`
+    ///
+    /// Stalls are ignored until a callback is set with `on_stall`.
+    pub fn new(stall_after: Duration) -> Self {
+        Self {
+            stall_after,
+            interval: Duration::from_secs(1),
+            on_stall: Box::new(|_| {}),
+        }
+    }
+
+    /// Set how often the threads are sampled, once a second by default
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Call `f` with the threads that newly stalled, every time some do
+    pub fn on_stall<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(&[StalledThread]) + 'static,
+    {
+        self.on_stall = Box::new(f);
+        self
+    }
+
+    /// Block until every thread has finished, reporting stalls along the way
+    pub(crate) fn wait<T>(mut self, threads: &[JoinHandle<T>], flags: &FlagSet) {
+        let mut reported = HashSet::new();
+
+        loop {
+            let mut stalled = Vec::new();
+
+            for (index, thread) in threads.iter().enumerate() {
+                if thread.is_finished() {
+                    reported.remove(&index);
+                    continue;
+                }
+
+                let Some(heartbeat_age) = flags.heartbeat(index).map(|heartbeat| heartbeat.age())
+                else {
+                    continue;
+                };
+
+                if heartbeat_age <= self.stall_after {
+                    reported.remove(&index);
+                } else if reported.insert(index) {
+                    stalled.push(StalledThread {
+                        index,
+                        name: thread.thread().name().map(String::from),
+                        heartbeat_age,
+                    });
+                }
+            }
+
+            if !stalled.is_empty() {
+                (self.on_stall)(&stalled);
+            }
+
+            if status::wait_finished(threads, flags, self.interval) {
+                return;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for HangDetection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HangDetection")
+            .field("stall_after", &self.stall_after)
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A thread whose heartbeat went quiet while it was being joined
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StalledThread {
+    /// Index of the thread within its container
+    pub index: usize,
+    /// Name of the thread, if it has one
+    pub name: Option<String>,
+    /// Time since the thread last beat when it was sampled
+    pub heartbeat_age: Duration,
+}
@@ -0,0 +1,50 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::pool::lock;
+
+/// A timestamp a worker refreshes periodically to show it is still making progress
+///
+/// Cheap to clone; all clones refer to the same timestamp. A worker calls
+/// `beat` at points where it is known to be progressing, e.g. once per loop
+/// iteration, and its container can then tell a busy worker from a wedged
+/// one by how long ago it last did so.
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    last: Arc<Mutex<Instant>>,
+}
+
+impl Heartbeat {
+    /// Create a heartbeat, counting as having just beaten
+    pub fn new() -> Self {
+        Self {
+            last: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Record that the worker is still making progress
+    pub fn beat(&self) {
+        *lock(&self.last) = Instant::now();
+    }
+
+    /// When the worker last beat, or when the heartbeat was created if it never has
+    pub fn last_beat(&self) -> Instant {
+        *lock(&self.last)
+    }
+
+    /// Time since the last beat
+    pub fn age(&self) -> Duration {
+        self.last_beat().elapsed()
+    }
+
+    /// Check whether the last beat is older than `max_age`
+    pub fn is_stale(&self, max_age: Duration) -> bool {
+        self.age() > max_age
+    }
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,109 @@
+use std::os::unix::thread::JoinHandleExt;
+use std::thread::JoinHandle;
+
+/// Interrupt whatever blocking syscall `thread` is in, so it fails with `EINTR`
+///
+/// Does nothing if another handler for `SIGURG` was installed first, or on an
+/// architecture whose signals aren't laid out as expected.
+pub(crate) fn interrupt<T>(thread: &JoinHandle<T>) {
+    if !thread.is_finished() {
+        os::interrupt(thread.as_pthread_t());
+    }
+}
+
+/// A no-op `SIGURG` handler, through `sigaction` and `pthread_kill`
+///
+/// Only for architectures with the generic signal numbers and `sigaction`
+/// layout.
+#[cfg(any(
+    target_arch = "x86",
+    target_arch = "x86_64",
+    target_arch = "arm",
+    target_arch = "aarch64",
+    target_arch = "riscv64"
+))]
+mod os {
+    use std::os::raw::c_int;
+    use std::os::unix::thread::RawPthread;
+    use std::ptr;
+    use std::sync::OnceLock;
+
+    /// `SIGURG`, ignored by default, so a stray one does no harm
+    const SIGURG: c_int = 23;
+
+    /// `SIG_DFL`, the default disposition
+    const DEFAULT_HANDLER: usize = 0;
+
+    /// `struct sigaction`, without `SA_RESTART` so interrupted syscalls fail with `EINTR`
+    #[repr(C)]
+    struct SigAction {
+        handler: usize,
+        mask: [u64; 16],
+        flags: c_int,
+        restorer: usize,
+    }
+
+    extern "C" {
+        fn sigaction(signum: c_int, act: *const SigAction, old: *mut SigAction) -> c_int;
+        fn pthread_kill(thread: RawPthread, sig: c_int) -> c_int;
+    }
+
+    /// Whether the no-op handler was installed, which is only done if nothing else handles the signal
+    static INSTALLED: OnceLock<bool> = OnceLock::new();
+
+    fn install() -> bool {
+        *INSTALLED.get_or_init(|| {
+            let mut old = SigAction {
+                handler: DEFAULT_HANDLER,
+                mask: [0; 16],
+                flags: 0,
+                restorer: 0,
+            };
+
+            // SAFETY: querying the current disposition only writes to `old`
+            if unsafe { sigaction(SIGURG, ptr::null(), &mut old) } != 0
+                || old.handler != DEFAULT_HANDLER
+            {
+                return false;
+            }
+
+            let action = SigAction {
+                handler: on_interrupt as extern "C" fn(c_int) as usize,
+                mask: [0; 16],
+                flags: 0,
+                restorer: 0,
+            };
+
+            // SAFETY: `on_interrupt` does nothing, so is async-signal-safe
+            unsafe { sigaction(SIGURG, &action, ptr::null_mut()) == 0 }
+        })
+    }
+
+    /// `thread` must be running, i.e. not yet joined
+    pub(super) fn interrupt(thread: RawPthread) {
+        if !install() {
+            return;
+        }
+
+        // SAFETY: callers pass a running thread, and the signal is handled by a
+        // no-op. Failing to deliver it only means the thread isn't interrupted.
+        unsafe { pthread_kill(thread, SIGURG) };
+    }
+
+    extern "C" fn on_interrupt(_signum: c_int) {}
+}
+
+/// Nothing, as MIPS, SPARC, Alpha and others number their signals differently
+/// and lay `sigaction` out in another order
+#[cfg(not(any(
+    target_arch = "x86",
+    target_arch = "x86_64",
+    target_arch = "arm",
+    target_arch = "aarch64",
+    target_arch = "riscv64"
+)))]
+mod os {
+    use std::os::unix::thread::RawPthread;
+
+    pub(super) fn interrupt(_thread: RawPthread) {}
+}
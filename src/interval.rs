@@ -0,0 +1,185 @@
+use std::sync::atomic::{self, AtomicBool};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::supervisor::random_unit;
+use crate::{spawn_terminable, TerminableThreadHandle};
+
+/// Spawn a thread running `f` every `period`, until terminated
+///
+/// The first run happens immediately. Between runs the thread is parked, and
+/// since terminating a `TerminableThreadHandle` also unparks its thread, it
+/// stops without waiting out the rest of the period.
+///
+/// Runs are scheduled at fixed intervals from the start, so a slow run
+/// shortens the wait before the next one rather than shifting every later run.
+/// See `IntervalBuilder` for jitter and other ways of handling slow runs.
+///
+/// # Panics
+///
+/// If `period` is zero
+pub fn spawn_interval<F>(period: Duration, f: F) -> TerminableThreadHandle<()>
+where
+    F: FnMut() + Send + 'static,
+{
+    IntervalBuilder::new(period).spawn(f)
+}
+
+/// What an interval runner does when a run overruns one or more ticks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MissedTickPolicy {
+    /// Run the missed ticks back to back until caught up with the schedule
+    #[default]
+    Burst,
+    /// Drop the missed ticks and wait for the next one on the original schedule
+    Skip,
+    /// Shift the schedule, waiting a full period from the end of the late run
+    Delay,
+}
+
+impl MissedTickPolicy {
+    /// Compute when to run next, given when the last run was due
+    ///
+    /// `period` must be non-zero, which the interval builder and scheduler
+    /// check up front.
+    pub(crate) fn next(self, due: Instant, period: Duration, now: Instant) -> Instant {
+        let next = due + period;
+
+        if next >= now {
+            return next;
+        }
+
+        match self {
+            MissedTickPolicy::Burst => next,
+            MissedTickPolicy::Skip => {
+                let missed = (now - next).as_nanos() / period.as_nanos() + 1;
+
+                next + period.saturating_mul(u32::try_from(missed).unwrap_or(u32::MAX))
+            }
+            MissedTickPolicy::Delay => now + period,
+        }
+    }
+}
+
+/// Builder for a thread running a task at a fixed interval, with optional jitter
+#[derive(Debug, Clone)]
+pub struct IntervalBuilder {
+    period: Duration,
+    jitter: Duration,
+    missed_tick_policy: MissedTickPolicy,
+}
+
+impl IntervalBuilder {
+    /// Create a builder for running a task every `period`
+    ///
+    /// # Panics
+    ///
+    /// If `period` is zero, which would keep the thread running `f` back to back
+    pub fn new(period: Duration) -> Self {
+        assert!(!period.is_zero(), "interval needs a non-zero period");
+
+        Self {
+            period,
+            jitter: Duration::ZERO,
+            missed_tick_policy: MissedTickPolicy::default(),
+        }
+    }
+
+    /// Delay each run by a random amount of up to `jitter`
+    ///
+    /// Spreads out runners started at the same time with the same period. The
+    /// jitter is applied to each run separately, so it doesn't accumulate.
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set what happens when a run overruns one or more ticks
+    pub fn missed_tick_policy(mut self, policy: MissedTickPolicy) -> Self {
+        self.missed_tick_policy = policy;
+        self
+    }
+
+    /// Spawn the thread running `f`, the first run happening immediately
+    pub fn spawn<F>(self, mut f: F) -> TerminableThreadHandle<()>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        spawn_terminable(move |flag| {
+            let mut due = Instant::now();
+
+            loop {
+                let offset = self.jitter.mul_f64(random_unit());
+
+                if park_until(&flag, due + offset) {
+                    break;
+                }
+
+                f();
+                due = self
+                    .missed_tick_policy
+                    .next(due, self.period, Instant::now());
+            }
+        })
+    }
+}
+
+/// Park the current thread until `deadline` passes or `flag` is raised
+///
+/// # Returns
+///
+/// `true` if woken by `flag`
+pub(crate) fn park_until(flag: &AtomicBool, deadline: Instant) -> bool {
+    loop {
+        if flag.load(atomic::Ordering::SeqCst) {
+            return true;
+        }
+
+        match deadline.checked_duration_since(Instant::now()) {
+            Some(remaining) if !remaining.is_zero() => thread::park_timeout(remaining),
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "non-zero period")]
+    fn zero_period_is_rejected() {
+        IntervalBuilder::new(Duration::ZERO);
+    }
+
+    #[test]
+    #[should_panic(expected = "non-zero period")]
+    fn spawn_interval_rejects_a_zero_period() {
+        spawn_interval(Duration::ZERO, || {});
+    }
+
+    #[test]
+    fn missed_tick_policies() {
+        let start = Instant::now();
+        let period = Duration::from_secs(10);
+        let late = start + Duration::from_secs(25);
+
+        let next = |policy: MissedTickPolicy, now| policy.next(start, period, now);
+
+        // On schedule, every policy waits for the next tick
+        for policy in [
+            MissedTickPolicy::Burst,
+            MissedTickPolicy::Skip,
+            MissedTickPolicy::Delay,
+        ] {
+            assert_eq!(next(policy, start + Duration::from_secs(3)), start + period);
+        }
+
+        assert_eq!(next(MissedTickPolicy::Burst, late), start + period);
+        assert_eq!(
+            next(MissedTickPolicy::Skip, late),
+            start + Duration::from_secs(30)
+        );
+        assert_eq!(next(MissedTickPolicy::Delay, late), late + period);
+    }
+}
@@ -1,17 +1,163 @@
 use std::any::Any;
-use std::fmt::Debug;
-use std::marker::PhantomData;
-use std::sync::atomic::{self, AtomicBool};
+use std::array;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::io;
+#[cfg(all(feature = "unix", target_os = "linux"))]
+use std::os::unix::thread::{JoinHandleExt, RawPthread};
+#[cfg(all(feature = "windows", windows))]
+use std::os::windows::io::{AsRawHandle, RawHandle};
+#[cfg(all(feature = "unix", target_os = "linux"))]
+use std::sync::atomic;
+use std::sync::atomic::AtomicBool;
+#[cfg(all(feature = "unix", target_os = "linux"))]
+use std::sync::mpsc;
 use std::sync::Arc;
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+#[cfg(all(feature = "windows", windows))]
+mod abort;
+#[cfg(any(
+    all(feature = "unix", target_os = "linux"),
+    all(feature = "windows", windows)
+))]
+mod affinity;
+mod cancel;
+mod channel;
+mod chunked;
+mod command;
+mod config;
+mod coordinator;
+mod copy;
+mod events;
+mod exit;
+mod flags;
+mod fuel;
+mod group;
+mod handle;
+mod hang;
+mod heartbeat;
+#[cfg(all(feature = "unix", target_os = "linux"))]
+mod interrupt;
+mod interval;
+mod map;
+mod metrics;
+#[cfg(any(
+    all(feature = "unix", target_os = "linux"),
+    all(feature = "windows", windows)
+))]
+mod native;
+mod parallel;
+mod pipeline;
+mod pool;
+#[cfg(any(
+    all(feature = "unix", target_os = "linux"),
+    all(feature = "windows", windows)
+))]
+mod priority;
+mod process;
+mod ready;
+mod registry;
+mod restartable;
+mod results;
+mod runtime;
+mod scheduler;
+mod scope;
+#[cfg(any(
+    all(feature = "unix", target_os = "linux"),
+    all(feature = "windows", windows)
+))]
+mod signal;
+mod snapshot;
+mod status;
+#[cfg(all(feature = "unix", target_os = "linux"))]
+mod stdin;
+mod supervisor;
+mod sync;
+mod token;
+mod traits;
+mod watchdog;
+mod watcher;
+
+use config::ThreadConfig;
+use interval::park_until;
+
+/// The error returned when joining a thread that panicked, carrying the panic payload
+pub type JoinError = Box<dyn Any + Send + 'static>;
+
+#[cfg(all(feature = "windows", windows))]
+pub use abort::ForceAborted;
+#[cfg(any(
+    all(feature = "unix", target_os = "linux"),
+    all(feature = "windows", windows)
+))]
+pub use affinity::{numa_nodes, CorePinning};
+#[cfg(all(feature = "unix", target_os = "linux"))]
+pub use cancel::CancelPipe;
+pub use cancel::{CancelIo, IoRegistration};
+pub use channel::{
+    recv_or_terminated, terminable_channel, ClosePolicy, PushError, RecvError, TerminableReceiver,
+    TerminableSender, WorkChannel,
+};
+pub use chunked::{ChunkedReport, ChunkedRunner, JobState};
+pub use command::{CommandOutput, CommandWorker};
+pub use coordinator::{PhaseReport, ShutdownCoordinator};
+pub use copy::{terminable_copy, CopyReport};
+pub use events::{EventKind, LifecycleEvent};
+pub use exit::{ExitCodeFold, ToExitCode, PANIC_EXIT_CODE};
+pub use flags::{link, FlagSet};
+pub use fuel::{FuelError, FuelLimiter};
+pub use group::{ScopedThreadGroup, TerminableThreadGroup};
+pub use handle::{spawn_after, spawn_terminable, TerminableThreadHandle};
+pub use hang::{HangDetection, StalledThread};
+pub use heartbeat::Heartbeat;
+pub use interval::{spawn_interval, IntervalBuilder, MissedTickPolicy};
+pub use map::TerminableThreadMap;
+pub use metrics::ThreadMetrics;
+pub use parallel::{
+    terminable_for_each, terminable_map, terminable_reduce, ForEachReport, ForEachTracker,
+    MapResult, ReduceHandle, ReduceResult, ResultCollector,
+};
+pub use pipeline::{fan_in, fan_out, Pipeline, PipelineBuilder};
+pub use pool::{
+    JobError, JobHandle, PendingJob, PoolStats, Priority, TerminablePool, TerminablePoolBuilder,
+};
+#[cfg(any(
+    all(feature = "unix", target_os = "linux"),
+    all(feature = "windows", windows)
+))]
+pub use priority::ThreadPriority;
+pub use process::TerminableProcessGroup;
+pub use ready::ReadySignal;
+pub use registry::{is_shutting_down, register, register_shared, shutdown_all};
+pub use restartable::RestartableThread;
+pub use results::{partition_results, JoinAllError, ThreadFailure};
+pub use runtime::{Runtime, RuntimeConfig, ShutdownReport, Worker, WorkerReport};
+pub use scheduler::{CronExpr, ParseCronError, Schedule, Scheduler};
+pub use scope::{scope, TerminableScope};
+#[cfg(any(
+    all(feature = "unix", target_os = "linux"),
+    all(feature = "windows", windows)
+))]
+pub use signal::terminate_on_signal;
+pub use snapshot::{Snapshot, ThreadSnapshot};
+pub use status::{GroupStats, ThreadOutcome, ThreadStatus, UnfinishedThread};
+#[cfg(all(feature = "unix", target_os = "linux"))]
+pub use stdin::spawn_stdin_reader;
+pub use supervisor::{Backoff, RestartPolicy, Supervisor, SupervisorBuilder};
+pub use sync::{TerminableCondvar, WaitGroup, WaitOutcome};
+pub use token::{SleepOutcome, StopReason, TimedReport, Token};
+pub use traits::{join_all, HealthSource, Join, Terminate};
+pub use watchdog::{resident_memory, MemoryWatchdog};
+pub use watcher::{spawn_fs_watcher, FsEvent};
 
 /// A basic thread manager that can signal all threads to terminate / finish early
 ///
 /// Note that threads will only terminate if the `Arc<AtomicBool>` flag is used
-#[derive(Debug)]
 pub struct TerminableThreads<T, const N: usize> {
     pub(crate) _threads: [JoinHandle<T>; N],
-    pub(crate) _terminate_flag: Arc<AtomicBool>,
+    pub(crate) _flags: FlagSet,
 }
 
 impl<T, const N: usize> TerminableThreads<T, N> {
@@ -21,15 +167,190 @@ impl<T, const N: usize> TerminableThreads<T, N> {
 
     /// Signal all threads to terminate and cease operation
     ///
+    /// The threads are also unparked, so those waiting in `std::thread::park`
+    /// (or `park_timeout`) can notice the flag straight away.
+    ///
     /// ## Note
     ///
     /// This does not guarantee all threads will terminate, or can be terminated.
     ///
     /// Threads will only terminate if the underlying function checks the flag passed to it.s
     pub fn terminate(&self) {
-        self._terminate_flag
-            .as_ref()
-            .store(true, atomic::Ordering::SeqCst);
+        self._flags.terminate();
+
+        for thread in &self._threads {
+            self._flags.wake(thread);
+        }
+    }
+
+    /// Signal each thread whose position in `mask` is `true` to terminate
+    ///
+    /// ## Note
+    ///
+    /// Only threads using their own flag (see `TerminableThreadsBuilder::thread_flag`)
+    /// can be terminated individually, threads using the shared flag only stop
+    /// on `terminate`.
+    pub fn terminate_each(&self, mask: [bool; N]) {
+        self._flags.terminate_each(mask);
+
+        for (thread, _) in self._threads.iter().zip(mask).filter(|(_, raise)| *raise) {
+            self._flags.wake(thread);
+        }
+    }
+
+    /// Signal termination, then wait up to `timeout` for every thread to acknowledge it
+    ///
+    /// A thread acknowledges termination by returning from its function, so
+    /// this tells whether the workers actually listened to the flag.
+    ///
+    /// # Returns
+    ///
+    /// `true` if every thread finished within `timeout`
+    pub fn terminate_and_wait_ack(&self, timeout: Duration) -> bool {
+        self.terminate();
+        status::wait_finished(&self._threads, &self._flags, timeout)
+    }
+
+    /// Signal termination and join all threads, reporting any that ignore it
+    ///
+    /// If some threads are still running `patience` after termination was
+    /// signalled, they are passed to `report` before joining carries on, so
+    /// threads that never check their flag don't make the join hang silently.
+    pub fn terminate_with_watchdog<R>(
+        self,
+        patience: Duration,
+        report: R,
+    ) -> [Result<T, JoinError>; N]
+    where
+        R: FnOnce(Vec<UnfinishedThread>),
+    {
+        self.terminate();
+        status::report_unfinished(&self._threads, &self._flags, patience, report);
+
+        self.join(false)
+    }
+
+    /// Signal termination and join all threads, giving them `grace` to finish
+    ///
+    /// Threads still inside a `Token::uninterruptible` section once `grace`
+    /// has passed aren't counted as hung, and are waited for until they leave it.
+    ///
+    /// # Errors
+    ///
+    /// Gives the threads back, without joining, if some were still running
+    /// outside a critical section after `grace`
+    pub fn shutdown(self, grace: Duration) -> Result<[Result<T, JoinError>; N], Self> {
+        self.terminate();
+
+        if !status::wait_shutdown(&self._threads, &self._flags, grace) {
+            return Err(self);
+        }
+
+        Ok(self.join(false))
+    }
+
+    /// The `pthread_t` of the thread at `index`, `None` if out of bounds
+    ///
+    /// For OS-specific operations the crate doesn't wrap. The container still
+    /// owns the thread: it must not be joined or detached through the id, and
+    /// the id is only valid until the container is joined.
+    ///
+    /// Only available on Linux, with the `unix` feature enabled.
+    #[cfg(all(feature = "unix", target_os = "linux"))]
+    pub fn as_pthread_t(&self, index: usize) -> Option<RawPthread> {
+        Some(self._threads.get(index)?.as_pthread_t())
+    }
+
+    /// The Windows `HANDLE` of the thread at `index`, `None` if out of bounds
+    ///
+    /// For OS-specific operations the crate doesn't wrap. The container still
+    /// owns the handle: it must not be closed, and is only valid until the
+    /// container is joined.
+    ///
+    /// Only available on Windows, with the `windows` feature enabled.
+    #[cfg(all(feature = "windows", windows))]
+    pub fn as_raw_handle(&self, index: usize) -> Option<RawHandle> {
+        Some(self._threads.get(index)?.as_raw_handle())
+    }
+
+    /// Current status of the thread at `index`, `None` if out of bounds
+    pub fn status(&self, index: usize) -> Option<ThreadStatus> {
+        self._flags
+            .status(index, self._threads.get(index)?.is_finished())
+    }
+
+    /// Snapshot of every thread's status, in index order
+    ///
+    /// Threads passed to `build_with_threads` already running are never
+    /// reported as `Spawned`, and their outcome is `Unknown` until joined.
+    pub fn statuses(&self) -> Vec<ThreadStatus> {
+        status::snapshot(&self._threads, &self._flags)
+    }
+
+    /// Take a snapshot of how many threads are running, finished and panicked
+    pub fn stats(&self) -> GroupStats {
+        status::stats(&self._threads, &self._flags)
+    }
+
+    /// Indices of the running threads whose last heartbeat is older than `max_age`
+    ///
+    /// Threads refresh their heartbeat through the handle from
+    /// `TerminableThreadsBuilder::heartbeat`. Threads that don't use it count
+    /// as having last beaten when the builder was created, and finished
+    /// threads are never listed.
+    pub fn stale_threads(&self, max_age: Duration) -> Vec<usize> {
+        status::stale_threads(&self._threads, &self._flags, max_age)
+    }
+
+    /// Milestones recorded for each thread, such as when it was signalled and when it finished
+    ///
+    /// Spawn and finish times are only recorded for threads spawned by the
+    /// builder, not ones passed to `build_with_threads`.
+    pub fn metrics(&self) -> [ThreadMetrics; N] {
+        array::from_fn(|index| self._flags.metrics(index).unwrap_or_default())
+    }
+
+    /// OS id of each thread, e.g. to find it in `top -H`, eBPF traces or a profiler
+    ///
+    /// Only known for threads spawned by the builder, once they have
+    /// started. `std::thread::ThreadId` has no relation to the OS id.
+    ///
+    /// Only available on Linux with the `unix` feature enabled, or on Windows
+    /// with the `windows` feature enabled.
+    #[cfg(any(
+        all(feature = "unix", target_os = "linux"),
+        all(feature = "windows", windows)
+    ))]
+    pub fn os_thread_ids(&self) -> [Option<u64>; N] {
+        array::from_fn(|index| self._flags.os_thread_id(index))
+    }
+
+    /// Snapshot of the container and every thread, e.g. to expose over an admin endpoint
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::of(&self._threads, &self._flags)
+    }
+
+    /// The most recent lifecycle events of the threads, oldest first
+    ///
+    /// Records when threads were spawned, signalled to terminate, and finished
+    /// or panicked, to reconstruct the order of a shutdown after the fact.
+    /// Only the most recent few hundred events are kept.
+    pub fn events(&self) -> Vec<LifecycleEvent> {
+        self._flags.events()
+    }
+
+    /// Block until every thread has signalled it is ready, or `timeout` passes
+    ///
+    /// Threads signal readiness through the latch from
+    /// `TerminableThreadsBuilder::ready_signal`. Threads that don't use it
+    /// never become ready.
+    ///
+    /// # Returns
+    ///
+    /// `true` if every thread became ready in time, otherwise the caller may
+    /// want to `terminate`
+    pub fn wait_ready(&self, timeout: Duration) -> bool {
+        self._flags.wait_ready(timeout)
     }
 
     /// Join all threads, optionally signalling termination
@@ -41,17 +362,142 @@ impl<T, const N: usize> TerminableThreads<T, N> {
     /// # Returns
     ///
     /// `[Result<T, Error>; N]`
-    /// 
+    ///
     /// An array of length N containing the results of joining each thread
-    pub fn join(
+    pub fn join(self, signal_terminate: bool) -> [Result<T, JoinError>; N] {
+        if signal_terminate {
+            self.terminate();
+        }
+
+        self._threads.map(JoinHandle::join)
+    }
+
+    /// Join all threads like `join`, reporting threads that stall along the way
+    ///
+    /// Rather than blocking opaquely, the join samples the threads' heartbeats
+    /// as configured by `detection`, so a slow thread that keeps beating can be
+    /// told apart from one that is stuck.
+    pub fn join_with_hang_detection(
         self,
         signal_terminate: bool,
-    ) -> [Result<T, Box<dyn Any + Send + 'static>>; N] {
+        detection: HangDetection,
+    ) -> [Result<T, JoinError>; N] {
         if signal_terminate {
             self.terminate();
         }
 
-        self._threads.map(JoinHandle::join)
+        detection.wait(&self._threads, &self._flags);
+
+        self.join(false)
+    }
+
+    /// Join all threads like `join`, folding their return values into a process exit code
+    ///
+    /// Threads that panicked count as `PANIC_EXIT_CODE`. The result can be
+    /// passed to `std::process::exit` to surface worker failure to the shell.
+    pub fn join_exit_code(self, signal_terminate: bool, fold: ExitCodeFold) -> i32
+    where
+        T: ToExitCode,
+    {
+        fold.fold(self.join(signal_terminate))
+    }
+
+    /// Join all threads like `join`, keying each result by its thread's name
+    ///
+    /// Threads are named when spawned by the builder with a `name_pattern`.
+    /// Threads without a name are keyed by their index.
+    pub fn join_named(self, signal_terminate: bool) -> HashMap<String, Result<T, JoinError>> {
+        self.into_group().join_named(signal_terminate)
+    }
+
+    /// Signal termination, then forcefully stop the threads still running and join them all
+    ///
+    /// A last resort for threads that ignore termination, e.g. inside
+    /// third-party code, typically after `shutdown` gave the container back.
+    /// Threads are stopped with `TerminateThread` and joined with a
+    /// `ForceAborted` error, while threads that already finished are joined
+    /// as usual.
+    ///
+    /// Only available on Windows, with the `windows` feature enabled.
+    ///
+    /// # Safety
+    ///
+    /// Each thread is stopped wherever it is, without unwinding: no destructors
+    /// run, locks they hold are never released and memory they own is leaked.
+    /// This includes locks inside the allocator, `std::io::stdout` and any
+    /// other library code, so the rest of the process may deadlock or see
+    /// half-updated state afterwards. The caller must be sure no thread can
+    /// be in such a section, e.g. because they only run a known computation.
+    /// Prefer `shutdown` with a grace period, and only use this when the
+    /// process is exiting anyway.
+    #[cfg(all(feature = "windows", windows))]
+    pub unsafe fn force_abort(self) -> [Result<T, JoinError>; N] {
+        self.terminate();
+
+        // SAFETY: upheld by the caller
+        self._threads
+            .map(|thread| unsafe { abort::join_or_abort(thread) })
+    }
+
+    /// Join all threads like `join`, succeeding only if none of them panicked
+    ///
+    /// # Errors
+    ///
+    /// A `JoinAllError` describing every thread that panicked
+    pub fn join_all_ok(self, signal_terminate: bool) -> Result<[T; N], JoinAllError> {
+        let values = self.into_group().join_all_ok(signal_terminate)?;
+
+        match values.try_into() {
+            Ok(values) => Ok(values),
+            Err(_) => unreachable!("exactly N threads were joined"),
+        }
+    }
+
+    /// Convert into a growable `TerminableThreadGroup`, keeping each thread's flag
+    pub fn into_group(self) -> TerminableThreadGroup<T> {
+        self.into()
+    }
+}
+
+impl<T, const N: usize> Terminate for TerminableThreads<T, N> {
+    fn terminate(&self) {
+        TerminableThreads::terminate(self);
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for TerminableThreads<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.snapshot().debug_container(f, "TerminableThreads")
+    }
+}
+
+impl<T, const N: usize> fmt::Display for TerminableThreads<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TerminableThreads ({})", self.snapshot())
+    }
+}
+
+impl<T, const N: usize> AsRef<FlagSet> for TerminableThreads<T, N> {
+    fn as_ref(&self) -> &FlagSet {
+        &self._flags
+    }
+}
+
+impl<T, const N: usize> Join for TerminableThreads<T, N> {
+    type Output = [Result<T, JoinError>; N];
+
+    fn join(self) -> Self::Output {
+        TerminableThreads::join(self, false)
+    }
+}
+
+impl<T, const N: usize> HealthSource for TerminableThreads<T, N> {
+    fn healthy(&self) -> bool {
+        !self.shutting_down() && !self._threads.iter().any(JoinHandle::is_finished)
+    }
+
+    fn shutting_down(&self) -> bool {
+        self._flags.is_terminated()
     }
 }
 
@@ -59,31 +505,464 @@ impl<T, const N: usize> TerminableThreads<T, N> {
 ///
 /// The builder is necessary to provide the termination flag (`Arc<AtomicBool>`)
 /// for threads, that are later provided to the builder, to use.
+///
+/// Alternatively the builder can spawn the threads itself, either all at once
+/// with `build_with_funcs` or one at a time with `add_thread`, applying the
+/// configured thread names and stack size.
 #[derive(Debug)]
 pub struct TerminableThreadsBuilder<T, const N: usize> {
-    terminate_flag: Arc<AtomicBool>,
-    _marker: PhantomData<T>,
+    flags: FlagSet,
+    config: ThreadConfig,
+    dependencies: HashMap<usize, Vec<usize>>,
+    max_runtimes: HashMap<usize, Duration>,
+    #[cfg(all(feature = "unix", target_os = "linux"))]
+    cpu_budgets: HashMap<usize, Duration>,
+    #[cfg(all(feature = "unix", target_os = "linux"))]
+    niceness: Option<i32>,
+    #[cfg(any(
+        all(feature = "unix", target_os = "linux"),
+        all(feature = "windows", windows)
+    ))]
+    priorities: HashMap<usize, ThreadPriority>,
+    threads: Vec<JoinHandle<T>>,
 }
 
 impl<T, const N: usize> TerminableThreadsBuilder<T, N> {
     /// Create a new `TeminableThreadBuilder`
     pub fn new() -> (Self, Arc<AtomicBool>) {
-        let flag = Arc::new(AtomicBool::new(false));
+        let flags = FlagSet::with_len(N);
+        let flag = Arc::clone(flags.group_flag());
 
         (
             Self {
-                terminate_flag: Arc::clone(&flag),
-                _marker: PhantomData,
+                flags,
+                config: ThreadConfig::default(),
+                dependencies: HashMap::new(),
+                max_runtimes: HashMap::new(),
+                #[cfg(all(feature = "unix", target_os = "linux"))]
+                cpu_budgets: HashMap::new(),
+                #[cfg(all(feature = "unix", target_os = "linux"))]
+                niceness: None,
+                #[cfg(any(
+                    all(feature = "unix", target_os = "linux"),
+                    all(feature = "windows", windows)
+                ))]
+                priorities: HashMap::new(),
+                threads: Vec::new(),
             },
             flag,
         )
     }
 
+    /// Flag for the thread at `index` only, raised by `terminate` and by `terminate_each`
+    ///
+    /// Threads spawned by hand that use this instead of the shared flag can be
+    /// terminated individually. `build_with_funcs` does this automatically.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is not less than `N`
+    pub fn thread_flag(&self, index: usize) -> Arc<AtomicBool> {
+        let flag = self.flags.flag(index).expect("thread index out of bounds");
+        Arc::clone(flag)
+    }
+
+    /// Readiness latch for the thread at `index`, for its function to raise once set up
+    ///
+    /// Threads declared with `start_after` to depend on this one wait for it.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is not less than `N`
+    pub fn ready_signal(&self, index: usize) -> ReadySignal {
+        let ready = self
+            .flags
+            .ready_signal(index)
+            .expect("thread index out of bounds");
+
+        ready.clone()
+    }
+
+    /// Heartbeat for the thread at `index`, for its function to refresh while making progress
+    ///
+    /// Threads whose heartbeat has gone quiet are listed by
+    /// `TerminableThreads::stale_threads`.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is not less than `N`
+    pub fn heartbeat(&self, index: usize) -> Heartbeat {
+        let heartbeat = self
+            .flags
+            .heartbeat(index)
+            .expect("thread index out of bounds");
+
+        heartbeat.clone()
+    }
+
+    /// Iteration budget for the thread at `index`, raising only that thread's flag after `limit` ticks
+    ///
+    /// Give the limiter to the thread's function and call `tick` once per
+    /// loop iteration.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is not less than `N`
+    pub fn fuel_limiter(&self, index: usize, limit: u64) -> FuelLimiter {
+        FuelLimiter::new(self.thread_flag(index), limit)
+    }
+
+    /// Hold back the thread at `index` until the thread at `dependency` signals it is ready
+    ///
+    /// The dependency's function signals readiness through the latch from
+    /// `ready_signal(dependency)`. May be called several times to wait on more
+    /// than one thread. If termination is signalled while waiting, the
+    /// function is still called, with its flag raised, so it can return
+    /// promptly.
+    ///
+    /// ## Note
+    ///
+    /// A cycle of dependencies is never started, until terminated.
+    ///
+    /// # Panics
+    ///
+    /// If either index is not less than `N`
+    pub fn start_after(mut self, index: usize, dependency: usize) -> Self {
+        assert!(index < N && dependency < N, "thread index out of bounds");
+
+        self.dependencies.entry(index).or_default().push(dependency);
+        self
+    }
+
+    /// Signal the thread at `index` to terminate once its function has run for `limit`
+    ///
+    /// Only that thread's own flag is raised, and its status is reported as
+    /// `ThreadStatus::TimedOut` until it finishes. The time spent waiting for a
+    /// `stagger` delay or for dependencies doesn't count towards the limit.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is not less than `N`
+    pub fn max_runtime(mut self, index: usize, limit: Duration) -> Self {
+        assert!(index < N, "thread index out of bounds");
+
+        self.max_runtimes.insert(index, limit);
+        self
+    }
+
+    /// Signal the thread at `index` to terminate once its function has used `budget` of CPU time
+    ///
+    /// Catches threads spinning on an otherwise idle machine, which a
+    /// `max_runtime` generous enough for normal operation would let run for
+    /// long. As with `max_runtime`, only that thread's own flag is raised and
+    /// its status is reported as `ThreadStatus::TimedOut` until it finishes. The CPU
+    /// time is sampled every few milliseconds, so the thread may slightly
+    /// overrun its budget.
+    ///
+    /// Only available on Linux, with the `unix` feature enabled.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is not less than `N`
+    #[cfg(all(feature = "unix", target_os = "linux"))]
+    pub fn max_cpu_time(mut self, index: usize, budget: Duration) -> Self {
+        assert!(index < N, "thread index out of bounds");
+
+        self.cpu_budgets.insert(index, budget);
+        self
+    }
+
+    /// Set the OS scheduling priority of the thread at `index` once it is spawned
+    ///
+    /// e.g. so background workers don't compete with an interactive thread.
+    /// The priority is set from the spawning thread straight after the spawn,
+    /// so the thread may briefly run at the default priority first.
+    ///
+    /// Only available on Linux with the `unix` feature enabled, or on Windows
+    /// with the `windows` feature enabled.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is not less than `N`
+    #[cfg(any(
+        all(feature = "unix", target_os = "linux"),
+        all(feature = "windows", windows)
+    ))]
+    pub fn priority(mut self, index: usize, priority: ThreadPriority) -> Self {
+        assert!(index < N, "thread index out of bounds");
+
+        self.priorities.insert(index, priority);
+        self
+    }
+
+    /// Renice each spawned thread to `niceness`, from `-20` (most favourable) to `19`
+    ///
+    /// Lets long-running batch work yield to interactive processes without an
+    /// external `renice`. Each thread renices itself before doing anything
+    /// else, and `add_thread` waits for it to have done so. Values outside
+    /// the range are clamped.
+    ///
+    /// Only available on Linux, with the `unix` feature enabled.
+    #[cfg(all(feature = "unix", target_os = "linux"))]
+    pub fn with_niceness(mut self, niceness: i32) -> Self {
+        self.niceness = Some(niceness);
+        self
+    }
+
+    /// Name each spawned thread after `pattern`, with `{i}` replaced by the thread's index
+    ///
+    /// e.g. `"worker-{i}"` names the threads `worker-0`, `worker-1`, ...
+    pub fn name_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.config.name_pattern = Some(pattern.into());
+        self
+    }
+
+    /// Set the stack size, in bytes, of each spawned thread
+    pub fn stack_size(mut self, size: usize) -> Self {
+        self.config.stack_size = Some(size);
+        self
+    }
+
+    /// Start the spawned threads one after another, `interval` apart, instead of all at once
+    ///
+    /// The thread at index `i` is spawned straight away but waits `i * interval`
+    /// before running its function, spreading out e.g. connections to a shared
+    /// resource. If termination is signalled first, the function is still
+    /// called, with its flag raised, so it can return promptly.
+    pub fn stagger(mut self, interval: Duration) -> Self {
+        self.config.stagger = Some(interval);
+        self
+    }
+
+    /// Terminate every thread as soon as any spawned thread panics
+    ///
+    /// The shared flag is raised while the panicking thread unwinds, so its
+    /// siblings stop instead of running on without it, e.g. waiting forever
+    /// on a coordinator that has died. The panic still reaches `join` as an
+    /// `Err` for that thread.
+    ///
+    /// ## Note
+    ///
+    /// Only threads spawned by the builder are covered, not those passed to
+    /// `build_with_threads`. Parked threads aren't unparked.
+    pub fn terminate_on_panic(self) -> Self {
+        self.flags.set_terminate_on_panic(true);
+        self
+    }
+
+    /// Interrupt the blocking syscall a thread is in when it is terminated
+    ///
+    /// A no-op signal is sent to the thread, so e.g. a blocking `read` fails
+    /// with `EINTR` and the thread can check its flag. See
+    /// `FlagSet::set_interrupt_on_terminate` for the caveats.
+    ///
+    /// Only available on Linux, with the `unix` feature enabled.
+    #[cfg(all(feature = "unix", target_os = "linux"))]
+    pub fn interrupt_on_terminate(self) -> Self {
+        self.flags.set_interrupt_on_terminate(true);
+        self
+    }
+
     /// Transform the builder into a `TerminableThreads<T, N>` struct with the specified threads
     pub fn build_with_threads(self, threads: [JoinHandle<T>; N]) -> TerminableThreads<T, N> {
+        for (index, thread) in threads.iter().enumerate() {
+            self.flags.attach(index, thread.thread().clone());
+        }
+
         TerminableThreads {
-            _terminate_flag: self.terminate_flag,
+            _flags: self.flags,
             _threads: threads,
         }
     }
+
+    /// Spawn one thread per function using the builder's thread configuration
+    ///
+    /// # Errors
+    ///
+    /// Returns the OS error if a thread could not be spawned, in which case the
+    /// threads spawned so far are signalled to terminate and joined.
+    ///
+    /// # Panics
+    ///
+    /// If threads have already been spawned with `add_thread`
+    pub fn build_with_funcs<F>(mut self, funcs: [F; N]) -> io::Result<TerminableThreads<T, N>>
+    where
+        T: Send + 'static,
+        F: FnOnce(Arc<AtomicBool>) -> T + Send + 'static,
+    {
+        assert!(self.threads.is_empty(), "threads were already added");
+
+        for func in funcs {
+            self = self.add_thread(func)?;
+        }
+
+        match self.build() {
+            Ok(threads) => Ok(threads),
+            Err(_) => unreachable!("exactly one thread is spawned per function"),
+        }
+    }
+
+    /// Spawn a single thread using the builder's thread configuration
+    ///
+    /// Threads are numbered in the order they are added, both for the name
+    /// pattern and for `thread_flag`. More than `N` threads may be added, in
+    /// which case `build` produces a `TerminableThreadGroup`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the OS error if the thread could not be spawned, or its priority
+    /// or nice level could not be set, in which case the threads spawned so far,
+    /// including this one, are signalled to terminate and joined.
+    pub fn add_thread<F>(mut self, func: F) -> io::Result<Self>
+    where
+        T: Send + 'static,
+        F: FnOnce(Arc<AtomicBool>) -> T + Send + 'static,
+    {
+        let index = self.threads.len();
+
+        let flag = match self.flags.flag(index) {
+            Some(flag) => Arc::clone(flag),
+            None => self.flags.push(),
+        };
+
+        let start = Instant::now() + self.config.start_delay(index);
+
+        let dependencies: Vec<ReadySignal> = self
+            .dependencies
+            .get(&index)
+            .into_iter()
+            .flatten()
+            .filter_map(|&dependency| self.flags.ready_signal(dependency).cloned())
+            .collect();
+
+        let max_runtime = self.max_runtimes.get(&index).map(|&limit| {
+            let timed_out = self.flags.timed_out(index).expect("flag was added above");
+            (Arc::clone(timed_out), limit)
+        });
+
+        #[cfg(all(feature = "unix", target_os = "linux"))]
+        let cpu_budget = self.cpu_budgets.get(&index).map(|&budget| {
+            let timed_out = self.flags.timed_out(index).expect("flag was added above");
+            (Arc::clone(timed_out), budget)
+        });
+
+        let tracking = self.flags.track(index).expect("flag was added above");
+
+        #[cfg(all(feature = "unix", target_os = "linux"))]
+        let (renice, reniced) = match self.niceness {
+            Some(niceness) => {
+                let (reniced_tx, reniced_rx) = mpsc::sync_channel(1);
+                (Some((niceness, reniced_tx)), Some(reniced_rx))
+            }
+            None => (None, None),
+        };
+
+        let spawned = self.config.builder(index).spawn(move || {
+            #[cfg(all(feature = "unix", target_os = "linux"))]
+            if let Some((niceness, reniced)) = renice {
+                let result = priority::renice_current(niceness);
+
+                if result.is_err() {
+                    flag.store(true, atomic::Ordering::SeqCst);
+                }
+
+                // The builder only hangs up once it has given up on the thread
+                let _ = reniced.send(result);
+            }
+
+            let tracking = tracking.enter();
+
+            if !park_until(&flag, start) {
+                for dependency in &dependencies {
+                    if !dependency.wait(&flag) {
+                        break;
+                    }
+                }
+            }
+
+            let _tracking = tracking.started();
+
+            let _limit = max_runtime.map(|(timed_out, limit)| {
+                watchdog::limit_runtime(Arc::clone(&flag), timed_out, limit)
+            });
+
+            #[cfg(all(feature = "unix", target_os = "linux"))]
+            let _budget = cpu_budget.map(|(timed_out, budget)| {
+                watchdog::limit_cpu_time(Arc::clone(&flag), timed_out, budget)
+            });
+
+            func(flag)
+        });
+
+        let thread = match spawned {
+            Ok(thread) => thread,
+            Err(error) => return Err(self.abandon(error)),
+        };
+
+        let configured: io::Result<()> = Ok(());
+
+        #[cfg(all(feature = "unix", target_os = "linux"))]
+        let configured = configured.and_then(|()| match reniced {
+            Some(reniced) => reniced
+                .recv()
+                .expect("the thread reports before doing anything else"),
+            None => Ok(()),
+        });
+
+        #[cfg(any(
+            all(feature = "unix", target_os = "linux"),
+            all(feature = "windows", windows)
+        ))]
+        let configured = configured.and_then(|()| match self.priorities.get(&index) {
+            Some(priority) => priority.apply(&thread),
+            None => Ok(()),
+        });
+
+        self.threads.push(thread);
+
+        match configured {
+            Ok(()) => Ok(self),
+            Err(error) => Err(self.abandon(error)),
+        }
+    }
+
+    /// Terminate and join every thread spawned so far, handing back the error that stopped the build
+    fn abandon(self, error: io::Error) -> io::Error {
+        self.flags.terminate();
+
+        for thread in self.threads {
+            let _ = thread.join();
+        }
+
+        error
+    }
+
+    /// Transform the builder into a container for the threads spawned with `add_thread`
+    ///
+    /// # Returns
+    ///
+    /// `Ok` with a `TerminableThreads<T, N>` if exactly `N` threads were added,
+    /// otherwise `Err` with a `TerminableThreadGroup` holding however many
+    /// threads were added. Either way the threads keep the flags they were
+    /// spawned with.
+    pub fn build(mut self) -> Result<TerminableThreads<T, N>, TerminableThreadGroup<T>> {
+        if self.threads.len() != N {
+            // Drop the flags reserved for threads that were never added
+            self.flags
+                .split_off(self.threads.len().min(self.flags.len()));
+
+            return Err(TerminableThreadGroup {
+                _threads: self.threads,
+                _flags: self.flags,
+            });
+        }
+
+        match self.threads.try_into() {
+            Ok(threads) => Ok(TerminableThreads {
+                _threads: threads,
+                _flags: self.flags,
+            }),
+            Err(_) => unreachable!("the number of threads was checked to be N"),
+        }
+    }
 }
@@ -0,0 +1,277 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::{
+    FlagSet, HealthSource, Join, JoinError, LifecycleEvent, Snapshot, Terminate, ThreadMetrics,
+    ThreadStatus,
+};
+
+/// A set of terminable threads, each registered under a user chosen key
+///
+/// Suited to services made up of distinct subsystems (e.g. `"http"`,
+/// `"metrics"`, `"cleanup"`), where addressing threads by position is awkward.
+///
+/// Each thread is given its own flag from a `FlagSet`, which is raised either
+/// for that thread alone by `terminate_key`, or for every thread at once by
+/// `terminate`. Members of the set are numbered in spawn order, which is the
+/// index events and snapshots report threads by.
+pub struct TerminableThreadMap<K, T> {
+    pub(crate) _threads: HashMap<K, (JoinHandle<T>, usize)>,
+    pub(crate) _flags: FlagSet,
+}
+
+impl<K: Eq + Hash, T> TerminableThreadMap<K, T> {
+    /// Create an empty map
+    pub fn new() -> Self {
+        Self {
+            _threads: HashMap::new(),
+            _flags: FlagSet::new(),
+        }
+    }
+
+    /// Spawn a thread registered under `key`, receiving its termination flag
+    ///
+    /// If the map is already terminating, the new thread's flag starts raised.
+    ///
+    /// # Returns
+    ///
+    /// The thread previously registered under `key`, if any. It is signalled
+    /// to terminate but not joined, so the caller decides whether to wait on it.
+    ///
+    /// # Panics
+    ///
+    /// If the OS fails to create the thread, like `std::thread::spawn`
+    pub fn spawn<F>(&mut self, key: K, func: F) -> Option<JoinHandle<T>>
+    where
+        F: FnOnce(Arc<AtomicBool>) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let index = self._flags.len();
+        let flag = self._flags.push();
+        let tracking = self._flags.track(index);
+        let tracking = tracking.expect("a member was just added for the thread");
+
+        let thread = thread::spawn(move || {
+            let _tracking = tracking.enter().started();
+            func(flag)
+        });
+
+        let (previous, previous_index) = self._threads.insert(key, (thread, index))?;
+        self._flags.terminate_one(previous_index);
+        self._flags.wake(&previous);
+
+        Some(previous)
+    }
+
+    /// Signal all threads to terminate and cease operation
+    ///
+    /// The threads are also unparked, so those waiting in `std::thread::park`
+    /// (or `park_timeout`) can notice their flag straight away.
+    ///
+    /// ## Note
+    ///
+    /// Threads will only terminate if the underlying function checks the flag passed to it.
+    pub fn terminate(&self) {
+        self._flags.terminate();
+
+        for (thread, _) in self._threads.values() {
+            self._flags.wake(thread);
+        }
+    }
+
+    /// Terminate every thread as soon as any thread in the map panics, or stop doing so
+    ///
+    /// See `FlagSet::set_terminate_on_panic`.
+    pub fn set_terminate_on_panic(&self, enabled: bool) {
+        self._flags.set_terminate_on_panic(enabled);
+    }
+
+    /// Signal only the thread registered under `key` to terminate
+    ///
+    /// # Returns
+    ///
+    /// `false` if no thread is registered under `key`
+    pub fn terminate_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match self._threads.get(key) {
+            Some((thread, index)) => {
+                self._flags.terminate_one(*index);
+                self._flags.wake(thread);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Look up the status of the thread registered under `key`
+    pub fn status<Q>(&self, key: &Q) -> Option<ThreadStatus>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let (thread, index) = self._threads.get(key)?;
+
+        self._flags.status(*index, thread.is_finished())
+    }
+
+    /// Snapshot of the status of every registered thread, by key
+    pub fn statuses(&self) -> HashMap<&K, ThreadStatus> {
+        self._threads
+            .iter()
+            .filter_map(|(key, (thread, index))| {
+                Some((key, self._flags.status(*index, thread.is_finished())?))
+            })
+            .collect()
+    }
+
+    /// Milestones recorded for every registered thread, by key
+    pub fn metrics(&self) -> HashMap<&K, ThreadMetrics> {
+        self._threads
+            .iter()
+            .filter_map(|(key, (_, index))| Some((key, self._flags.metrics(*index)?)))
+            .collect()
+    }
+
+    /// The most recent lifecycle events of the map's threads, oldest first
+    ///
+    /// Threads are identified by spawn order, including threads since
+    /// replaced under the same key. Only the most recent few hundred events
+    /// are kept.
+    pub fn events(&self) -> Vec<LifecycleEvent> {
+        self._flags.events()
+    }
+
+    /// Snapshot of the map and its threads, in spawn order, e.g. to expose over an admin endpoint
+    pub fn snapshot(&self) -> Snapshot {
+        let mut threads: Vec<_> = self
+            ._threads
+            .values()
+            .map(|(thread, index)| (*index, thread))
+            .collect();
+
+        threads.sort_unstable_by_key(|&(index, _)| index);
+
+        Snapshot::of_indexed(threads, &self._flags)
+    }
+
+    /// Check whether a thread is registered under `key`
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self._threads.contains_key(key)
+    }
+
+    /// Iterate over the keys of all registered threads, in arbitrary order
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self._threads.keys()
+    }
+
+    /// Number of threads in the map
+    pub fn len(&self) -> usize {
+        self._threads.len()
+    }
+
+    /// Check whether the map has no threads
+    pub fn is_empty(&self) -> bool {
+        self._threads.is_empty()
+    }
+
+    /// Join all threads, optionally signalling termination
+    ///
+    /// # Returns
+    ///
+    /// A `HashMap` containing the result of joining each thread under its key
+    pub fn join(self, signal_terminate: bool) -> HashMap<K, Result<T, JoinError>> {
+        if signal_terminate {
+            self.terminate();
+        }
+
+        self._threads
+            .into_iter()
+            .map(|(key, (thread, _))| (key, thread.join()))
+            .collect()
+    }
+}
+
+impl<K: Eq + Hash, T> Default for TerminableThreadMap<K, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq + Hash, T> Terminate for TerminableThreadMap<K, T> {
+    fn terminate(&self) {
+        TerminableThreadMap::terminate(self);
+    }
+}
+
+impl<K: Eq + Hash, T> Join for TerminableThreadMap<K, T> {
+    type Output = HashMap<K, Result<T, JoinError>>;
+
+    fn join(self) -> Self::Output {
+        TerminableThreadMap::join(self, false)
+    }
+}
+
+impl<K: Eq + Hash, T> HealthSource for TerminableThreadMap<K, T> {
+    fn healthy(&self) -> bool {
+        !self.shutting_down()
+            && !self
+                ._threads
+                .values()
+                .any(|(thread, _)| thread.is_finished())
+    }
+
+    fn shutting_down(&self) -> bool {
+        self._flags.is_terminated()
+    }
+}
+
+impl<K, T> AsRef<FlagSet> for TerminableThreadMap<K, T> {
+    fn as_ref(&self) -> &FlagSet {
+        &self._flags
+    }
+}
+
+impl<K: fmt::Debug, T> fmt::Debug for TerminableThreadMap<K, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TerminableThreadMap")
+            .field("len", &self._threads.len())
+            .field("terminated", &self._flags.is_terminated())
+            .field("threads", &Statuses(self))
+            .finish()
+    }
+}
+
+impl<K: Eq + Hash, T> fmt::Display for TerminableThreadMap<K, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TerminableThreadMap ({})", self.snapshot())
+    }
+}
+
+/// Lists each thread's status by key in `Debug` output, instead of its handle
+struct Statuses<'a, K, T>(&'a TerminableThreadMap<K, T>);
+
+impl<K: fmt::Debug, T> fmt::Debug for Statuses<'_, K, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut threads = f.debug_map();
+
+        for (key, (thread, index)) in &self.0._threads {
+            if let Some(status) = self.0._flags.status(*index, thread.is_finished()) {
+                threads.entry(key, &format_args!("{status}"));
+            }
+        }
+
+        threads.finish()
+    }
+}
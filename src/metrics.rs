@@ -0,0 +1,289 @@
+use std::cell::RefCell;
+use std::mem;
+#[cfg(any(
+    all(feature = "unix", target_os = "linux"),
+    all(feature = "windows", windows)
+))]
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+use crate::flags::Group;
+#[cfg(any(
+    all(feature = "unix", target_os = "linux"),
+    all(feature = "windows", windows)
+))]
+use crate::native;
+use crate::pool::lock;
+use crate::{EventKind, ThreadOutcome, ThreadStatus};
+
+/// Timestamps of the milestones in a managed thread's life
+///
+/// Milestones that haven't happened yet, or that couldn't be recorded, are
+/// `None`. Spawn and finish times are only known for threads spawned by the
+/// crate itself, not ones passed in already running.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ThreadMetrics {
+    /// When the thread started running
+    pub spawned: Option<Instant>,
+    /// When the thread was first signalled to terminate, by its own flag or the group's
+    pub signalled: Option<Instant>,
+    /// When the thread first noticed termination through `Token::should_stop`
+    pub observed: Option<Instant>,
+    /// When the thread returned or panicked
+    pub finished: Option<Instant>,
+}
+
+impl ThreadMetrics {
+    /// Time between the thread being signalled and first noticing it
+    pub fn time_to_observe(&self) -> Option<Duration> {
+        Some(self.observed?.saturating_duration_since(self.signalled?))
+    }
+
+    /// Time between the thread being signalled and finishing
+    pub fn time_to_terminate(&self) -> Option<Duration> {
+        Some(self.finished?.saturating_duration_since(self.signalled?))
+    }
+
+    /// Time between the thread starting and finishing
+    pub fn runtime(&self) -> Option<Duration> {
+        Some(self.finished?.saturating_duration_since(self.spawned?))
+    }
+}
+
+/// The milestones recorded so far for one member of a `FlagSet`
+#[derive(Debug, Default)]
+pub(crate) struct Timeline {
+    metrics: Mutex<ThreadMetrics>,
+    running: AtomicBool,
+    panicked: AtomicBool,
+    thread: OnceLock<Thread>,
+    waiters: Mutex<Vec<Thread>>,
+    owner: Mutex<Option<Owner>>,
+    #[cfg(any(
+        all(feature = "unix", target_os = "linux"),
+        all(feature = "windows", windows)
+    ))]
+    os_id: AtomicU64,
+}
+
+/// The group a tracked thread's events go to, and its index there
+///
+/// Kept up to date as the member moves between sets, so a thread merged
+/// into another container reports to that container.
+#[derive(Debug, Clone)]
+struct Owner {
+    group: Arc<Group>,
+    index: usize,
+}
+
+thread_local! {
+    /// Timeline of the managed thread running on this OS thread, if any
+    static CURRENT: RefCell<Option<Arc<Timeline>>> = const { RefCell::new(None) };
+}
+
+impl Timeline {
+    /// The recorded milestones, counting the group as signalled at `group_signalled`
+    pub(crate) fn snapshot(&self, group_signalled: Option<Instant>) -> ThreadMetrics {
+        let mut metrics = *lock(&self.metrics);
+
+        metrics.signalled = match (metrics.signalled, group_signalled) {
+            (Some(own), Some(group)) => Some(own.min(group)),
+            (own, group) => own.or(group),
+        };
+
+        metrics
+    }
+
+    /// OS id of the thread, once it has started
+    #[cfg(any(
+        all(feature = "unix", target_os = "linux"),
+        all(feature = "windows", windows)
+    ))]
+    pub(crate) fn os_id(&self) -> Option<u64> {
+        match self.os_id.load(atomic::Ordering::SeqCst) {
+            0 => None,
+            id => Some(id),
+        }
+    }
+
+    /// Record the thread running as the member, so raising its flag can wake it
+    ///
+    /// Only the first thread recorded is kept.
+    pub(crate) fn attach(&self, thread: Thread) {
+        let _ = self.thread.set(thread);
+    }
+
+    /// Unpark the member's thread, if known, so one waiting on its flag notices it
+    pub(crate) fn unpark(&self) {
+        if let Some(thread) = self.thread.get() {
+            thread.unpark();
+        }
+    }
+
+    /// Check whether the tracked thread has returned or panicked
+    pub(crate) fn is_finished(&self) -> bool {
+        lock(&self.metrics).finished.is_some()
+    }
+
+    /// Arrange for `waiter` to be unparked once the tracked thread finishes
+    ///
+    /// # Returns
+    ///
+    /// `false` if no thread is tracked, e.g. one passed in already running,
+    /// so nothing will unpark `waiter`
+    pub(crate) fn unpark_on_finish(&self, waiter: Thread) -> bool {
+        if lock(&self.metrics).spawned.is_none() {
+            return false;
+        }
+
+        let mut waiters = lock(&self.waiters);
+
+        if !waiters.iter().any(|thread| thread.id() == waiter.id()) {
+            waiters.push(waiter);
+        }
+
+        true
+    }
+
+    /// Record that the member's own flag was raised, unless it already was
+    pub(crate) fn signalled(&self) {
+        lock(&self.metrics)
+            .signalled
+            .get_or_insert_with(Instant::now);
+    }
+
+    /// Record that a thread is being spawned as the member at `index` of `group`
+    ///
+    /// The returned guard is moved into the new thread, which `enter`s it.
+    /// Dropping it records the thread as finished.
+    pub(crate) fn track(self: &Arc<Self>, group: Arc<Group>, index: usize) -> Tracking {
+        lock(&self.metrics).spawned = Some(Instant::now());
+        *lock(&self.owner) = Some(Owner { group, index });
+
+        Tracking {
+            timeline: Arc::clone(self),
+            entered: false,
+        }
+    }
+
+    /// Record that the member now sits at `index` of `group`, after moving between sets
+    ///
+    /// Only matters for a tracked thread, whose events follow the member.
+    pub(crate) fn reassign(&self, group: &Arc<Group>, index: usize) {
+        if let Some(owner) = lock(&self.owner).as_mut() {
+            owner.group = Arc::clone(group);
+            owner.index = index;
+        }
+    }
+
+    fn owner(&self) -> Owner {
+        lock(&self.owner)
+            .clone()
+            .expect("a tracked timeline has an owner")
+    }
+
+    /// Refine a status worked out from the thread's handle and flag with what was recorded
+    ///
+    /// Threads that were never tracked are left as they are.
+    pub(crate) fn refine(&self, status: ThreadStatus) -> ThreadStatus {
+        if lock(&self.metrics).spawned.is_none() {
+            return status;
+        }
+
+        match status {
+            ThreadStatus::Running if !self.running.load(atomic::Ordering::SeqCst) => {
+                ThreadStatus::Spawned
+            }
+            ThreadStatus::Finished(ThreadOutcome::Unknown) => {
+                if self.panicked.load(atomic::Ordering::SeqCst) {
+                    ThreadStatus::Finished(ThreadOutcome::Panicked)
+                } else {
+                    ThreadStatus::Finished(ThreadOutcome::Returned)
+                }
+            }
+            status => status,
+        }
+    }
+}
+
+/// Record that the current thread noticed termination, if it is tracked and hasn't already
+pub(crate) fn observed() {
+    // Ignores a thread that is being torn down, whose timeline is already finished
+    let _ = CURRENT.try_with(|current| {
+        if let Some(timeline) = &*current.borrow() {
+            lock(&timeline.metrics)
+                .observed
+                .get_or_insert_with(Instant::now);
+        }
+    });
+}
+
+/// Records a tracked thread as finished when dropped, including by panicking
+pub(crate) struct Tracking {
+    timeline: Arc<Timeline>,
+    entered: bool,
+}
+
+impl Tracking {
+    /// Make the current thread the tracked one, so it records noticing termination
+    pub(crate) fn enter(mut self) -> Self {
+        CURRENT.with(|current| *current.borrow_mut() = Some(Arc::clone(&self.timeline)));
+        self.timeline.attach(thread::current());
+
+        #[cfg(any(
+            all(feature = "unix", target_os = "linux"),
+            all(feature = "windows", windows)
+        ))]
+        self.timeline
+            .os_id
+            .store(native::current_os_id(), atomic::Ordering::SeqCst);
+
+        let owner = self.timeline.owner();
+        owner.group.record(EventKind::Spawned, Some(owner.index));
+        self.entered = true;
+        self
+    }
+
+    /// Record that the thread has started running its function
+    pub(crate) fn started(self) -> Self {
+        self.timeline.running.store(true, atomic::Ordering::SeqCst);
+        self
+    }
+}
+
+impl Drop for Tracking {
+    fn drop(&mut self) {
+        let panicked = self.entered && thread::panicking();
+
+        // Stored before the finish, so whoever sees the thread finished also sees how
+        self.timeline
+            .panicked
+            .store(panicked, atomic::Ordering::SeqCst);
+
+        lock(&self.timeline.metrics).finished = Some(Instant::now());
+
+        // Taken after recording the finish, so a waiter registering late sees it instead
+        for waiter in mem::take(&mut *lock(&self.timeline.waiters)) {
+            waiter.unpark();
+        }
+
+        if self.entered {
+            let kind = if panicked {
+                EventKind::Panicked
+            } else {
+                EventKind::Finished
+            };
+
+            let owner = self.timeline.owner();
+            owner.group.record(kind, Some(owner.index));
+
+            if panicked {
+                owner.group.member_panicked();
+            }
+            let _ = CURRENT.try_with(|current| current.borrow_mut().take());
+        }
+    }
+}
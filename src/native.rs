@@ -0,0 +1,35 @@
+/// OS id of the calling thread, as shown by e.g. `top -H` or Task Manager
+pub(crate) fn current_os_id() -> u64 {
+    os::current_os_id()
+}
+
+/// Thread ids, through `gettid`
+#[cfg(all(feature = "unix", target_os = "linux"))]
+mod os {
+    use std::os::raw::c_int;
+
+    extern "C" {
+        fn gettid() -> c_int;
+    }
+
+    pub(super) fn current_os_id() -> u64 {
+        // SAFETY: `gettid` has no preconditions and always succeeds
+        let tid = unsafe { gettid() };
+
+        u64::try_from(tid).expect("thread ids are positive")
+    }
+}
+
+/// Thread ids, through `GetCurrentThreadId`
+#[cfg(all(feature = "windows", windows))]
+mod os {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentThreadId() -> u32;
+    }
+
+    pub(super) fn current_os_id() -> u64 {
+        // SAFETY: `GetCurrentThreadId` has no preconditions
+        u64::from(unsafe { GetCurrentThreadId() })
+    }
+}
@@ -0,0 +1,419 @@
+use std::fmt;
+use std::iter::Enumerate;
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
+use std::sync::{Arc, Mutex};
+use std::vec;
+
+use crate::pool::lock;
+use crate::{HealthSource, Join, JoinAllError, TerminableThreadGroup, Terminate, Token, WaitGroup};
+
+/// Apply `f` to every item on `threads` worker threads, collecting the results in input order
+///
+/// Items are handed out in order, and workers check their termination flag
+/// before taking each one, so terminating the group lets the items already
+/// started finish and skips the rest. The `ResultCollector` then gives back
+/// the results of the completed prefix and the indices of the skipped items.
+///
+/// ## Note
+///
+/// The number of workers is only known at run time, so they are spawned into
+/// a `TerminableThreadGroup` rather than a fixed size `TerminableThreads`.
+///
+/// # Panics
+///
+/// If `threads` is zero
+pub fn terminable_map<I, U, F>(
+    items: I,
+    threads: usize,
+    f: F,
+) -> (TerminableThreadGroup<()>, ResultCollector<U>)
+where
+    I: IntoIterator,
+    I::Item: Send + 'static,
+    U: Send + 'static,
+    F: Fn(I::Item) -> U + Send + Sync + 'static,
+{
+    let items: Vec<I::Item> = items.into_iter().collect();
+    let results = Arc::new(Mutex::new(items.iter().map(|_| None).collect::<Vec<_>>()));
+
+    let (group, finished) = {
+        let results = Arc::clone(&results);
+
+        spawn_workers(items, threads, move |index, item| {
+            let result = f(item);
+            lock(&results)[index] = Some(result);
+        })
+    };
+
+    (group, ResultCollector { results, finished })
+}
+
+/// Collects the results of a `terminable_map` once its workers have stopped
+#[derive(Debug)]
+pub struct ResultCollector<U> {
+    results: Arc<Mutex<Vec<Option<U>>>>,
+    finished: WaitGroup,
+}
+
+impl<U> ResultCollector<U> {
+    /// Number of items processed so far
+    pub fn completed(&self) -> usize {
+        lock(&self.results).iter().flatten().count()
+    }
+
+    /// Block until every worker has stopped, then gather the results
+    ///
+    /// The workers stop once all items are processed, or soon after their
+    /// group is terminated.
+    pub fn collect(self) -> MapResult<U> {
+        self.finished.wait(&AtomicBool::new(false));
+
+        let mut completed = Vec::new();
+        let mut skipped = Vec::new();
+
+        for (index, result) in lock(&self.results).drain(..).enumerate() {
+            match result {
+                Some(result) if skipped.is_empty() => completed.push(result),
+                _ => skipped.push(index),
+            }
+        }
+
+        MapResult { completed, skipped }
+    }
+}
+
+/// Outcome of a `terminable_map`, gathered by `ResultCollector::collect`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapResult<U> {
+    /// Results for the leading items that were processed, in input order
+    pub completed: Vec<U>,
+    /// Indices of the items after that prefix, which were skipped because of
+    /// termination or because `f` panicked on them or an earlier item
+    pub skipped: Vec<usize>,
+}
+
+impl<U> MapResult<U> {
+    /// Check whether every item was processed
+    pub fn is_complete(&self) -> bool {
+        self.skipped.is_empty()
+    }
+}
+
+/// Run `f` on every item on `threads` worker threads, for side effects only
+///
+/// The companion of `terminable_map` for work like converting files or
+/// uploads. Items are handed out in order, and terminating the group lets the
+/// items already started finish and skips the rest. The `ForEachTracker`
+/// reports how many items completed.
+///
+/// # Panics
+///
+/// If `threads` is zero
+pub fn terminable_for_each<I, F>(
+    items: I,
+    threads: usize,
+    f: F,
+) -> (TerminableThreadGroup<()>, ForEachTracker)
+where
+    I: IntoIterator,
+    I::Item: Send + 'static,
+    F: Fn(I::Item) + Send + Sync + 'static,
+{
+    let items: Vec<I::Item> = items.into_iter().collect();
+    let total = items.len();
+    let completed = Arc::new(AtomicUsize::new(0));
+
+    let (group, finished) = {
+        let completed = Arc::clone(&completed);
+
+        spawn_workers(items, threads, move |_, item| {
+            f(item);
+            completed.fetch_add(1, atomic::Ordering::SeqCst);
+        })
+    };
+
+    (
+        group,
+        ForEachTracker {
+            completed,
+            total,
+            finished,
+        },
+    )
+}
+
+/// Tracks the progress of a `terminable_for_each`
+#[derive(Debug)]
+pub struct ForEachTracker {
+    completed: Arc<AtomicUsize>,
+    total: usize,
+    finished: WaitGroup,
+}
+
+impl ForEachTracker {
+    /// Number of items processed so far
+    pub fn completed(&self) -> usize {
+        self.completed.load(atomic::Ordering::SeqCst)
+    }
+
+    /// Total number of items
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Block until every worker has stopped, then report how far they got
+    pub fn wait(self) -> ForEachReport {
+        self.finished.wait(&AtomicBool::new(false));
+
+        ForEachReport {
+            completed: self.completed(),
+            total: self.total,
+        }
+    }
+}
+
+/// Outcome of a `terminable_for_each`, reported by `ForEachTracker::wait`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ForEachReport {
+    /// Number of items `f` returned for
+    pub completed: usize,
+    /// Total number of items
+    pub total: usize,
+}
+
+impl ForEachReport {
+    /// Check whether every item was processed
+    pub fn is_complete(&self) -> bool {
+        self.completed == self.total
+    }
+}
+
+/// Fold every item on `threads` worker threads, mapping each with `map` and combining with `reduce`
+///
+/// Each worker folds the items it takes into its own partial result, and the
+/// partial results are combined when the `ReduceHandle` is joined. Since
+/// items are spread over the workers in no fixed way, `reduce` should be
+/// associative and commutative. Terminating the handle skips the items not
+/// yet started, and joining it still gives the fold of those that were.
+///
+/// # Panics
+///
+/// If `threads` is zero
+pub fn terminable_reduce<I, A, M, R>(
+    items: I,
+    threads: usize,
+    map: M,
+    reduce: R,
+) -> ReduceHandle<A, R>
+where
+    I: IntoIterator,
+    I::Item: Send + 'static,
+    A: Send + 'static,
+    M: Fn(I::Item) -> A + Send + Sync + 'static,
+    R: Fn(A, A) -> A + Send + Sync + 'static,
+{
+    assert!(threads > 0, "parallel helpers need at least one thread");
+
+    let items: Vec<I::Item> = items.into_iter().collect();
+    let total = items.len();
+    let queue = queue(items);
+    let map = Arc::new(map);
+    let reduce = Arc::new(reduce);
+    let mut group = TerminableThreadGroup::default();
+
+    for _ in 0..threads {
+        let queue = Arc::clone(&queue);
+        let map = Arc::clone(&map);
+        let reduce = Arc::clone(&reduce);
+
+        group.push(move |flag| {
+            let mut partial = None;
+            let mut completed = 0;
+
+            while let Some((_, item)) = next_item(&queue, &flag) {
+                let mapped = map(item);
+
+                partial = Some(match partial {
+                    Some(partial) => reduce(partial, mapped),
+                    None => mapped,
+                });
+                completed += 1;
+            }
+
+            (partial, completed)
+        });
+    }
+
+    ReduceHandle {
+        _group: group,
+        _reduce: reduce,
+        _total: total,
+    }
+}
+
+/// The workers of a `terminable_reduce`, combining their partial results when joined
+pub struct ReduceHandle<A, R> {
+    pub(crate) _group: TerminableThreadGroup<(Option<A>, usize)>,
+    pub(crate) _reduce: Arc<R>,
+    pub(crate) _total: usize,
+}
+
+impl<A, R> ReduceHandle<A, R>
+where
+    R: Fn(A, A) -> A,
+{
+    /// Signal the workers to stop taking items
+    ///
+    /// Items already started are still folded in.
+    pub fn terminate(&self) {
+        self._group.terminate();
+    }
+
+    /// Join the workers, optionally signalling termination, and combine their partial results
+    ///
+    /// # Errors
+    ///
+    /// A `JoinAllError` if any worker panicked, in which case the items it had
+    /// folded are lost
+    pub fn join(self, signal_terminate: bool) -> Result<ReduceResult<A>, JoinAllError> {
+        let partials = self._group.join_all_ok(signal_terminate)?;
+
+        let mut value = None;
+        let mut completed = 0;
+
+        for (partial, count) in partials {
+            completed += count;
+
+            value = match (value, partial) {
+                (Some(value), Some(partial)) => Some((self._reduce)(value, partial)),
+                (value, partial) => value.or(partial),
+            };
+        }
+
+        Ok(ReduceResult {
+            value,
+            completed,
+            total: self._total,
+        })
+    }
+}
+
+impl<A, R> Terminate for ReduceHandle<A, R> {
+    fn terminate(&self) {
+        self._group.terminate();
+    }
+}
+
+impl<A, R> Join for ReduceHandle<A, R>
+where
+    R: Fn(A, A) -> A,
+{
+    type Output = Result<ReduceResult<A>, JoinAllError>;
+
+    fn join(self) -> Self::Output {
+        ReduceHandle::join(self, false)
+    }
+}
+
+impl<A, R> HealthSource for ReduceHandle<A, R> {
+    fn healthy(&self) -> bool {
+        self._group.healthy()
+    }
+
+    fn shutting_down(&self) -> bool {
+        self._group.shutting_down()
+    }
+}
+
+impl<A: fmt::Debug, R> fmt::Debug for ReduceHandle<A, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReduceHandle")
+            .field("_group", &self._group)
+            .field("_total", &self._total)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Outcome of a `terminable_reduce`, produced by joining its `ReduceHandle`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReduceResult<A> {
+    /// The fold of every processed item, `None` if no item was processed
+    pub value: Option<A>,
+    /// Number of items that were folded in
+    pub completed: usize,
+    /// Total number of items
+    pub total: usize,
+}
+
+impl<A> ReduceResult<A> {
+    /// Check whether every item was folded in
+    pub fn is_complete(&self) -> bool {
+        self.completed == self.total
+    }
+}
+
+/// Spawn `threads` workers calling `f` on each item with its index, in order, until terminated
+///
+/// # Returns
+///
+/// The group running the workers, and a `WaitGroup` that completes once all
+/// of them have stopped, including by panicking
+pub(crate) fn spawn_workers<T, F>(
+    items: Vec<T>,
+    threads: usize,
+    f: F,
+) -> (TerminableThreadGroup<()>, WaitGroup)
+where
+    T: Send + 'static,
+    F: Fn(usize, T) + Send + Sync + 'static,
+{
+    assert!(threads > 0, "parallel helpers need at least one thread");
+
+    let queue = queue(items);
+    let f = Arc::new(f);
+    let finished = WaitGroup::new();
+    let mut group = TerminableThreadGroup::default();
+
+    finished.add(threads);
+
+    for _ in 0..threads {
+        let queue = Arc::clone(&queue);
+        let f = Arc::clone(&f);
+        let done = DoneOnDrop(finished.clone());
+
+        group.push(move |flag| {
+            let _done = done;
+
+            while let Some((index, item)) = next_item(&queue, &flag) {
+                f(index, item);
+            }
+        });
+    }
+
+    (group, finished)
+}
+
+/// Items shared between workers, handed out in order along with their index
+type Queue<T> = Arc<Mutex<Enumerate<vec::IntoIter<T>>>>;
+
+fn queue<T>(items: Vec<T>) -> Queue<T> {
+    Arc::new(Mutex::new(items.into_iter().enumerate()))
+}
+
+/// Take the next item, unless termination has been signalled
+fn next_item<T>(queue: &Queue<T>, flag: &AtomicBool) -> Option<(usize, T)> {
+    if flag.should_stop() {
+        return None;
+    }
+
+    lock(queue).next()
+}
+
+/// Marks a worker as done in its `WaitGroup` when dropped, including by panicking
+struct DoneOnDrop(WaitGroup);
+
+impl Drop for DoneOnDrop {
+    fn drop(&mut self) {
+        self.0.done();
+    }
+}
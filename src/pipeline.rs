@@ -0,0 +1,542 @@
+use std::fmt;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use crate::{
+    ClosePolicy, FlagSet, HealthSource, Join, JoinError, TerminableThreadGroup, Terminate, Token,
+    WorkChannel,
+};
+
+/// Default number of items buffered between two pipeline stages
+const DEFAULT_CAPACITY: usize = 64;
+
+type SpawnStages<T> = Box<dyn FnOnce(usize, WorkChannel<T>) -> Vec<TerminableThreadGroup<()>>>;
+
+/// A chain of stages, each a group of terminable workers, connected by channels
+///
+/// Items flow from a source through every stage to the pipeline's output.
+/// Each stage has its own termination flag, and closes the channel to the
+/// next stage once all of its workers have stopped, so shutdown ripples
+/// through the pipeline in order: `terminate` stops the source, and every
+/// stage after it finishes the items already in flight before stopping.
+/// `abort` stops every stage at once, dropping whatever is still queued.
+///
+/// The pipeline's own `FlagSet` is linked to the source, so registering the
+/// pipeline or linking it to another set stops it just like `terminate`.
+pub struct Pipeline<T> {
+    pub(crate) _stages: Vec<TerminableThreadGroup<()>>,
+    pub(crate) _output: WorkChannel<T>,
+    pub(crate) _flags: FlagSet,
+}
+
+impl<T: Send + 'static> Pipeline<T> {
+    /// Start building a pipeline whose items are produced by `f` on its own thread
+    ///
+    /// `f` pushes items onto the channel it is given, and should stop once its
+    /// termination flag is raised or a push fails.
+    pub fn source<F>(f: F) -> PipelineBuilder<T>
+    where
+        F: FnOnce(&WorkChannel<T>, Arc<AtomicBool>) + Send + 'static,
+    {
+        PipelineBuilder {
+            spawn: Box::new(move |_, output| {
+                let mut group = TerminableThreadGroup::default();
+                let guard = CloseOnDrop { output };
+
+                group.push(move |flag| f(&guard.output, flag));
+
+                vec![group]
+            }),
+            stages: 1,
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+
+    /// Start building a pipeline fed with the items of `items`
+    ///
+    /// Items are produced lazily, so `items` may be unbounded.
+    pub fn from_items<I>(items: I) -> PipelineBuilder<T>
+    where
+        I: IntoIterator<Item = T> + Send + 'static,
+    {
+        Self::source(move |output, flag| {
+            for item in items {
+                if flag.should_stop() || output.push(item).is_err() {
+                    break;
+                }
+            }
+        })
+    }
+}
+
+impl<T> Pipeline<T> {
+    /// Take the next item from the last stage, blocking until one is available
+    ///
+    /// # Returns
+    ///
+    /// `None` once every stage has stopped and the output is drained
+    pub fn recv(&self) -> Option<T> {
+        self._output.pop()
+    }
+
+    /// Iterate over the pipeline's output until every stage has stopped
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self._output.iter()
+    }
+
+    /// The channel the last stage pushes onto, e.g. to consume it on another thread
+    pub fn output(&self) -> &WorkChannel<T> {
+        &self._output
+    }
+
+    /// Stop the source, letting every later stage drain the items in flight
+    ///
+    /// ## Note
+    ///
+    /// The source only stops if it checks its flag, or pushes onto a channel
+    /// that has been closed.
+    pub fn terminate(&self) {
+        self._flags.terminate();
+    }
+
+    /// Signal every stage to terminate at once, dropping items still queued between stages
+    pub fn abort(&self) {
+        for stage in &self._stages {
+            stage.terminate();
+        }
+    }
+
+    /// Number of stages, including the source
+    pub fn stages(&self) -> usize {
+        self._stages.len()
+    }
+
+    /// Join every stage in order, optionally signalling termination
+    ///
+    /// Items not taken from the output are dropped once the pipeline is joined.
+    pub fn join(self, signal_terminate: bool) -> Vec<Result<(), JoinError>> {
+        if signal_terminate {
+            self.terminate();
+        }
+
+        self._stages
+            .into_iter()
+            .flat_map(|stage| stage.join(false))
+            .collect()
+    }
+}
+
+impl<T> Terminate for Pipeline<T> {
+    fn terminate(&self) {
+        Pipeline::terminate(self);
+    }
+}
+
+impl<T> Join for Pipeline<T> {
+    type Output = Vec<Result<(), JoinError>>;
+
+    fn join(self) -> Self::Output {
+        Pipeline::join(self, false)
+    }
+}
+
+impl<T> AsRef<FlagSet> for Pipeline<T> {
+    fn as_ref(&self) -> &FlagSet {
+        &self._flags
+    }
+}
+
+impl<T> HealthSource for Pipeline<T> {
+    fn healthy(&self) -> bool {
+        self._stages.iter().all(HealthSource::healthy)
+    }
+
+    fn shutting_down(&self) -> bool {
+        self._stages.iter().any(HealthSource::shutting_down)
+    }
+}
+
+impl<T> fmt::Debug for Pipeline<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("_stages", &self._stages)
+            .field("queued", &self._output.len())
+            .finish()
+    }
+}
+
+/// Builder for a `Pipeline`, created by `Pipeline::source` or `Pipeline::from_items`
+///
+/// Threads are only spawned by `build`.
+pub struct PipelineBuilder<T> {
+    spawn: SpawnStages<T>,
+    stages: usize,
+    capacity: usize,
+}
+
+impl<T: Send + 'static> PipelineBuilder<T> {
+    /// Add a stage of `workers` threads, each applying `f` to the items of the previous stage
+    ///
+    /// # Panics
+    ///
+    /// If `workers` is zero
+    pub fn stage<U, F>(self, workers: usize, f: F) -> PipelineBuilder<U>
+    where
+        U: Send + 'static,
+        F: Fn(T) -> U + Send + Sync + 'static,
+    {
+        assert!(workers > 0, "pipeline stage needs at least one worker");
+
+        let previous = self.spawn;
+        let f = Arc::new(f);
+
+        PipelineBuilder {
+            spawn: Box::new(move |capacity, output| {
+                let mut group = TerminableThreadGroup::default();
+                let input = WorkChannel::bounded(
+                    capacity,
+                    Arc::clone(group.as_ref().group_flag()),
+                    ClosePolicy::Bail,
+                );
+                let guard = Arc::new(CloseOnDrop { output });
+
+                for _ in 0..workers {
+                    let input = input.clone();
+                    let guard = Arc::clone(&guard);
+                    let f = Arc::clone(&f);
+
+                    group.push(move |_| {
+                        for item in input.iter() {
+                            if guard.output.push(f(item)).is_err() {
+                                break;
+                            }
+                        }
+                    });
+                }
+
+                let mut stages = previous(capacity, input);
+                stages.push(group);
+                stages
+            }),
+            stages: self.stages + 1,
+            capacity: self.capacity,
+        }
+    }
+
+    /// Set how many items may be queued between two stages, 64 by default
+    ///
+    /// A full queue makes the stage before it wait, so a slow stage holds
+    /// back the ones before it instead of letting items pile up.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is zero, once the pipeline is built
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Spawn every stage, returning the running pipeline
+    pub fn build(self) -> Pipeline<T> {
+        let output = WorkChannel::new(Arc::new(AtomicBool::new(false)), ClosePolicy::Drain);
+        let stages = (self.spawn)(self.capacity, output.clone());
+        let flags = FlagSet::new();
+
+        if let Some(source) = stages.first() {
+            flags.link(source.as_ref());
+        }
+
+        Pipeline {
+            _stages: stages,
+            _output: output,
+            _flags: flags,
+        }
+    }
+}
+
+impl<T> fmt::Debug for PipelineBuilder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PipelineBuilder")
+            .field("stages", &self.stages)
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+/// Spread the items of `input` over `workers` threads applying `f`, collecting the results
+///
+/// Returns the group running the workers, along with the channel their
+/// results arrive on, in no particular order. The channel is closed once
+/// every item has been processed, or once the group is terminated and the
+/// results already produced have been taken.
+///
+/// # Panics
+///
+/// If `workers` is zero
+pub fn fan_out<I, U, F>(
+    input: I,
+    workers: usize,
+    f: F,
+) -> (TerminableThreadGroup<()>, WorkChannel<U>)
+where
+    I: IntoIterator + Send + 'static,
+    I::Item: Send + 'static,
+    U: Send + 'static,
+    F: Fn(I::Item) -> U + Send + Sync + 'static,
+{
+    assert!(workers > 0, "fan out needs at least one worker");
+
+    let mut group = TerminableThreadGroup::default();
+    let flag = Arc::clone(group.as_ref().group_flag());
+    let items = WorkChannel::bounded(workers * 2, Arc::clone(&flag), ClosePolicy::Bail);
+    let output = WorkChannel::new(flag, ClosePolicy::Drain);
+
+    {
+        let guard = CloseOnDrop {
+            output: items.clone(),
+        };
+
+        group.push(move |flag| {
+            for item in input {
+                if flag.should_stop() || guard.output.push(item).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    let guard = Arc::new(CloseOnDrop {
+        output: output.clone(),
+    });
+    let f = Arc::new(f);
+
+    for _ in 0..workers {
+        let items = items.clone();
+        let guard = Arc::clone(&guard);
+        let f = Arc::clone(&f);
+
+        group.push(move |_| {
+            for item in items.iter() {
+                if guard.output.push(f(item)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    (group, output)
+}
+
+/// Merge several channels into one, forwarding each on its own thread
+///
+/// Returns the group running the forwarding threads, along with the merged
+/// channel, which is closed once every input channel is closed and drained,
+/// or once the group is terminated.
+pub fn fan_in<T, I>(outputs: I) -> (TerminableThreadGroup<()>, WorkChannel<T>)
+where
+    T: Send + 'static,
+    I: IntoIterator<Item = WorkChannel<T>>,
+{
+    let mut group = TerminableThreadGroup::default();
+    let merged = WorkChannel::new(Arc::clone(group.as_ref().group_flag()), ClosePolicy::Drain);
+    let guard = Arc::new(CloseOnDrop {
+        output: merged.clone(),
+    });
+
+    for input in outputs {
+        let guard = Arc::clone(&guard);
+
+        group.push(move |flag| {
+            while let Some(item) = input.pop_or_terminated(&flag) {
+                if guard.output.push(item).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    (group, merged)
+}
+
+/// Closes a stage's output channel once dropped
+///
+/// Each worker of a stage holds a reference, so the channel is closed when
+/// the last of them stops, including by panicking.
+struct CloseOnDrop<T> {
+    output: WorkChannel<T>,
+}
+
+impl<T> Drop for CloseOnDrop<T> {
+    fn drop(&mut self) {
+        self.output.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{self, AtomicUsize};
+    use std::sync::{mpsc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::pool::lock;
+
+    /// Far longer than any pipeline here should take to stop
+    const PATIENCE: Duration = Duration::from_secs(10);
+
+    /// Join `pipeline` on another thread, failing the test if that takes too long
+    fn join_promptly<T: Send + 'static>(pipeline: Pipeline<T>) -> Vec<Result<(), JoinError>> {
+        let (sender, joined) = mpsc::channel();
+        thread::spawn(move || sender.send(pipeline.join(false)).unwrap());
+
+        joined.recv_timeout(PATIENCE).unwrap()
+    }
+
+    #[test]
+    fn stages_deliver_items_in_order() {
+        let pipeline = Pipeline::from_items(0..100)
+            .stage(1, |item| item * 2)
+            .stage(1, |item| item + 1)
+            .capacity(4)
+            .build();
+
+        let items: Vec<_> = pipeline.iter().collect();
+
+        assert_eq!(items, (0..100).map(|item| item * 2 + 1).collect::<Vec<_>>());
+        assert_eq!(pipeline.stages(), 3);
+        assert!(join_promptly(pipeline).iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn terminate_drains_items_in_flight() {
+        let pipeline = Pipeline::from_items(0..)
+            .stage(2, |item| item)
+            .capacity(1)
+            .build();
+
+        for _ in 0..5 {
+            assert!(pipeline.recv().is_some());
+        }
+
+        pipeline.terminate();
+
+        // Ends once the items already produced have been taken
+        pipeline.iter().for_each(drop);
+        assert!(join_promptly(pipeline).iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn terminating_the_flag_set_stops_the_source() {
+        let pipeline = Pipeline::from_items(0..)
+            .stage(1, |item| item)
+            .capacity(1)
+            .build();
+        assert!(pipeline.recv().is_some());
+
+        pipeline.as_ref().terminate();
+
+        pipeline.iter().for_each(drop);
+        assert!(join_promptly(pipeline).iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn abort_stops_every_stage() {
+        let pipeline = Pipeline::from_items(0..)
+            .stage(1, |item| item)
+            .capacity(1)
+            .build();
+        assert!(pipeline.recv().is_some());
+
+        pipeline.abort();
+
+        assert!(join_promptly(pipeline).iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn abort_wakes_a_source_blocked_on_a_full_stage() {
+        let (release, gate) = mpsc::channel::<()>();
+        let gate = Mutex::new(gate);
+        let produced = Arc::new(AtomicUsize::new(0));
+
+        let pipeline = Pipeline::source({
+            let produced = Arc::clone(&produced);
+
+            move |output, flag| {
+                for item in 0.. {
+                    if flag.should_stop() || output.push(item).is_err() {
+                        break;
+                    }
+                    produced.fetch_add(1, atomic::Ordering::SeqCst);
+                }
+            }
+        })
+        .stage(1, move |item| {
+            let _ = lock(&gate).recv();
+            item
+        })
+        .capacity(1)
+        .build();
+
+        // One item held by the stuck stage, one queued, and the source blocked on the next
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(produced.load(atomic::Ordering::SeqCst), 2);
+
+        pipeline.abort();
+        drop(release);
+
+        assert!(join_promptly(pipeline).iter().all(Result::is_ok));
+        assert_eq!(produced.load(atomic::Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn a_panicking_stage_closes_its_output() {
+        let pipeline = Pipeline::from_items(0..10)
+            .stage(1, |item| {
+                assert_ne!(item, 3, "expected in this test");
+                item
+            })
+            .build();
+
+        assert_eq!(pipeline.iter().collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(
+            join_promptly(pipeline)
+                .iter()
+                .filter(|result| result.is_err())
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn fan_out_processes_every_item() {
+        let (group, output) = fan_out(0..20, 3, |item| item + 1);
+
+        let mut items: Vec<_> = output.iter().collect();
+        items.sort_unstable();
+
+        assert_eq!(items, (1..=20).collect::<Vec<_>>());
+        assert!(group.join(false).iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn fan_in_merges_until_every_input_is_closed() {
+        let inputs: Vec<_> = (0..2)
+            .map(|_| WorkChannel::new(Arc::new(AtomicBool::new(false)), ClosePolicy::Drain))
+            .collect();
+
+        let (group, merged) = fan_in(inputs.clone());
+
+        for (offset, input) in inputs.iter().enumerate() {
+            input.push(offset).unwrap();
+            input.push(offset + 10).unwrap();
+            input.close();
+        }
+
+        let mut items: Vec<_> = merged.iter().collect();
+        items.sort_unstable();
+
+        assert_eq!(items, [0, 1, 10, 11]);
+        assert!(group.join(false).iter().all(Result::is_ok));
+    }
+}
@@ -0,0 +1,817 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{self, AtomicBool, AtomicU64, AtomicUsize};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, PoisonError, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::cancel;
+use crate::{FlagSet, HealthSource, Join, JoinError, Terminate, ThreadStatus};
+
+use queue::{JobQueue, Local, QueuedJob};
+
+mod queue;
+
+/// A type-erased job, returning whether it ran without panicking
+type Job = Box<dyn FnOnce(Arc<AtomicBool>) -> bool + Send + 'static>;
+
+/// A set of terminable worker threads pulling jobs from a shared queue
+///
+/// Each worker has its own deque of jobs and steals from its siblings once
+/// that runs dry, which keeps contention low for large numbers of small jobs.
+///
+/// Every job receives its own termination flag, which is raised either when
+/// that job is cancelled through its `JobHandle` or when the whole pool is
+/// terminated, so long running jobs can stop early.
+///
+/// A pool built with more `max_threads` than `min_threads` is elastic: extra
+/// workers are spawned while the queue backs up and retire again after being
+/// idle for the configured timeout.
+pub struct TerminablePool {
+    pub(crate) _shared: Arc<Shared>,
+    pub(crate) _flags: FlagSet,
+}
+
+pub(crate) struct Shared {
+    queue: JobQueue,
+    running: Mutex<HashMap<u64, Arc<AtomicBool>>>,
+    threads: Mutex<Vec<JoinHandle<()>>>,
+    next_id: AtomicU64,
+    live: AtomicUsize,
+    idle: AtomicUsize,
+    submitted: AtomicU64,
+    completed: AtomicU64,
+    failed: AtomicU64,
+    cancelled: AtomicU64,
+    terminated_at: Mutex<Option<Instant>>,
+    min_threads: usize,
+    max_threads: usize,
+    idle_timeout: Duration,
+    closed: AtomicBool,
+    terminate_flag: Arc<AtomicBool>,
+}
+
+impl TerminablePool {
+    /// Create a pool with a fixed number of workers waiting for jobs
+    pub fn new(threads: usize) -> Self {
+        TerminablePoolBuilder::new()
+            .min_threads(threads)
+            .max_threads(threads)
+            .build()
+    }
+
+    pub fn builder() -> TerminablePoolBuilder {
+        TerminablePoolBuilder::new()
+    }
+
+    /// Queue a job with `Priority::Normal` to be run by the next idle worker
+    ///
+    /// If every worker is busy and the pool is elastic, a new worker is spawned
+    /// to pick the job up.
+    ///
+    /// ## Note
+    ///
+    /// Jobs submitted after termination has been signalled are never run, and
+    /// their handle reports `JobError::Lost`.
+    pub fn submit<R, F>(&self, job: F) -> JobHandle<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(Arc<AtomicBool>) -> R + Send + 'static,
+    {
+        self.submit_with_priority(job, Priority::Normal)
+    }
+
+    /// Queue a job that is picked up ahead of queued jobs with a lower priority
+    ///
+    /// Lower priority jobs are still taken now and then while higher priority
+    /// ones are waiting, so they cannot be starved indefinitely.
+    pub fn submit_with_priority<R, F>(&self, job: F, priority: Priority) -> JobHandle<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(Arc<AtomicBool>) -> R + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let id = self._shared.next_id.fetch_add(1, atomic::Ordering::SeqCst);
+        let flag = Arc::new(AtomicBool::new(false));
+
+        if !self._shared.terminate_flag.load(atomic::Ordering::SeqCst) {
+            let run: Job = Box::new(move |flag| {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| job(flag)));
+                let succeeded = result.is_ok();
+                let _ = sender.send(result);
+                succeeded
+            });
+
+            self._shared
+                .submitted
+                .fetch_add(1, atomic::Ordering::SeqCst);
+            self._shared.queue.push(QueuedJob {
+                id,
+                priority,
+                flag: Arc::clone(&flag),
+                run,
+            });
+
+            // Termination may have been signalled while the job was pushed, after
+            // the workers drained the queue for the last time, so it'd never be
+            // dropped and its handle would wait for as long as the pool lives
+            if self._shared.terminate_flag.load(atomic::Ordering::SeqCst) {
+                self._shared.queue.remove(id);
+            } else if self._shared.queue.len() > self._shared.idle.load(atomic::Ordering::SeqCst) {
+                Shared::grow(&self._shared);
+            }
+        }
+
+        JobHandle {
+            _receiver: receiver,
+            _flag: flag,
+            _id: id,
+            _shared: Arc::downgrade(&self._shared),
+        }
+    }
+
+    /// Signal all workers and running jobs to terminate
+    ///
+    /// Workers stop taking jobs from the queue immediately, any jobs still
+    /// queued are dropped, and the flag of every running job is raised. The
+    /// workers are also unparked, so a job waiting in `std::thread::park` can
+    /// notice its flag straight away.
+    ///
+    /// ## Note
+    ///
+    /// Running jobs will only terminate if they check the flag passed to them.
+    pub fn terminate(&self) {
+        self._flags.terminate();
+    }
+
+    /// Take a snapshot of the pool's job counters and worker activity
+    pub fn stats(&self) -> PoolStats {
+        let shared = &self._shared;
+        let live = shared.live.load(atomic::Ordering::SeqCst);
+        let idle = shared.idle.load(atomic::Ordering::SeqCst).min(live);
+
+        PoolStats {
+            submitted: shared.submitted.load(atomic::Ordering::SeqCst),
+            completed: shared.completed.load(atomic::Ordering::SeqCst),
+            failed: shared.failed.load(atomic::Ordering::SeqCst),
+            cancelled: shared.cancelled.load(atomic::Ordering::SeqCst),
+            queued: shared.queue.len(),
+            busy_workers: live - idle,
+            idle_workers: idle,
+            since_terminate: lock(&shared.terminated_at).map(|at| at.elapsed()),
+        }
+    }
+
+    /// Status of the current worker at `index`, `None` if `index` is out of bounds
+    ///
+    /// Workers are listed in spawn order, so indices shift as retired
+    /// workers are cleared out, which happens whenever a new one is spawned.
+    pub fn status(&self, index: usize) -> Option<ThreadStatus> {
+        let threads = lock(&self._shared.threads);
+        let thread = threads.get(index)?;
+
+        Some(ThreadStatus::of(
+            thread.is_finished(),
+            &self._shared.terminate_flag,
+        ))
+    }
+
+    /// Snapshot of every current worker's status, in spawn order
+    ///
+    /// Workers are `Running` whether they are busy or waiting for a job.
+    pub fn statuses(&self) -> Vec<ThreadStatus> {
+        lock(&self._shared.threads)
+            .iter()
+            .map(|thread| ThreadStatus::of(thread.is_finished(), &self._shared.terminate_flag))
+            .collect()
+    }
+
+    /// Join all workers, optionally signalling termination
+    ///
+    /// Without termination the workers first finish every job still in the
+    /// queue, since no further jobs can be submitted once the pool is consumed.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec` with one result per current worker
+    pub fn join(self, signal_terminate: bool) -> Vec<Result<(), JoinError>> {
+        if signal_terminate {
+            self.terminate();
+        }
+
+        self._shared.closed.store(true, atomic::Ordering::SeqCst);
+        self._shared.queue.wake_all();
+
+        let threads = std::mem::take(&mut *lock(&self._shared.threads));
+
+        threads.into_iter().map(JoinHandle::join).collect()
+    }
+
+    /// Finish every queued job, then join all workers
+    ///
+    /// No new jobs can be submitted once this is called, and nothing already
+    /// queued is lost. Equivalent to `join(false)`.
+    pub fn shutdown_drain(self) -> Vec<Result<(), JoinError>> {
+        self.join(false)
+    }
+
+    /// Signal termination, join all workers and hand back the jobs that never started
+    ///
+    /// Running jobs have their flag raised, while queued jobs are taken out of
+    /// the queue instead of being dropped, so they can be run or stored elsewhere.
+    pub fn shutdown_now(self) -> Vec<PendingJob> {
+        let pending = self
+            ._shared
+            .queue
+            .drain()
+            .into_iter()
+            .map(|job| PendingJob { _job: job })
+            .collect();
+
+        self.join(true);
+
+        pending
+    }
+}
+
+impl AsRef<FlagSet> for TerminablePool {
+    /// A set without members, whose termination terminates the pool
+    fn as_ref(&self) -> &FlagSet {
+        &self._flags
+    }
+}
+
+impl Terminate for TerminablePool {
+    fn terminate(&self) {
+        TerminablePool::terminate(self);
+    }
+}
+
+impl Join for TerminablePool {
+    type Output = Vec<Result<(), JoinError>>;
+
+    fn join(self) -> Self::Output {
+        TerminablePool::join(self, false)
+    }
+}
+
+impl HealthSource for TerminablePool {
+    fn healthy(&self) -> bool {
+        !self.shutting_down()
+            && self._shared.live.load(atomic::Ordering::SeqCst) >= self._shared.min_threads
+    }
+
+    fn shutting_down(&self) -> bool {
+        self._shared.terminate_flag.load(atomic::Ordering::SeqCst)
+    }
+}
+
+impl fmt::Debug for TerminablePool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TerminablePool")
+            .field("live", &self._shared.live.load(atomic::Ordering::SeqCst))
+            .field("_terminate_flag", &self._shared.terminate_flag)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A snapshot of a `TerminablePool`'s activity, returned by `TerminablePool::stats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Jobs accepted by `submit` or `submit_with_priority`
+    pub submitted: u64,
+    /// Jobs that ran to completion
+    pub completed: u64,
+    /// Jobs that panicked
+    pub failed: u64,
+    /// Jobs cancelled through their `JobHandle` before they started
+    pub cancelled: u64,
+    /// Jobs waiting to be picked up by a worker
+    pub queued: usize,
+    /// Workers currently running a job
+    pub busy_workers: usize,
+    /// Workers currently waiting for a job
+    pub idle_workers: usize,
+    /// Time since termination was first signalled, if it has been
+    pub since_terminate: Option<Duration>,
+}
+
+/// How urgently a job submitted to a `TerminablePool` should be run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+/// Builder for a `TerminablePool`, used to configure how many workers it runs
+#[derive(Debug, Clone)]
+pub struct TerminablePoolBuilder {
+    min_threads: usize,
+    max_threads: usize,
+    idle_timeout: Duration,
+}
+
+impl TerminablePoolBuilder {
+    /// Create a new `TerminablePoolBuilder` for a pool with a single worker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of workers that are always kept running
+    pub fn min_threads(mut self, threads: usize) -> Self {
+        self.min_threads = threads;
+        self
+    }
+
+    /// Upper limit on workers spawned while the queue is backed up
+    ///
+    /// Values below `min_threads` are raised to `min_threads`.
+    pub fn max_threads(mut self, threads: usize) -> Self {
+        self.max_threads = threads;
+        self
+    }
+
+    /// How long a worker above `min_threads` may sit idle before it retires
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Spawn the `min_threads` initial workers and return the pool
+    pub fn build(self) -> TerminablePool {
+        let shared = Arc::new(Shared {
+            queue: JobQueue::new(),
+            running: Mutex::new(HashMap::new()),
+            threads: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+            live: AtomicUsize::new(0),
+            idle: AtomicUsize::new(0),
+            submitted: AtomicU64::new(0),
+            completed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            cancelled: AtomicU64::new(0),
+            terminated_at: Mutex::new(None),
+            min_threads: self.min_threads,
+            max_threads: self.max_threads.max(self.min_threads),
+            idle_timeout: self.idle_timeout,
+            closed: AtomicBool::new(false),
+            terminate_flag: Arc::new(AtomicBool::new(false)),
+        });
+
+        for _ in 0..self.min_threads {
+            Shared::grow(&shared);
+        }
+
+        let flags = FlagSet::new();
+
+        flags.on_terminate({
+            let shared = Arc::downgrade(&shared);
+
+            move || {
+                if let Some(shared) = shared.upgrade() {
+                    shared.terminate();
+                }
+            }
+        });
+
+        TerminablePool {
+            _shared: shared,
+            _flags: flags,
+        }
+    }
+}
+
+impl Default for TerminablePoolBuilder {
+    fn default() -> Self {
+        Self {
+            min_threads: 1,
+            max_threads: 1,
+            idle_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Shared {
+    fn terminate(&self) {
+        lock(&self.terminated_at).get_or_insert_with(Instant::now);
+        self.terminate_flag.store(true, atomic::Ordering::SeqCst);
+        cancel::raised(&self.terminate_flag);
+
+        for flag in lock(&self.running).values() {
+            flag.store(true, atomic::Ordering::SeqCst);
+            cancel::raised(flag);
+        }
+
+        self.queue.wake_all();
+
+        for thread in lock(&self.threads).iter() {
+            thread.thread().unpark();
+        }
+    }
+
+    /// Spawn another worker unless the pool is already at `max_threads`
+    fn grow(shared: &Arc<Self>) {
+        let reserved = shared
+            .live
+            .fetch_update(atomic::Ordering::SeqCst, atomic::Ordering::SeqCst, |live| {
+                (live < shared.max_threads).then_some(live + 1)
+            })
+            .is_ok();
+
+        if !reserved {
+            return;
+        }
+
+        let worker = {
+            let shared = Arc::clone(shared);
+            thread::spawn(move || work(&shared))
+        };
+
+        let mut threads = lock(&shared.threads);
+        threads.retain(|thread| !thread.is_finished());
+        threads.push(worker);
+    }
+
+    /// Give up a worker slot if the pool is above `min_threads`
+    fn shrink(&self) -> bool {
+        self.live
+            .fetch_update(atomic::Ordering::SeqCst, atomic::Ordering::SeqCst, |live| {
+                (live > self.min_threads).then_some(live - 1)
+            })
+            .is_ok()
+    }
+}
+
+/// Releases a worker's slot in the pool and its deque when it exits without
+/// retiring
+///
+/// Panicking jobs are caught before they reach the worker, but should the
+/// worker unwind anyway, a replacement is spawned so the pool keeps its size.
+struct LiveWorker<'a> {
+    shared: &'a Arc<Shared>,
+    local: Local,
+    retired: bool,
+}
+
+impl Drop for LiveWorker<'_> {
+    fn drop(&mut self) {
+        self.shared.queue.deregister(&self.local);
+
+        if !self.retired {
+            self.shared.live.fetch_sub(1, atomic::Ordering::SeqCst);
+        }
+
+        let stopping = self.shared.terminate_flag.load(atomic::Ordering::SeqCst)
+            || self.shared.closed.load(atomic::Ordering::SeqCst);
+
+        if thread::panicking() && !stopping {
+            Shared::grow(self.shared);
+        }
+    }
+}
+
+fn work(shared: &Arc<Shared>) {
+    let mut worker = LiveWorker {
+        shared,
+        local: shared.queue.register(),
+        retired: false,
+    };
+
+    loop {
+        if shared.terminate_flag.load(atomic::Ordering::SeqCst) {
+            shared.queue.drain();
+            return;
+        }
+
+        let Some(QueuedJob { id, flag, run, .. }) =
+            shared.queue.pop(&worker.local, &shared.terminate_flag)
+        else {
+            if shared.closed.load(atomic::Ordering::SeqCst) && shared.queue.is_empty() {
+                return;
+            }
+
+            shared.idle.fetch_add(1, atomic::Ordering::SeqCst);
+
+            let timed_out = shared.queue.wait(shared.idle_timeout, || {
+                shared.terminate_flag.load(atomic::Ordering::SeqCst)
+                    || shared.closed.load(atomic::Ordering::SeqCst)
+            });
+
+            shared.idle.fetch_sub(1, atomic::Ordering::SeqCst);
+
+            if timed_out && shared.queue.is_empty() && shared.shrink() {
+                worker.retired = true;
+                return;
+            }
+
+            continue;
+        };
+
+        if flag.load(atomic::Ordering::SeqCst) {
+            shared.cancelled.fetch_add(1, atomic::Ordering::SeqCst);
+            continue;
+        }
+
+        lock(&shared.running).insert(id, Arc::clone(&flag));
+
+        // Termination may have been signalled after the job was taken from the
+        // queue but before it was registered as running
+        if shared.terminate_flag.load(atomic::Ordering::SeqCst) {
+            flag.store(true, atomic::Ordering::SeqCst);
+            cancel::raised(&flag);
+        }
+
+        let counter = if run(flag) {
+            &shared.completed
+        } else {
+            &shared.failed
+        };
+
+        counter.fetch_add(1, atomic::Ordering::SeqCst);
+        lock(&shared.running).remove(&id);
+    }
+}
+
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A job that was still queued when its pool was shut down with `shutdown_now`
+///
+/// Dropping it without running it makes its `JobHandle` report `JobError::Lost`.
+pub struct PendingJob {
+    _job: QueuedJob,
+}
+
+impl PendingJob {
+    /// Run the job on the current thread, sending its result to its `JobHandle`
+    pub fn run(self) {
+        let QueuedJob { flag, run, .. } = self._job;
+        run(flag);
+    }
+}
+
+impl fmt::Debug for PendingJob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingJob")
+            .field("_id", &self._job.id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A handle to the result of a job submitted to a `TerminablePool`
+///
+/// The handle can also cancel its job without affecting the rest of the pool.
+pub struct JobHandle<R> {
+    pub(crate) _receiver: mpsc::Receiver<thread::Result<R>>,
+    pub(crate) _flag: Arc<AtomicBool>,
+    pub(crate) _id: u64,
+    pub(crate) _shared: Weak<Shared>,
+}
+
+impl<R> JobHandle<R> {
+    /// Cancel this job
+    ///
+    /// A job that has not started yet is removed from the queue and never runs,
+    /// while a running job has its termination flag raised.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the job was removed from the queue before it started
+    pub fn cancel(&self) -> bool {
+        self._flag.store(true, atomic::Ordering::SeqCst);
+        cancel::raised(&self._flag);
+
+        let Some(shared) = self._shared.upgrade() else {
+            return false;
+        };
+
+        let removed = shared.queue.remove(self._id).is_some();
+
+        if removed {
+            shared.cancelled.fetch_add(1, atomic::Ordering::SeqCst);
+        }
+
+        removed
+    }
+
+    /// Check whether this job has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self._flag.load(atomic::Ordering::SeqCst)
+    }
+
+    /// Wait for the job to finish and return its result
+    ///
+    /// A cancelled job that still finished running returns its result as normal.
+    pub fn join(self) -> Result<R, JobError> {
+        match self._receiver.recv() {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(payload)) => Err(JobError::Panicked(payload)),
+            Err(_) if self.is_cancelled() => Err(JobError::Cancelled),
+            Err(_) => Err(JobError::Lost),
+        }
+    }
+}
+
+impl<R> Join for JobHandle<R> {
+    type Output = Result<R, JobError>;
+
+    fn join(self) -> Self::Output {
+        JobHandle::join(self)
+    }
+}
+
+impl<R> fmt::Debug for JobHandle<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JobHandle")
+            .field("_id", &self._id)
+            .field("_flag", &self._flag)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Reasons a job submitted to a `TerminablePool` did not produce a result
+#[derive(Debug)]
+pub enum JobError {
+    /// The job was dropped before it ran because the pool was terminated
+    Lost,
+    /// The job was cancelled through its `JobHandle` before producing a result
+    Cancelled,
+    /// The job panicked, carrying the panic payload
+    ///
+    /// The worker that ran it carries on with the next job.
+    Panicked(Box<dyn Any + Send + 'static>),
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::Lost => write!(f, "job was dropped before producing a result"),
+            JobError::Cancelled => write!(f, "job was cancelled before producing a result"),
+            JobError::Panicked(_) => write!(f, "job panicked"),
+        }
+    }
+}
+
+impl Error for JobError {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{SleepOutcome, Token};
+
+    /// Long enough that a test only passes this quickly if the pool did its job
+    const PATIENCE: Duration = Duration::from_secs(10);
+
+    /// Occupy a worker until the returned sender is dropped, once it has started
+    fn block(pool: &TerminablePool) -> mpsc::Sender<()> {
+        let (started, has_started) = mpsc::channel();
+        let (release, released) = mpsc::channel::<()>();
+
+        pool.submit(move |_| {
+            started.send(()).unwrap();
+            let _ = released.recv();
+        });
+
+        has_started.recv_timeout(PATIENCE).unwrap();
+        release
+    }
+
+    #[test]
+    fn jobs_run_in_priority_order() {
+        let pool = TerminablePool::new(1);
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let release = block(&pool);
+
+        for (name, priority) in [
+            ("low", Priority::Low),
+            ("normal", Priority::Normal),
+            ("high", Priority::High),
+        ] {
+            let order = Arc::clone(&order);
+            pool.submit_with_priority(move |_| lock(&order).push(name), priority);
+        }
+
+        drop(release);
+        pool.join(false);
+
+        assert_eq!(*lock(&order), ["high", "normal", "low"]);
+    }
+
+    #[test]
+    fn idle_workers_steal_from_busy_ones() {
+        let pool = TerminablePool::new(2);
+        let release = block(&pool);
+        let (done, finished) = mpsc::channel();
+
+        // Half of these land on the blocked worker's deque
+        for id in 0..8 {
+            let done = done.clone();
+            pool.submit(move |_| done.send(id).unwrap());
+        }
+
+        let mut ids: Vec<_> = (0..8)
+            .map(|_| finished.recv_timeout(PATIENCE).unwrap())
+            .collect();
+        ids.sort_unstable();
+
+        assert_eq!(ids, (0..8).collect::<Vec<_>>());
+
+        drop(release);
+        pool.join(false);
+    }
+
+    #[test]
+    fn terminate_wakes_running_jobs() {
+        let pool = TerminablePool::new(1);
+        let (started, has_started) = mpsc::channel();
+
+        let job = pool.submit(move |flag| {
+            started.send(()).unwrap();
+            flag.sleep(PATIENCE * 6)
+        });
+
+        has_started.recv_timeout(PATIENCE).unwrap();
+        let start = Instant::now();
+        pool.terminate();
+
+        assert_eq!(job.join().unwrap(), SleepOutcome::Terminated);
+        assert!(start.elapsed() < PATIENCE);
+    }
+
+    #[test]
+    fn jobs_submitted_during_terminate_are_not_stranded() {
+        for _ in 0..50 {
+            let pool = TerminablePool::new(1);
+            let stop = AtomicBool::new(false);
+
+            let handles = thread::scope(|scope| {
+                let submitter = scope.spawn(|| {
+                    let mut handles = Vec::new();
+
+                    while !stop.load(atomic::Ordering::SeqCst) {
+                        handles.push(pool.submit(|_| ()));
+                    }
+
+                    handles
+                });
+
+                thread::sleep(Duration::from_millis(1));
+                pool.terminate();
+                thread::sleep(Duration::from_millis(1));
+                stop.store(true, atomic::Ordering::SeqCst);
+
+                submitter.join().unwrap()
+            });
+
+            let (sender, joined) = mpsc::channel();
+            thread::spawn(move || {
+                for handle in handles {
+                    let _ = handle.join();
+                }
+                sender.send(()).unwrap();
+            });
+
+            // The pool is still alive, so a job stranded in its queue blocks its handle
+            assert_eq!(joined.recv_timeout(PATIENCE), Ok(()));
+            drop(pool);
+        }
+    }
+
+    #[test]
+    fn shutdown_now_hands_back_queued_jobs() {
+        let pool = TerminablePool::new(1);
+        let (started, has_started) = mpsc::channel();
+
+        // Keeps the only worker busy until `shutdown_now` terminates it
+        pool.submit(move |flag| {
+            started.send(()).unwrap();
+            flag.sleep(PATIENCE * 6)
+        });
+
+        has_started.recv_timeout(PATIENCE).unwrap();
+        let handles: Vec<_> = (0..3).map(|id| pool.submit(move |_| id)).collect();
+        let pending = pool.shutdown_now();
+
+        assert_eq!(pending.len(), 3);
+
+        for job in pending {
+            job.run();
+        }
+
+        let results: Vec<_> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        assert_eq!(results, [0, 1, 2]);
+    }
+}
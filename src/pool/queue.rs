@@ -0,0 +1,505 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::Duration;
+
+use super::{lock, Job, Priority};
+
+/// How many times a waiting lane may be passed over for a higher priority one
+/// before its oldest job is taken anyway
+const STARVATION_LIMIT: usize = 8;
+
+pub(crate) struct QueuedJob {
+    pub(crate) id: u64,
+    pub(crate) priority: Priority,
+    pub(crate) flag: Arc<AtomicBool>,
+    pub(crate) run: Job,
+}
+
+/// A worker's own deque, which siblings steal from when they run out of work
+pub(crate) type Local = Arc<Mutex<Lanes>>;
+
+/// One FIFO lane of jobs per priority
+///
+/// Jobs are taken from the highest priority lane that has any, except that a
+/// lane passed over `STARVATION_LIMIT` times in a row is served next, so low
+/// priority jobs still make progress under a steady stream of urgent ones.
+#[derive(Default)]
+pub(crate) struct Lanes {
+    lanes: [VecDeque<QueuedJob>; 3],
+    passed_over: [usize; 3],
+}
+
+impl Lanes {
+    fn push(&mut self, job: QueuedJob) {
+        self.lanes[job.priority as usize].push_back(job);
+    }
+
+    fn pop(&mut self) -> Option<QueuedJob> {
+        let served = self.next_lane()?;
+        self.serve(served);
+
+        self.lanes[served].pop_front()
+    }
+
+    /// The lane `pop` takes from next, if any has jobs
+    fn next_lane(&self) -> Option<usize> {
+        let highest = self.lanes.iter().position(|lane| !lane.is_empty())?;
+
+        let starved = (highest + 1..self.lanes.len())
+            .find(|&lane| !self.lanes[lane].is_empty() && self.is_starved(lane));
+
+        Some(starved.unwrap_or(highest))
+    }
+
+    fn is_starved(&self, lane: usize) -> bool {
+        self.passed_over[lane] >= STARVATION_LIMIT
+    }
+
+    /// Count a job taken from `served` against the lanes it was preferred over
+    fn serve(&mut self, served: usize) {
+        for lane in 0..self.lanes.len() {
+            if lane == served || self.lanes[lane].is_empty() {
+                self.passed_over[lane] = 0;
+            } else if lane > served {
+                self.passed_over[lane] += 1;
+            }
+        }
+    }
+
+    /// Take the oldest job from the most urgent lane above `lane`
+    fn pop_above(&mut self, lane: usize) -> Option<QueuedJob> {
+        self.lanes[..lane].iter_mut().find_map(VecDeque::pop_front)
+    }
+
+    /// Take a job for another worker, preferring the most urgent
+    fn steal(&mut self) -> Option<QueuedJob> {
+        self.lanes.iter_mut().find_map(VecDeque::pop_back)
+    }
+
+    fn remove(&mut self, id: u64) -> Option<QueuedJob> {
+        self.lanes.iter_mut().find_map(|lane| {
+            let index = lane.iter().position(|job| job.id == id)?;
+            lane.remove(index)
+        })
+    }
+
+    fn take(&mut self) -> impl Iterator<Item = QueuedJob> {
+        std::mem::take(&mut self.lanes).into_iter().flatten()
+    }
+}
+
+/// Job storage for a pool: one deque per worker plus a shared injector queue
+///
+/// Submitted jobs are spread over the workers' deques, so workers mostly take
+/// from their own deque instead of contending on one central queue. The
+/// injector holds jobs submitted while no worker is registered, and jobs left
+/// behind by workers that exit.
+///
+/// Priorities hold across deques: a worker takes a more urgent job from the
+/// injector or a sibling before its own less urgent ones.
+pub(crate) struct JobQueue {
+    injector: Mutex<Lanes>,
+    locals: Mutex<Vec<Local>>,
+    pending: AtomicUsize,
+    /// Jobs waiting in each priority lane, over every deque
+    pending_by_lane: [AtomicUsize; 3],
+    next_local: AtomicUsize,
+    available: Condvar,
+}
+
+impl JobQueue {
+    pub(crate) fn new() -> Self {
+        Self {
+            injector: Mutex::new(Lanes::default()),
+            locals: Mutex::new(Vec::new()),
+            pending: AtomicUsize::new(0),
+            pending_by_lane: Default::default(),
+            next_local: AtomicUsize::new(0),
+            available: Condvar::new(),
+        }
+    }
+
+    /// Number of jobs waiting to be picked up
+    pub(crate) fn len(&self) -> usize {
+        self.pending.load(atomic::Ordering::SeqCst)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add a job to the next worker's deque in turn, and wake an idle worker
+    pub(crate) fn push(&self, job: QueuedJob) {
+        {
+            // Pushing while holding the registry means a worker can't deregister
+            // its deque between us choosing it and pushing to it
+            let locals = lock(&self.locals);
+
+            self.pending.fetch_add(1, atomic::Ordering::SeqCst);
+            self.pending_by_lane[job.priority as usize].fetch_add(1, atomic::Ordering::SeqCst);
+
+            if locals.is_empty() {
+                lock(&self.injector).push(job);
+            } else {
+                let next = self.next_local.fetch_add(1, atomic::Ordering::Relaxed);
+                lock(&locals[next % locals.len()]).push(job);
+            }
+        }
+
+        let _injector = lock(&self.injector);
+        self.available.notify_one();
+    }
+
+    /// Create a deque for a new worker
+    pub(crate) fn register(&self) -> Local {
+        let local = Local::default();
+        lock(&self.locals).push(Arc::clone(&local));
+        local
+    }
+
+    /// Remove an exiting worker's deque, handing its remaining jobs to the injector
+    pub(crate) fn deregister(&self, local: &Local) {
+        let mut locals = lock(&self.locals);
+        locals.retain(|other| !Arc::ptr_eq(other, local));
+
+        let mut leftover = lock(local).take().peekable();
+
+        if leftover.peek().is_some() {
+            let mut injector = lock(&self.injector);
+            leftover.for_each(|job| injector.push(job));
+            self.available.notify_all();
+        }
+    }
+
+    /// Take the next job for a worker
+    ///
+    /// The worker's own deque is tried first, unless a more urgent job waits
+    /// elsewhere, then the injector, and finally the other workers' deques.
+    /// `stop` is checked before each steal so that a terminating pool doesn't
+    /// keep searching for work.
+    pub(crate) fn pop(&self, local: &Local, stop: &AtomicBool) -> Option<QueuedJob> {
+        let urgent = self.pop_more_urgent(local, stop);
+        let job = urgent
+            .or_else(|| lock(local).pop())
+            .or_else(|| self.steal(local, stop))?;
+        self.dequeued(&job);
+
+        Some(job)
+    }
+
+    /// Take a job more urgent than any in the worker's own deque from elsewhere
+    fn pop_more_urgent(&self, local: &Local, stop: &AtomicBool) -> Option<QueuedJob> {
+        let lane = {
+            let own = lock(local);
+            let lane = own.next_lane()?;
+
+            // A starved lane is served ahead of anything more urgent
+            if own.is_starved(lane) {
+                return None;
+            }
+
+            lane
+        };
+
+        let elsewhere = self.pending_by_lane[..lane]
+            .iter()
+            .any(|pending| pending.load(atomic::Ordering::SeqCst) > 0);
+
+        if !elsewhere || stop.load(atomic::Ordering::SeqCst) {
+            return None;
+        }
+
+        // Released before locking the registry, which `push` takes first
+        let injected = lock(&self.injector).pop_above(lane);
+
+        let job = injected.or_else(|| {
+            let victims = lock(&self.locals).clone();
+
+            victims
+                .iter()
+                .filter(|victim| !Arc::ptr_eq(victim, local))
+                .find_map(|victim| lock(victim).pop_above(lane))
+        })?;
+
+        lock(local).serve(job.priority as usize);
+
+        Some(job)
+    }
+
+    /// Update the counters for a job taken off the queue
+    fn dequeued(&self, job: &QueuedJob) {
+        self.pending.fetch_sub(1, atomic::Ordering::SeqCst);
+        self.pending_by_lane[job.priority as usize].fetch_sub(1, atomic::Ordering::SeqCst);
+    }
+
+    fn steal(&self, local: &Local, stop: &AtomicBool) -> Option<QueuedJob> {
+        if stop.load(atomic::Ordering::SeqCst) {
+            return None;
+        }
+
+        if let Some(job) = lock(&self.injector).pop() {
+            return Some(job);
+        }
+
+        let victims = lock(&self.locals).clone();
+        let start = self.next_local.load(atomic::Ordering::Relaxed);
+
+        for offset in 0..victims.len() {
+            let victim = &victims[(start + offset) % victims.len()];
+
+            if Arc::ptr_eq(victim, local) {
+                continue;
+            }
+
+            if stop.load(atomic::Ordering::SeqCst) {
+                return None;
+            }
+
+            if let Some(job) = lock(victim).steal() {
+                return Some(job);
+            }
+        }
+
+        None
+    }
+
+    /// Remove a specific job that has not started yet
+    pub(crate) fn remove(&self, id: u64) -> Option<QueuedJob> {
+        let locals = lock(&self.locals);
+
+        let job = std::iter::once(&self.injector)
+            .chain(locals.iter().map(|local| &**local))
+            .find_map(|lanes| lock(lanes).remove(id))?;
+
+        self.dequeued(&job);
+
+        Some(job)
+    }
+
+    /// Remove every job that has not started yet
+    pub(crate) fn drain(&self) -> Vec<QueuedJob> {
+        let locals = lock(&self.locals);
+
+        let jobs: Vec<_> = std::iter::once(&self.injector)
+            .chain(locals.iter().map(|local| &**local))
+            .flat_map(|lanes| lock(lanes).take().collect::<Vec<_>>())
+            .collect();
+
+        jobs.iter().for_each(|job| self.dequeued(job));
+
+        jobs
+    }
+
+    /// Block until a job may be available, `interrupted` returns true or `timeout` passes
+    ///
+    /// # Returns
+    ///
+    /// `true` if the wait timed out
+    pub(crate) fn wait(&self, timeout: Duration, interrupted: impl Fn() -> bool) -> bool {
+        let injector = lock(&self.injector);
+
+        if !self.is_empty() || interrupted() {
+            return false;
+        }
+
+        let (_injector, wait) = self
+            .available
+            .wait_timeout(injector, timeout)
+            .unwrap_or_else(PoisonError::into_inner);
+
+        wait.timed_out()
+    }
+
+    /// Wake every waiting worker so it re-checks the pool's state
+    pub(crate) fn wake_all(&self) {
+        let _injector = lock(&self.injector);
+        self.available.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn job(id: u64, priority: Priority) -> QueuedJob {
+        QueuedJob {
+            id,
+            priority,
+            flag: Arc::new(AtomicBool::new(false)),
+            run: Box::new(|_| true),
+        }
+    }
+
+    fn pop_all(lanes: &mut Lanes) -> Vec<u64> {
+        std::iter::from_fn(|| lanes.pop())
+            .map(|job| job.id)
+            .collect()
+    }
+
+    #[test]
+    fn lanes_pop_by_priority_then_in_order() {
+        let mut lanes = Lanes::default();
+
+        for (id, priority) in [
+            (0, Priority::Low),
+            (1, Priority::Normal),
+            (2, Priority::High),
+            (3, Priority::Normal),
+            (4, Priority::High),
+        ] {
+            lanes.push(job(id, priority));
+        }
+
+        assert_eq!(pop_all(&mut lanes), [2, 4, 1, 3, 0]);
+    }
+
+    #[test]
+    fn lanes_serve_a_starved_lane() {
+        let mut lanes = Lanes::default();
+        lanes.push(job(0, Priority::Low));
+
+        for id in 1..=STARVATION_LIMIT as u64 + 2 {
+            lanes.push(job(id, Priority::High));
+        }
+
+        let order = pop_all(&mut lanes);
+
+        assert_eq!(order.iter().position(|&id| id == 0), Some(STARVATION_LIMIT));
+    }
+
+    #[test]
+    fn workers_steal_from_siblings() {
+        let queue = JobQueue::new();
+        let stop = AtomicBool::new(false);
+        let first = queue.register();
+        let second = queue.register();
+
+        // Spread over the two deques in turn
+        for id in 0..4 {
+            queue.push(job(id, Priority::Normal));
+        }
+
+        let mut taken: Vec<_> = std::iter::from_fn(|| queue.pop(&second, &stop))
+            .map(|job| job.id)
+            .collect();
+        taken.sort_unstable();
+
+        assert_eq!(taken, [0, 1, 2, 3]);
+        assert!(queue.is_empty());
+        assert!(queue.pop(&first, &stop).is_none());
+    }
+
+    #[test]
+    fn stealing_prefers_urgent_jobs() {
+        let queue = JobQueue::new();
+        let stop = AtomicBool::new(false);
+        let owner = queue.register();
+
+        queue.push(job(0, Priority::Low));
+        queue.push(job(1, Priority::High));
+        queue.push(job(2, Priority::Normal));
+
+        let thief = queue.register();
+        let stolen = queue.pop(&thief, &stop).map(|job| job.id);
+
+        assert_eq!(stolen, Some(1));
+        assert_eq!(lock(&owner).pop().map(|job| job.id), Some(2));
+    }
+
+    #[test]
+    fn workers_take_more_urgent_jobs_from_elsewhere_first() {
+        let queue = JobQueue::new();
+        let stop = AtomicBool::new(false);
+
+        // Lands in the injector, as no worker is registered yet
+        queue.push(job(0, Priority::High));
+
+        let first = queue.register();
+        let second = queue.register();
+
+        // One each, in turn
+        queue.push(job(1, Priority::Low));
+        queue.push(job(2, Priority::Normal));
+
+        let order: Vec<_> = std::iter::from_fn(|| queue.pop(&first, &stop))
+            .map(|job| job.id)
+            .collect();
+
+        assert_eq!(order, [0, 2, 1]);
+        assert!(queue.pop(&second, &stop).is_none());
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn starved_lanes_are_served_before_urgent_jobs_elsewhere() {
+        let queue = JobQueue::new();
+        let stop = AtomicBool::new(false);
+        let first = queue.register();
+        let second = queue.register();
+
+        // The low job lands on the first deque, then the high ones alternate
+        for id in 0..=STARVATION_LIMIT as u64 * 2 + 2 {
+            let priority = if id == 0 {
+                Priority::Low
+            } else {
+                Priority::High
+            };
+            queue.push(job(id, priority));
+        }
+
+        let order: Vec<_> = std::iter::from_fn(|| queue.pop(&first, &stop))
+            .map(|job| job.id)
+            .collect();
+
+        assert_eq!(order.iter().position(|&id| id == 0), Some(STARVATION_LIMIT));
+        assert!(queue.pop(&second, &stop).is_none());
+    }
+
+    #[test]
+    fn stopped_workers_only_take_their_own_jobs() {
+        let queue = JobQueue::new();
+        let stop = AtomicBool::new(true);
+        let owner = queue.register();
+
+        queue.push(job(0, Priority::Normal));
+
+        let thief = queue.register();
+
+        assert!(queue.pop(&thief, &stop).is_none());
+        assert_eq!(queue.pop(&owner, &stop).map(|job| job.id), Some(0));
+    }
+
+    #[test]
+    fn deregistering_hands_jobs_to_the_rest() {
+        let queue = JobQueue::new();
+        let stop = AtomicBool::new(false);
+        let leaving = queue.register();
+
+        queue.push(job(0, Priority::Normal));
+        queue.push(job(1, Priority::Normal));
+
+        let staying = queue.register();
+        queue.deregister(&leaving);
+
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.pop(&staying, &stop).map(|job| job.id), Some(0));
+        assert_eq!(queue.pop(&staying, &stop).map(|job| job.id), Some(1));
+    }
+
+    #[test]
+    fn removing_a_queued_job() {
+        let queue = JobQueue::new();
+        let _worker = queue.register();
+
+        queue.push(job(0, Priority::Normal));
+        queue.push(job(1, Priority::Normal));
+
+        assert_eq!(queue.remove(1).map(|job| job.id), Some(1));
+        assert!(queue.remove(1).is_none());
+        assert_eq!(queue.len(), 1);
+    }
+}
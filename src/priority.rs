@@ -0,0 +1,156 @@
+use std::io;
+use std::thread::JoinHandle;
+
+/// OS scheduling priority of a thread, relative to the process's other threads
+///
+/// On Linux, this picks the thread's scheduling policy: `SCHED_IDLE`,
+/// `SCHED_BATCH`, `SCHED_OTHER`, then `SCHED_RR` at its lowest and highest
+/// priority. On Windows, it maps onto the `THREAD_PRIORITY_*` level of the
+/// same name.
+///
+/// ## Note
+///
+/// Raising a thread above `Normal` usually needs elevated privileges, e.g.
+/// `CAP_SYS_NICE` on Linux.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ThreadPriority {
+    /// Only runs when nothing else wants the CPU, e.g. background indexing
+    Lowest,
+    /// Runs behind normal threads, e.g. batch work
+    BelowNormal,
+    /// The default for new threads
+    #[default]
+    Normal,
+    /// Runs ahead of normal threads
+    AboveNormal,
+    /// Runs ahead of everything but the OS itself
+    Highest,
+}
+
+impl ThreadPriority {
+    /// Set the priority of the running `thread`
+    ///
+    /// # Errors
+    ///
+    /// The OS error if the priority couldn't be set, e.g. for lack of privileges
+    pub(crate) fn apply<T>(self, thread: &JoinHandle<T>) -> io::Result<()> {
+        os::apply(self, thread)
+    }
+}
+
+/// Set the nice level of the calling thread, from `-20` (most favourable) to `19`
+///
+/// Values outside that range are clamped.
+///
+/// # Errors
+///
+/// The OS error if the nice level couldn't be set, e.g. for lack of privileges
+/// to lower it
+#[cfg(all(feature = "unix", target_os = "linux"))]
+pub(crate) fn renice_current(niceness: i32) -> io::Result<()> {
+    os::renice_current(niceness)
+}
+
+/// Scheduling policies, through `pthread_setschedparam`, and nice levels
+#[cfg(all(feature = "unix", target_os = "linux"))]
+mod os {
+    use std::io;
+    use std::os::raw::{c_int, c_uint};
+    use std::os::unix::thread::{JoinHandleExt, RawPthread};
+    use std::thread::JoinHandle;
+
+    use super::ThreadPriority;
+
+    const SCHED_OTHER: c_int = 0;
+    const SCHED_RR: c_int = 2;
+    const SCHED_BATCH: c_int = 3;
+    const SCHED_IDLE: c_int = 5;
+
+    /// `PRIO_PROCESS`, which on Linux addresses a single thread
+    const PRIO_PROCESS: c_int = 0;
+
+    #[repr(C)]
+    struct SchedParam {
+        sched_priority: c_int,
+    }
+
+    extern "C" {
+        fn pthread_setschedparam(
+            thread: RawPthread,
+            policy: c_int,
+            param: *const SchedParam,
+        ) -> c_int;
+        fn sched_get_priority_min(policy: c_int) -> c_int;
+        fn sched_get_priority_max(policy: c_int) -> c_int;
+        fn setpriority(which: c_int, who: c_uint, prio: c_int) -> c_int;
+    }
+
+    pub(super) fn apply<T>(priority: ThreadPriority, thread: &JoinHandle<T>) -> io::Result<()> {
+        // SAFETY: looking up a policy's priority range has no preconditions
+        let (policy, sched_priority) = unsafe {
+            match priority {
+                ThreadPriority::Lowest => (SCHED_IDLE, 0),
+                ThreadPriority::BelowNormal => (SCHED_BATCH, 0),
+                ThreadPriority::Normal => (SCHED_OTHER, 0),
+                ThreadPriority::AboveNormal => (SCHED_RR, sched_get_priority_min(SCHED_RR)),
+                ThreadPriority::Highest => (SCHED_RR, sched_get_priority_max(SCHED_RR)),
+            }
+        };
+
+        let param = SchedParam { sched_priority };
+
+        // SAFETY: the handle keeps the thread joinable, so its id is valid
+        match unsafe { pthread_setschedparam(thread.as_pthread_t(), policy, &param) } {
+            0 => Ok(()),
+            error => Err(io::Error::from_raw_os_error(error)),
+        }
+    }
+
+    pub(super) fn renice_current(niceness: i32) -> io::Result<()> {
+        // SAFETY: `setpriority` has no preconditions, and `0` addresses the
+        // calling thread
+        if unsafe { setpriority(PRIO_PROCESS, 0, niceness) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+/// Thread priority levels, through `SetThreadPriority`
+#[cfg(all(feature = "windows", windows))]
+mod os {
+    use std::io;
+    use std::os::windows::io::{AsRawHandle, RawHandle};
+    use std::thread::JoinHandle;
+
+    use super::ThreadPriority;
+
+    const THREAD_PRIORITY_LOWEST: i32 = -2;
+    const THREAD_PRIORITY_BELOW_NORMAL: i32 = -1;
+    const THREAD_PRIORITY_NORMAL: i32 = 0;
+    const THREAD_PRIORITY_ABOVE_NORMAL: i32 = 1;
+    const THREAD_PRIORITY_HIGHEST: i32 = 2;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetThreadPriority(thread: RawHandle, priority: i32) -> i32;
+    }
+
+    pub(super) fn apply<T>(priority: ThreadPriority, thread: &JoinHandle<T>) -> io::Result<()> {
+        let level = match priority {
+            ThreadPriority::Lowest => THREAD_PRIORITY_LOWEST,
+            ThreadPriority::BelowNormal => THREAD_PRIORITY_BELOW_NORMAL,
+            ThreadPriority::Normal => THREAD_PRIORITY_NORMAL,
+            ThreadPriority::AboveNormal => THREAD_PRIORITY_ABOVE_NORMAL,
+            ThreadPriority::Highest => THREAD_PRIORITY_HIGHEST,
+        };
+
+        // SAFETY: the join handle owns the thread handle and keeps it open
+        if unsafe { SetThreadPriority(thread.as_raw_handle(), level) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use std::fmt;
+use std::io;
+use std::process::{Child, Command, ExitStatus};
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use crate::{
+    ExitCodeFold, FlagSet, HealthSource, Join, JoinError, SleepOutcome, TerminableThreadGroup,
+    Terminate, Token,
+};
+
+/// How often a waiter thread checks whether its child has exited
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A group of child processes, each waited on by a terminable thread
+///
+/// Subprocess workers get the same group semantics as threads: `terminate`
+/// raises the waiters' flags and wakes them, and each waiter kills its child
+/// straight away, through `Child::kill`, then reaps it. A child that exits
+/// on its own is reaped as soon as it does, and joining the group gives each
+/// child's exit status, in the order they were added.
+///
+/// The waiters' flags come from a `FlagSet`, so the group can be linked with
+/// thread containers to stop both together.
+///
+/// ## Note
+///
+/// Waiters poll their child every few milliseconds rather than blocking in
+/// `Child::wait`, which couldn't be interrupted to kill it.
+pub struct TerminableProcessGroup {
+    pub(crate) _waiters: TerminableThreadGroup<io::Result<ExitStatus>>,
+    pub(crate) _ids: Vec<u32>,
+}
+
+impl TerminableProcessGroup {
+    /// Create a group with no processes
+    pub fn new() -> Self {
+        Self {
+            _waiters: TerminableThreadGroup::default(),
+            _ids: Vec::new(),
+        }
+    }
+
+    /// Spawn `command` as a child process of the group
+    ///
+    /// If the group has already been terminated, the child is killed straight
+    /// away.
+    ///
+    /// # Returns
+    ///
+    /// The index of the child within the group
+    ///
+    /// # Errors
+    ///
+    /// The OS error if the process or its waiter thread couldn't be spawned
+    pub fn spawn(&mut self, command: &mut Command) -> io::Result<usize> {
+        self.push(command.spawn()?)
+    }
+
+    /// Hand an already spawned child over to the group
+    ///
+    /// # Returns
+    ///
+    /// The index of the child within the group
+    ///
+    /// # Errors
+    ///
+    /// The OS error if the waiter thread couldn't be spawned, in which case
+    /// the child is killed and the group is left unchanged
+    pub fn push(&mut self, child: Child) -> io::Result<usize> {
+        let id = child.id();
+        let child = Waited { child };
+
+        let index = self
+            ._waiters
+            .spawn_additional(move |flag| child.wait_or_kill(&flag))?;
+
+        self._ids.push(id);
+        Ok(index)
+    }
+
+    /// Signal all waiters to kill their child
+    pub fn terminate(&self) {
+        self._waiters.terminate();
+    }
+
+    /// Signal only the waiter at `index` to kill its child, leaving the rest running
+    ///
+    /// # Returns
+    ///
+    /// `false` if `index` is out of bounds
+    pub fn terminate_one(&self, index: usize) -> bool {
+        self._waiters.terminate_one(index)
+    }
+
+    /// The OS process id of each child, in the order they were added
+    ///
+    /// Ids of children that have exited may have been reused by the OS.
+    pub fn ids(&self) -> &[u32] {
+        &self._ids
+    }
+
+    /// Whether the child at `index` has exited and been reaped, `None` if `index` is out of bounds
+    pub fn is_finished(&self, index: usize) -> Option<bool> {
+        self._waiters
+            ._threads
+            .get(index)
+            .map(|waiter| waiter.is_finished())
+    }
+
+    /// Number of children in the group
+    pub fn len(&self) -> usize {
+        self._ids.len()
+    }
+
+    /// Check whether the group has no children
+    pub fn is_empty(&self) -> bool {
+        self._ids.is_empty()
+    }
+
+    /// Wait for every child to exit, optionally killing them first
+    ///
+    /// # Returns
+    ///
+    /// A `Vec` containing the exit status of each child, in the order they
+    /// were added, or the OS error if waiting on or killing it failed
+    pub fn join(self, signal_terminate: bool) -> Vec<Result<io::Result<ExitStatus>, JoinError>> {
+        self._waiters.join(signal_terminate)
+    }
+
+    /// Wait for every child like `join`, folding their exit statuses into a process exit code
+    ///
+    /// Children that couldn't be waited on count as failures, as do those
+    /// killed by a signal, including by `terminate`.
+    pub fn join_exit_code(self, signal_terminate: bool, fold: ExitCodeFold) -> i32 {
+        self._waiters.join_exit_code(signal_terminate, fold)
+    }
+}
+
+impl Default for TerminableProcessGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Terminate for TerminableProcessGroup {
+    fn terminate(&self) {
+        TerminableProcessGroup::terminate(self);
+    }
+}
+
+impl Join for TerminableProcessGroup {
+    type Output = Vec<Result<io::Result<ExitStatus>, JoinError>>;
+
+    fn join(self) -> Self::Output {
+        TerminableProcessGroup::join(self, false)
+    }
+}
+
+impl HealthSource for TerminableProcessGroup {
+    fn healthy(&self) -> bool {
+        self._waiters.healthy()
+    }
+
+    fn shutting_down(&self) -> bool {
+        self._waiters.shutting_down()
+    }
+}
+
+impl AsRef<FlagSet> for TerminableProcessGroup {
+    fn as_ref(&self) -> &FlagSet {
+        &self._waiters._flags
+    }
+}
+
+impl fmt::Debug for TerminableProcessGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TerminableProcessGroup")
+            .field("ids", &self._ids)
+            .field("terminated", &self._waiters._flags.is_terminated())
+            .finish()
+    }
+}
+
+/// A child owned by its waiter, killed if dropped before being reaped
+///
+/// Keeps a child from outliving its waiter, e.g. when the waiter thread
+/// couldn't be spawned.
+pub(crate) struct Waited {
+    pub(crate) child: Child,
+}
+
+impl Waited {
+    /// Wait for the child to exit, killing it once `flag` is raised
+    pub(crate) fn wait_or_kill(mut self, flag: &AtomicBool) -> io::Result<ExitStatus> {
+        loop {
+            if let Some(status) = self.child.try_wait()? {
+                return Ok(status);
+            }
+
+            if flag.sleep(POLL_INTERVAL) == SleepOutcome::Terminated {
+                // Fails only if the child has already exited, which `wait` reports
+                let _ = self.child.kill();
+                return self.child.wait();
+            }
+        }
+    }
+}
+
+impl Drop for Waited {
+    fn drop(&mut self) {
+        // Reaped children report their status again rather than `None`
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+use crate::pool::lock;
+
+/// A one-shot latch a worker raises once it has finished setting up
+///
+/// Cheap to clone; all clones refer to the same latch. Other threads can wait
+/// for it, e.g. to only start once the threads they depend on are ready.
+#[derive(Debug, Clone, Default)]
+pub struct ReadySignal {
+    inner: Arc<Latch>,
+}
+
+#[derive(Debug, Default)]
+struct Latch {
+    ready: AtomicBool,
+    waiters: Mutex<Vec<Thread>>,
+}
+
+impl ReadySignal {
+    /// Create a latch that isn't ready yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark the latch as ready, waking everyone waiting on it
+    pub fn ready(&self) {
+        self.inner.ready.store(true, atomic::Ordering::SeqCst);
+
+        for waiter in lock(&self.inner.waiters).iter() {
+            waiter.unpark();
+        }
+    }
+
+    /// Check whether the latch has been marked as ready
+    pub fn is_ready(&self) -> bool {
+        self.inner.ready.load(atomic::Ordering::SeqCst)
+    }
+
+    /// Block until the latch is ready, or until `flag` is raised
+    ///
+    /// Waiting is done by parking, so unparking the thread (as terminating a
+    /// container does) lets it notice `flag` straight away.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the latch is ready, `false` if `flag` was raised first
+    pub fn wait(&self, flag: &AtomicBool) -> bool {
+        self.wait_until(Some(flag), None)
+    }
+
+    /// Block until the latch is ready, or until `timeout` passes
+    ///
+    /// # Returns
+    ///
+    /// `true` if the latch is ready, `false` if the timeout passed first
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        self.wait_until(None, Some(Instant::now() + timeout))
+    }
+
+    pub(crate) fn wait_until(&self, flag: Option<&AtomicBool>, deadline: Option<Instant>) -> bool {
+        if self.is_ready() {
+            return true;
+        }
+
+        let current = thread::current();
+        lock(&self.inner.waiters).push(current.clone());
+
+        let ready = loop {
+            if self.is_ready() {
+                break true;
+            }
+
+            if flag.is_some_and(|flag| flag.load(atomic::Ordering::SeqCst)) {
+                break false;
+            }
+
+            match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if !remaining.is_zero() => thread::park_timeout(remaining),
+                    _ => break self.is_ready(),
+                },
+                None => thread::park(),
+            }
+        };
+
+        lock(&self.inner.waiters).retain(|waiter| waiter.id() != current.id());
+
+        ready
+    }
+}
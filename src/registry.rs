@@ -0,0 +1,112 @@
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+
+use crate::pool::lock;
+use crate::{FlagSet, Terminate};
+
+/// Raised by `shutdown_all`, linked to every container passed to `register`
+static REGISTRY: OnceLock<FlagSet> = OnceLock::new();
+
+/// Containers passed to `register_shared`, terminated once the registry is raised
+static SHARED: Mutex<Vec<Weak<dyn Terminate + Send + Sync>>> = Mutex::new(Vec::new());
+
+fn registry() -> &'static FlagSet {
+    REGISTRY.get_or_init(|| {
+        let registry = FlagSet::new();
+        registry.on_terminate(terminate_shared);
+        registry
+    })
+}
+
+fn terminate_shared() {
+    let containers: Vec<_> = lock(&SHARED).iter().filter_map(Weak::upgrade).collect();
+
+    for container in containers {
+        container.terminate();
+    }
+}
+
+/// Register `container` to be terminated by `shutdown_all`
+///
+/// Lets containers built independently across an application be stopped from
+/// one place, without passing their flags through every layer. Takes any
+/// container built on a `FlagSet`, pools and supervisors included. The
+/// registry only holds on to the container weakly, so registering doesn't
+/// keep its threads around once it is dropped.
+///
+/// A container registered after `shutdown_all` is terminated straight away.
+pub fn register<C>(container: &C)
+where
+    C: AsRef<FlagSet> + ?Sized,
+{
+    registry().link(container.as_ref());
+}
+
+/// Register anything implementing `Terminate` to be terminated by `shutdown_all`
+///
+/// For types that aren't built on a `FlagSet`, e.g. an application's own
+/// services. `shutdown_all` calls their `terminate`. The registry only holds
+/// on to `container` weakly, so registering doesn't keep it alive.
+///
+/// A container registered after `shutdown_all` is terminated straight away.
+pub fn register_shared<C>(container: &Arc<C>)
+where
+    C: Terminate + Send + Sync + 'static,
+{
+    let registry = registry();
+
+    {
+        let mut shared = lock(&SHARED);
+
+        // Dropped containers are pruned here rather than left to pile up
+        shared.retain(|container| container.strong_count() > 0);
+        shared.push(Arc::downgrade(container) as Weak<dyn Terminate + Send + Sync>);
+    }
+
+    // `shutdown_all` may have gone through the list before it was pushed to
+    if registry.is_terminated() {
+        container.terminate();
+    }
+}
+
+/// Terminate every container passed to `register`, and every one registered from now on
+///
+/// The containers are terminated just as by their own `terminate`, so parked
+/// and sleeping workers wake straight away.
+///
+/// ## Note
+///
+/// The containers' threads aren't joined. Each container's owner is still
+/// responsible for joining it.
+pub fn shutdown_all() {
+    registry().terminate();
+}
+
+/// Check whether `shutdown_all` has been called
+pub fn is_shutting_down() -> bool {
+    registry().is_terminated()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Service;
+
+    impl Terminate for Service {
+        fn terminate(&self) {}
+    }
+
+    #[test]
+    fn dropped_shared_containers_are_pruned() {
+        let kept = Arc::new(Service);
+        register_shared(&kept);
+
+        for _ in 0..100 {
+            register_shared(&Arc::new(Service));
+        }
+
+        // The one kept alive, and the last one dropped since the last pruning
+        assert!(lock(&SHARED).len() <= 2);
+        drop(kept);
+    }
+}
@@ -0,0 +1,201 @@
+use std::mem;
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::{FlagSet, HealthSource, Join, JoinError, Terminate};
+
+/// A single terminable thread that keeps hold of the closure it was spawned with
+///
+/// Because the closure is stored, a worker that has finished or panicked can be
+/// started again with `restart()`, receiving a fresh termination flag each time.
+///
+/// The current worker is the only member of the thread's `FlagSet`. Terminating
+/// the set as a whole, e.g. through `shutdown_all` or a linked set, also stops
+/// every worker started by later restarts.
+#[derive(Debug)]
+pub struct RestartableThread<T, F> {
+    pub(crate) _thread: JoinHandle<T>,
+    pub(crate) _flags: FlagSet,
+    pub(crate) _func: F,
+}
+
+impl<T, F> RestartableThread<T, F>
+where
+    T: Send + 'static,
+    F: Fn(Arc<AtomicBool>) -> T + Clone + Send + 'static,
+{
+    /// Spawn a new thread running `func`, keeping the closure for later restarts
+    pub fn new(func: F) -> Self {
+        let flags = FlagSet::with_len(1);
+        let thread = Self::spawn(&func, &flags, 0, None);
+
+        Self {
+            _thread: thread,
+            _flags: flags,
+            _func: func,
+        }
+    }
+
+    /// Stop the current worker and start a new one from the stored closure
+    ///
+    /// The new worker only starts running once the current one has been
+    /// signalled to terminate and joined, so two generations never run at the
+    /// same time.
+    ///
+    /// # Returns
+    ///
+    /// The result of joining the previous worker
+    ///
+    /// # Panics
+    ///
+    /// If the new thread can't be spawned, in which case the current worker
+    /// is kept, though signalled to terminate
+    pub fn restart(&mut self) -> Result<T, JoinError> {
+        self.terminate();
+
+        self._flags.push();
+        let (previous_joined, joined) = mpsc::channel();
+        let thread = Self::spawn(&self._func, &self._flags, 1, Some(joined));
+
+        let current = self._flags.split_off(1);
+        drop(mem::replace(&mut self._flags, current));
+
+        let previous = mem::replace(&mut self._thread, thread).join();
+        let _ = previous_joined.send(());
+
+        previous
+    }
+
+    /// Spawn the worker for the member at `index`, once `previous` has been joined if given
+    fn spawn(
+        func: &F,
+        flags: &FlagSet,
+        index: usize,
+        previous: Option<Receiver<()>>,
+    ) -> JoinHandle<T> {
+        let func = func.clone();
+        let flag = Arc::clone(flags.flag(index).expect("member was just added"));
+        let tracking = flags.track(index).expect("member was just added");
+
+        thread::spawn(move || {
+            let tracking = tracking.enter();
+
+            if let Some(previous) = previous {
+                let _ = previous.recv();
+            }
+
+            let _tracking = tracking.started();
+            func(flag)
+        })
+    }
+}
+
+impl<T, F> RestartableThread<T, F> {
+    /// Signal the current worker to terminate, unparking it
+    ///
+    /// A worker started by a later `restart` gets a fresh flag.
+    ///
+    /// ## Note
+    ///
+    /// As with `TerminableThreads`, the worker only stops if it checks the flag.
+    pub fn terminate(&self) {
+        self._flags.terminate_one(0);
+    }
+
+    /// Check whether the current worker has finished, either normally or by panicking
+    pub fn is_finished(&self) -> bool {
+        self._thread.is_finished()
+    }
+
+    /// Join the current worker, optionally signalling termination
+    pub fn join(self, signal_terminate: bool) -> Result<T, JoinError> {
+        if signal_terminate {
+            self.terminate();
+        }
+
+        self._thread.join()
+    }
+}
+
+impl<T, F> Terminate for RestartableThread<T, F> {
+    fn terminate(&self) {
+        RestartableThread::terminate(self);
+    }
+}
+
+impl<T, F> Join for RestartableThread<T, F> {
+    type Output = Result<T, JoinError>;
+
+    fn join(self) -> Self::Output {
+        RestartableThread::join(self, false)
+    }
+}
+
+impl<T, F> AsRef<FlagSet> for RestartableThread<T, F> {
+    fn as_ref(&self) -> &FlagSet {
+        &self._flags
+    }
+}
+
+impl<T, F> HealthSource for RestartableThread<T, F> {
+    fn healthy(&self) -> bool {
+        !self.shutting_down() && !self.is_finished()
+    }
+
+    fn shutting_down(&self) -> bool {
+        self._flags.is_member_terminated(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{self, AtomicUsize};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::Token;
+
+    /// Far longer than any worker here should take to stop
+    const PATIENCE: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn restart_runs_one_generation_at_a_time() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let overlapped = Arc::new(AtomicBool::new(false));
+
+        let mut thread = RestartableThread::new({
+            let (running, overlapped) = (Arc::clone(&running), Arc::clone(&overlapped));
+
+            move |flag: Arc<AtomicBool>| {
+                if running.fetch_add(1, atomic::Ordering::SeqCst) > 0 {
+                    overlapped.store(true, atomic::Ordering::SeqCst);
+                }
+
+                let outcome = flag.sleep(PATIENCE * 6);
+                running.fetch_sub(1, atomic::Ordering::SeqCst);
+                outcome
+            }
+        });
+
+        for _ in 0..3 {
+            assert!(thread.restart().is_ok());
+            assert!(!thread.shutting_down());
+        }
+
+        thread.join(true).unwrap();
+        assert!(!overlapped.load(atomic::Ordering::SeqCst));
+    }
+
+    #[test]
+    fn terminating_the_flag_set_stops_later_generations() {
+        let mut thread = RestartableThread::new(|flag: Arc<AtomicBool>| flag.sleep(PATIENCE * 6));
+
+        thread.as_ref().terminate();
+        thread.restart().unwrap();
+
+        assert!(thread.shutting_down());
+        thread.join(false).unwrap();
+    }
+}
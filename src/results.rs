@@ -0,0 +1,118 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::JoinError;
+
+/// Split the results of a join into the successful values and the failures
+///
+/// Accepts the array or `Vec` returned by `join`, or any other iterator of
+/// results. Failures are paired with their position, so they can be traced
+/// back to the thread that produced them.
+pub fn partition_results<T, E, I>(results: I) -> (Vec<T>, Vec<(usize, E)>)
+where
+    I: IntoIterator<Item = Result<T, E>>,
+{
+    let mut values = Vec::new();
+    let mut failures = Vec::new();
+
+    for (index, result) in results.into_iter().enumerate() {
+        match result {
+            Ok(value) => values.push(value),
+            Err(error) => failures.push((index, error)),
+        }
+    }
+
+    (values, failures)
+}
+
+/// Error returned when one or more threads of a container panicked
+///
+/// Collects every failed thread, so all of them can be reported at once.
+#[derive(Debug)]
+pub struct JoinAllError {
+    /// Each thread that panicked, in the order the threads were joined
+    pub failures: Vec<ThreadFailure>,
+    /// Number of threads joined, including those that succeeded
+    pub total: usize,
+}
+
+/// A single thread that panicked, as part of a `JoinAllError`
+#[derive(Debug)]
+pub struct ThreadFailure {
+    /// Position of the thread within its container
+    pub index: usize,
+    /// Name of the thread, if it had one
+    pub name: Option<String>,
+    /// The panic payload
+    pub error: JoinError,
+}
+
+impl ThreadFailure {
+    /// The panic message, if the thread panicked with a string
+    pub fn message(&self) -> Option<&str> {
+        self.error
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| self.error.downcast_ref::<String>().map(String::as_str))
+    }
+}
+
+impl JoinAllError {
+    /// Collect the results of joining threads with the given names
+    ///
+    /// # Returns
+    ///
+    /// Every value if no thread panicked
+    pub(crate) fn collect<T>(
+        names: Vec<Option<String>>,
+        results: impl IntoIterator<Item = Result<T, JoinError>>,
+    ) -> Result<Vec<T>, JoinAllError> {
+        let (values, failures) = partition_results(results);
+
+        if failures.is_empty() {
+            return Ok(values);
+        }
+
+        let mut names = names;
+
+        Err(JoinAllError {
+            total: values.len() + failures.len(),
+            failures: failures
+                .into_iter()
+                .map(|(index, error)| ThreadFailure {
+                    index,
+                    name: names.get_mut(index).and_then(Option::take),
+                    error,
+                })
+                .collect(),
+        })
+    }
+}
+
+impl fmt::Display for JoinAllError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} threads panicked",
+            self.failures.len(),
+            self.total
+        )?;
+
+        for (position, failure) in self.failures.iter().enumerate() {
+            let separator = if position == 0 { ": " } else { ", " };
+            write!(f, "{separator}#{}", failure.index)?;
+
+            if let Some(name) = &failure.name {
+                write!(f, " '{name}'")?;
+            }
+
+            if let Some(message) = failure.message() {
+                write!(f, " ({message})")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for JoinAllError {}
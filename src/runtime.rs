@@ -0,0 +1,201 @@
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
+use std::sync::Arc;
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+use crate::{registry, FlagSet, JoinError, TerminableThreadGroup, ThreadMetrics};
+
+/// A worker run by `Runtime::run`, receiving its termination flag
+pub type Worker = Box<dyn FnOnce(Arc<AtomicBool>) + Send + 'static>;
+
+/// Decides when `Runtime::run` signals its workers to terminate
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeConfig {
+    /// Signal termination as soon as any worker exits, normally or by panicking
+    pub terminate_on_first_exit: bool,
+    /// Signal termination once the workers have been running for this long
+    pub deadline: Option<Duration>,
+    /// Signal termination once this set is terminated, e.g. from another thread
+    /// or through a container linked to it
+    pub shutdown: Option<Arc<FlagSet>>,
+}
+
+/// Entry point running a set of workers to completion with shared termination
+///
+/// Intended for small binaries: spawn the workers, block until they have all
+/// stopped and get a report of how each one ended.
+#[derive(Debug)]
+pub struct Runtime;
+
+impl Runtime {
+    /// Box a closure as a `Worker`, inferring the type of its flag argument
+    pub fn worker<F>(func: F) -> Worker
+    where
+        F: FnOnce(Arc<AtomicBool>) + Send + 'static,
+    {
+        Box::new(func)
+    }
+
+    /// Spawn every worker in a `TerminableThreadGroup` and block until all have finished
+    ///
+    /// Termination is signalled according to `config`, and also by Ctrl-C or
+    /// SIGTERM through `terminate_on_signal` when the `unix` or `windows`
+    /// feature is enabled, and by `shutdown_all`, since the group is passed
+    /// to `register`. Without any of these, the workers simply run until
+    /// they finish by themselves.
+    ///
+    /// Terminating the group wakes workers that are parked or sleeping on
+    /// their flag, and the calling thread is woken by each worker exiting,
+    /// so shutdown isn't held up by polling.
+    ///
+    /// # Panics
+    ///
+    /// If a worker thread couldn't be spawned
+    pub fn run(config: RuntimeConfig, workers: Vec<Worker>) -> ShutdownReport {
+        let start = Instant::now();
+        let runtime = thread::current();
+        let exited = Arc::new(AtomicUsize::new(0));
+        let mut group = TerminableThreadGroup::default();
+
+        for worker in workers {
+            let runtime = runtime.clone();
+            let exited = Arc::clone(&exited);
+
+            group
+                .spawn_additional(move |flag| {
+                    let _exit = WakeOnExit { runtime, exited };
+                    let start = Instant::now();
+                    worker(flag);
+                    start.elapsed()
+                })
+                .expect("failed to spawn thread");
+        }
+
+        if let Some(shutdown) = &config.shutdown {
+            shutdown.link(&group._flags);
+        }
+
+        registry::register(&group);
+
+        #[cfg(any(
+            all(feature = "unix", target_os = "linux"),
+            all(feature = "windows", windows)
+        ))]
+        let signals = crate::terminate_on_signal(&group).is_ok();
+
+        #[cfg(not(any(
+            all(feature = "unix", target_os = "linux"),
+            all(feature = "windows", windows)
+        )))]
+        let signals = false;
+
+        let deadline = config
+            .deadline
+            .and_then(|deadline| start.checked_add(deadline));
+
+        loop {
+            let finished = exited.load(atomic::Ordering::SeqCst);
+
+            if finished == group.len() {
+                break;
+            }
+
+            if config.terminate_on_first_exit && finished > 0 {
+                group.terminate();
+            }
+
+            match deadline {
+                Some(deadline) if !group._flags.is_terminated() => {
+                    match deadline.checked_duration_since(Instant::now()) {
+                        Some(remaining) if !remaining.is_zero() => thread::park_timeout(remaining),
+                        _ => group.terminate(),
+                    }
+                }
+                _ => thread::park(),
+            }
+        }
+
+        let terminated = group._flags.is_terminated();
+        let metrics = group.metrics();
+
+        let workers = group
+            .join(false)
+            .into_iter()
+            .zip(metrics)
+            .enumerate()
+            .map(|(index, (result, metrics))| WorkerReport {
+                index,
+                result,
+                metrics,
+            })
+            .collect();
+
+        ShutdownReport {
+            workers,
+            elapsed: start.elapsed(),
+            terminated,
+            signals,
+        }
+    }
+}
+
+/// Counts a worker as exited and unparks the runtime thread, including when the worker panics
+struct WakeOnExit {
+    runtime: Thread,
+    exited: Arc<AtomicUsize>,
+}
+
+impl Drop for WakeOnExit {
+    fn drop(&mut self) {
+        self.exited.fetch_add(1, atomic::Ordering::SeqCst);
+        self.runtime.unpark();
+    }
+}
+
+/// Outcome of a `Runtime::run` call
+#[derive(Debug)]
+pub struct ShutdownReport {
+    /// How each worker ended, in the order they were given
+    pub workers: Vec<WorkerReport>,
+    /// Total time from spawning the workers until the last one finished
+    pub elapsed: Duration,
+    /// Whether termination was signalled, rather than every worker finishing by itself
+    pub terminated: bool,
+    /// Whether the workers were set to terminate on Ctrl-C or SIGTERM
+    ///
+    /// `false` without the `unix` or `windows` feature, or if the handler
+    /// couldn't be installed.
+    pub signals: bool,
+}
+
+impl ShutdownReport {
+    /// Whether every worker finished without panicking
+    pub fn is_clean(&self) -> bool {
+        self.workers.iter().all(|worker| worker.result.is_ok())
+    }
+}
+
+/// How a single worker run by `Runtime::run` ended
+#[derive(Debug)]
+pub struct WorkerReport {
+    /// Position of the worker in the list given to `Runtime::run`
+    pub index: usize,
+    /// How long the worker ran for, or the panic payload if it panicked
+    pub result: Result<Duration, JoinError>,
+    /// Milestones recorded for the worker, such as when it was signalled and when it finished
+    pub metrics: ThreadMetrics,
+}
+
+/// Run workers with `Runtime::run`, boxing each closure
+///
+/// Takes a `RuntimeConfig`, followed by a semicolon and the worker closures,
+/// and returns the `ShutdownReport`.
+#[macro_export]
+macro_rules! run_workers {
+    ($config:expr; $($worker:expr),+ $(,)?) => {
+        $crate::Runtime::run(
+            $config,
+            ::std::vec![$($crate::Runtime::worker($worker)),+],
+        )
+    };
+}
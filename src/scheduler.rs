@@ -0,0 +1,549 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::interval::park_until;
+use crate::{
+    FlagSet, HealthSource, Join, JoinError, MissedTickPolicy, TerminableThreadGroup, Terminate,
+};
+
+/// When a `Scheduler` job runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    /// Every given period, starting one period after the job is added
+    ///
+    /// Runs that overrun later ticks cause those ticks to be skipped.
+    Every(Duration),
+    /// Whenever the cron expression matches, in UTC
+    Cron(CronExpr),
+}
+
+/// A standard five field cron expression: minute, hour, day of month, month and day of week
+///
+/// Each field is `*`, a number, a range `a-b`, any of those followed by a step
+/// `/n`, or a comma separated list of them. A number with a step, `a/n`,
+/// steps from `a` to the end of the field's range. Days of the week run from `0`
+/// (Sunday) to `6`, with `7` also accepted for Sunday. Names (`MON`, `JAN`)
+/// and shorthands (`@daily`) aren't supported.
+///
+/// As in cron, when both the day of month and day of week are restricted, a
+/// day matching either one matches. A field starting with `*`, e.g. `*/2`,
+/// doesn't count as restricted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+/// How far ahead `CronExpr::next_after` searches before giving up
+const SEARCH_DAYS: u64 = 366 * 8;
+
+impl CronExpr {
+    /// The first time strictly after `after` matched by the expression
+    ///
+    /// # Returns
+    ///
+    /// `None` if nothing matches within the next eight years, e.g. for
+    /// `0 0 31 2 *` (the 31st of February)
+    pub fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        let seconds = after.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        let mut minute = seconds / 60 + 1;
+
+        for _ in 0..SEARCH_DAYS {
+            let day = minute / MINUTES_PER_DAY;
+
+            if self.matches_day(day) {
+                for minute_of_day in minute % MINUTES_PER_DAY..MINUTES_PER_DAY {
+                    if has(self.hours, minute_of_day / 60) && has(self.minutes, minute_of_day % 60)
+                    {
+                        let minute = day * MINUTES_PER_DAY + minute_of_day;
+                        return Some(UNIX_EPOCH + Duration::from_secs(minute * 60));
+                    }
+                }
+            }
+
+            minute = (day + 1) * MINUTES_PER_DAY;
+        }
+
+        None
+    }
+
+    fn matches_day(&self, day: u64) -> bool {
+        let (month, day_of_month) = month_and_day(day);
+        // 1970-01-01 was a Thursday
+        let day_of_week = (day + 4) % 7;
+
+        let by_month = has(self.days_of_month, day_of_month);
+        let by_week = has(self.days_of_week, day_of_week);
+
+        let by_day = match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => by_month || by_week,
+            _ => by_month && by_week,
+        };
+
+        has(self.months, month) && by_day
+    }
+}
+
+const MINUTES_PER_DAY: u64 = 24 * 60;
+
+fn has(set: u64, value: u64) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Month (1-12) and day of month (1-31) of a day counted from 1970-01-01
+///
+/// Uses Howard Hinnant's `civil_from_days` algorithm.
+fn month_and_day(day: u64) -> (u64, u64) {
+    let z = day + 719_468;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+
+    (month, day_of_month)
+}
+
+impl FromStr for CronExpr {
+    type Err = ParseCronError;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            return Err(ParseCronError {
+                field: "expression",
+                value: expression.to_owned(),
+            });
+        };
+
+        let mut days_of_week_set = parse_field("day of week", days_of_week, 0, 7)?;
+
+        // Both 0 and 7 mean Sunday
+        if has(days_of_week_set, 7) {
+            days_of_week_set = (days_of_week_set | 1) & !(1 << 7);
+        }
+
+        Ok(CronExpr {
+            minutes: parse_field("minute", minutes, 0, 59)?,
+            hours: parse_field("hour", hours, 0, 23)?,
+            days_of_month: parse_field("day of month", days_of_month, 1, 31)?,
+            months: parse_field("month", months, 1, 12)?,
+            days_of_week: days_of_week_set,
+            any_day_of_month: days_of_month.starts_with('*'),
+            any_day_of_week: days_of_week.starts_with('*'),
+        })
+    }
+}
+
+/// Parse one cron field into a bit set of the values it matches
+fn parse_field(
+    field: &'static str,
+    value: &str,
+    min: u64,
+    max: u64,
+) -> Result<u64, ParseCronError> {
+    let error = || ParseCronError {
+        field,
+        value: value.to_owned(),
+    };
+
+    let mut set = 0;
+
+    for part in value.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step.parse().map_err(|_| error())?)),
+            None => (part, None),
+        };
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (
+                    start.parse().map_err(|_| error())?,
+                    end.parse().map_err(|_| error())?,
+                ),
+                None => {
+                    let single = range.parse().map_err(|_| error())?;
+
+                    // As in cron, `a/n` steps from `a` to the end of the range
+                    match step {
+                        Some(_) => (single, max),
+                        None => (single, single),
+                    }
+                }
+            },
+        };
+
+        let step = step.unwrap_or(1);
+
+        if step == 0 || start < min || end > max || start > end {
+            return Err(error());
+        }
+
+        for value in (start..=end).step_by(step) {
+            set |= 1 << value;
+        }
+    }
+
+    Ok(set)
+}
+
+/// Error returned when a cron expression can't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCronError {
+    field: &'static str,
+    value: String,
+}
+
+impl fmt::Display for ParseCronError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid cron {}: `{}`", self.field, self.value)
+    }
+}
+
+impl Error for ParseCronError {}
+
+/// Runs named jobs on their own schedules, sharing one termination flag
+///
+/// Each job gets its own thread in a `TerminableThreadGroup`, named after the
+/// job, which sleeps until the job is next due. Jobs can be disabled and
+/// re-enabled while the scheduler runs; a disabled job keeps its schedule but
+/// skips its runs.
+#[derive(Debug)]
+pub struct Scheduler {
+    pub(crate) _group: TerminableThreadGroup<()>,
+    pub(crate) _enabled: HashMap<String, Arc<AtomicBool>>,
+}
+
+impl Scheduler {
+    /// Create a scheduler with no jobs
+    pub fn new() -> Self {
+        Self {
+            _group: TerminableThreadGroup::default(),
+            _enabled: HashMap::new(),
+        }
+    }
+
+    /// Add a job running `f` on `schedule`, enabled straight away
+    ///
+    /// # Returns
+    ///
+    /// `false`, without adding the job, if a job with that name already exists
+    ///
+    /// # Panics
+    ///
+    /// If `schedule` is `Schedule::Every` with a zero period
+    pub fn add<F>(&mut self, name: impl Into<String>, schedule: Schedule, mut f: F) -> bool
+    where
+        F: FnMut() + Send + 'static,
+    {
+        assert!(
+            schedule != Schedule::Every(Duration::ZERO),
+            "scheduled job needs a non-zero period"
+        );
+
+        let name = name.into();
+
+        if self._enabled.contains_key(&name) {
+            return false;
+        }
+
+        let enabled = Arc::new(AtomicBool::new(true));
+
+        let job = {
+            let enabled = Arc::clone(&enabled);
+
+            move |flag: Arc<AtomicBool>| {
+                let mut due = Instant::now();
+                let mut last_fired = None;
+
+                loop {
+                    due = match &schedule {
+                        Schedule::Every(period) => {
+                            MissedTickPolicy::Skip.next(due, *period, Instant::now())
+                        }
+                        Schedule::Cron(expression) => {
+                            let now = SystemTime::now();
+
+                            match next_cron_run(expression, now, last_fired) {
+                                Some(next) => {
+                                    last_fired = Some(next);
+                                    Instant::now()
+                                        + next.duration_since(now).unwrap_or(Duration::ZERO)
+                                }
+                                None => break,
+                            }
+                        }
+                    };
+
+                    if park_until(&flag, due) {
+                        break;
+                    }
+
+                    if enabled.load(atomic::Ordering::SeqCst) {
+                        f();
+                    }
+                }
+            }
+        };
+
+        self._group
+            .spawn(thread::Builder::new().name(name.clone()), job);
+        self._enabled.insert(name, enabled);
+
+        true
+    }
+
+    /// Resume running the named job on its schedule
+    ///
+    /// # Returns
+    ///
+    /// `false` if there is no job with that name
+    pub fn enable(&self, name: &str) -> bool {
+        self.set_enabled(name, true)
+    }
+
+    /// Skip the named job's runs until it is enabled again
+    ///
+    /// A run that has already started is not interrupted.
+    ///
+    /// # Returns
+    ///
+    /// `false` if there is no job with that name
+    pub fn disable(&self, name: &str) -> bool {
+        self.set_enabled(name, false)
+    }
+
+    fn set_enabled(&self, name: &str, enabled: bool) -> bool {
+        match self._enabled.get(name) {
+            Some(flag) => {
+                flag.store(enabled, atomic::Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Check whether the named job is enabled, `None` if there is no such job
+    pub fn is_enabled(&self, name: &str) -> Option<bool> {
+        self._enabled
+            .get(name)
+            .map(|flag| flag.load(atomic::Ordering::SeqCst))
+    }
+
+    /// Names of all jobs, in arbitrary order
+    pub fn jobs(&self) -> impl Iterator<Item = &str> {
+        self._enabled.keys().map(String::as_str)
+    }
+
+    /// Stop scheduling further runs of every job
+    ///
+    /// Jobs waiting for their next run stop immediately, while a run that has
+    /// already started is allowed to finish.
+    pub fn terminate(&self) {
+        self._group.terminate();
+    }
+
+    /// Join all job threads, optionally signalling termination
+    ///
+    /// Without termination, this only returns once every job has stopped by
+    /// itself, which only cron jobs with no further matching time do.
+    pub fn join(self, signal_terminate: bool) -> HashMap<String, Result<(), JoinError>> {
+        if signal_terminate {
+            self.terminate();
+        }
+
+        self._group.join_named(false)
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// When a cron job runs next, given when it last fired
+///
+/// Never the minute it last fired again, even if the clock has drifted or
+/// been stepped back since then.
+fn next_cron_run(
+    expression: &CronExpr,
+    now: SystemTime,
+    last_fired: Option<SystemTime>,
+) -> Option<SystemTime> {
+    let after = last_fired.map_or(now, |last_fired| last_fired.max(now));
+
+    expression.next_after(after)
+}
+
+impl Terminate for Scheduler {
+    fn terminate(&self) {
+        Scheduler::terminate(self);
+    }
+}
+
+impl Join for Scheduler {
+    type Output = HashMap<String, Result<(), JoinError>>;
+
+    fn join(self) -> Self::Output {
+        Scheduler::join(self, false)
+    }
+}
+
+impl AsRef<FlagSet> for Scheduler {
+    fn as_ref(&self) -> &FlagSet {
+        self._group.as_ref()
+    }
+}
+
+impl HealthSource for Scheduler {
+    fn healthy(&self) -> bool {
+        self._group.healthy()
+    }
+
+    fn shutting_down(&self) -> bool {
+        self._group.shutting_down()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::*;
+
+    /// `minutes` after 1970-01-01 00:00 UTC, a Thursday
+    fn at(minutes: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(minutes * 60)
+    }
+
+    /// The next `count` matches of `expression` after the epoch, in minutes
+    fn matches(expression: &str, count: usize) -> Vec<u64> {
+        let expression: CronExpr = expression.parse().unwrap();
+        let mut after = at(0);
+
+        (0..count)
+            .map(|_| {
+                after = expression.next_after(after).unwrap();
+                after.duration_since(UNIX_EPOCH).unwrap().as_secs() / 60
+            })
+            .collect()
+    }
+
+    fn days(minutes: Vec<u64>) -> Vec<u64> {
+        minutes
+            .into_iter()
+            .map(|minute| minute / MINUTES_PER_DAY)
+            .collect()
+    }
+
+    #[test]
+    fn next_after_is_strictly_later() {
+        let expression: CronExpr = "5 * * * *".parse().unwrap();
+
+        assert_eq!(expression.next_after(at(5)), Some(at(65)));
+        assert_eq!(
+            expression.next_after(at(5) - Duration::from_secs(1)),
+            Some(at(5))
+        );
+    }
+
+    #[test]
+    fn steps_lists_and_ranges() {
+        assert_eq!(matches("*/15 * * * *", 4), [15, 30, 45, 60]);
+        assert_eq!(matches("5/15 * * * *", 5), [5, 20, 35, 50, 65]);
+        assert_eq!(matches("10-20/5,59 * * * *", 5), [10, 15, 20, 59, 70]);
+        assert_eq!(matches("0 9-17/4 * * *", 4), [540, 780, 1020, 1980]);
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either() {
+        // The 13th, or any Friday: Friday 2nd, Friday 9th, Tuesday 13th, Friday 16th
+        assert_eq!(days(matches("0 0 13 * 5", 4)), [1, 8, 12, 15]);
+    }
+
+    #[test]
+    fn star_based_day_fields_are_unrestricted() {
+        // Odd days of the month that are Mondays: the 5th and 19th of January
+        assert_eq!(days(matches("0 0 */2 * 1", 2)), [4, 18]);
+        // Sundays, as 7, whatever the day of month
+        assert_eq!(days(matches("0 0 */1 * 7", 2)), [3, 10]);
+    }
+
+    #[test]
+    fn months_and_leap_days() {
+        // 1972-02-29, then 1976-02-29
+        assert_eq!(days(matches("0 0 29 2 *", 2)), [789, 2250]);
+        assert_eq!(days(matches("0 0 1 3 *", 1)), [59]);
+    }
+
+    #[test]
+    fn impossible_dates_never_match() {
+        let expression: CronExpr = "0 0 31 2 *".parse().unwrap();
+
+        assert_eq!(expression.next_after(at(0)), None);
+    }
+
+    #[test]
+    fn cron_runs_never_repeat_a_minute() {
+        let expression: CronExpr = "5 * * * *".parse().unwrap();
+
+        assert_eq!(next_cron_run(&expression, at(4), None), Some(at(5)));
+        // Woken a little early by the wall clock, or after it was stepped back
+        assert_eq!(
+            next_cron_run(&expression, at(5) - Duration::from_secs(1), Some(at(5))),
+            Some(at(65))
+        );
+        assert_eq!(next_cron_run(&expression, at(2), Some(at(5))), Some(at(65)));
+        // Stepped forward past later matches
+        assert_eq!(
+            next_cron_run(&expression, at(200), Some(at(5))),
+            Some(at(245))
+        );
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        for expression in [
+            "",
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a * * * *",
+            "1,,2 * * * *",
+        ] {
+            assert!(expression.parse::<CronExpr>().is_err(), "{expression:?}");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "non-zero period")]
+    fn zero_period_is_rejected() {
+        Scheduler::new().add("job", Schedule::Every(Duration::ZERO), || {});
+    }
+}
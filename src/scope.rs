@@ -0,0 +1,157 @@
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, Scope, ScopedJoinHandle, Thread};
+
+use crate::cancel;
+use crate::pool::lock;
+use crate::{HealthSource, Terminate};
+
+/// Run `f` with a `TerminableScope`, in which spawned threads may borrow local data
+///
+/// Built on `std::thread::scope`: every thread spawned in the scope is joined
+/// before this function returns. When `signal_terminate` is set, termination
+/// is signalled as soon as `f` returns, before the remaining threads are joined.
+/// Termination is also signalled if `f` panics, so the implicit join can't hang
+/// on workers waiting for the flag.
+pub fn scope<'env, F, T>(signal_terminate: bool, f: F) -> T
+where
+    F: for<'scope> FnOnce(&TerminableScope<'scope, 'env>) -> T,
+{
+    let flag = Arc::new(AtomicBool::new(false));
+    let threads = Arc::new(Mutex::new(Vec::new()));
+
+    thread::scope(|scope| {
+        let _exit = SignalOnExit {
+            flag: &flag,
+            threads: &threads,
+            signal_terminate,
+        };
+
+        f(&TerminableScope {
+            _scope: scope,
+            _terminate_flag: Arc::clone(&flag),
+            _threads: Arc::clone(&threads),
+        })
+    })
+}
+
+/// A scope for spawning terminable threads that can borrow from the enclosing stack frame
+///
+/// Created by `scope`; all threads spawned through it share one termination flag.
+#[derive(Debug)]
+pub struct TerminableScope<'scope, 'env: 'scope> {
+    pub(crate) _scope: &'scope Scope<'scope, 'env>,
+    pub(crate) _terminate_flag: Arc<AtomicBool>,
+    pub(crate) _threads: Arc<Mutex<Vec<Thread>>>,
+}
+
+impl<'scope, 'env> TerminableScope<'scope, 'env> {
+    /// Spawn a scoped thread running `f`, passing it the shared termination flag
+    pub fn spawn<F, T>(&self, f: F) -> ScopedJoinHandle<'scope, T>
+    where
+        F: FnOnce(Arc<AtomicBool>) -> T + Send + 'scope,
+        T: Send + 'scope,
+    {
+        let flag = Arc::clone(&self._terminate_flag);
+        let handle = self._scope.spawn(move || f(flag));
+
+        lock(&self._threads).push(handle.thread().clone());
+
+        // Termination may have been signalled before the thread was recorded
+        if self._terminate_flag.load(atomic::Ordering::SeqCst) {
+            handle.thread().unpark();
+        }
+
+        handle
+    }
+
+    /// Spawn a scoped thread like `spawn`, signalling the whole scope to terminate if it panics
+    ///
+    /// Useful for threads whose siblings can't make progress without them.
+    pub fn spawn_critical<F, T>(&self, f: F) -> ScopedJoinHandle<'scope, T>
+    where
+        F: FnOnce(Arc<AtomicBool>) -> T + Send + 'scope,
+        T: Send + 'scope,
+    {
+        let threads = Arc::clone(&self._threads);
+
+        self.spawn(move |flag| {
+            let signal = Arc::clone(&flag);
+            let _exit = SignalOnExit {
+                flag: &signal,
+                threads: &threads,
+                signal_terminate: false,
+            };
+
+            f(flag)
+        })
+    }
+
+    /// Signal all threads in the scope to terminate
+    ///
+    /// The threads are also unparked, so those waiting in `std::thread::park`
+    /// (or `park_timeout`) can notice the flag straight away.
+    ///
+    /// ## Note
+    ///
+    /// Threads will only terminate if they check the flag passed to them.
+    pub fn terminate(&self) {
+        raise(&self._terminate_flag, &self._threads);
+    }
+}
+
+impl Terminate for TerminableScope<'_, '_> {
+    fn terminate(&self) {
+        TerminableScope::terminate(self);
+    }
+}
+
+impl HealthSource for TerminableScope<'_, '_> {
+    fn healthy(&self) -> bool {
+        !self.shutting_down()
+    }
+
+    fn shutting_down(&self) -> bool {
+        self._terminate_flag.load(atomic::Ordering::SeqCst)
+    }
+}
+
+/// Raise the scope's flag and unpark every thread spawned in it
+fn raise(flag: &AtomicBool, threads: &Mutex<Vec<Thread>>) {
+    flag.store(true, atomic::Ordering::SeqCst);
+    cancel::raised(flag);
+
+    for thread in lock(threads).iter() {
+        thread.unpark();
+    }
+}
+
+struct SignalOnExit<'a> {
+    flag: &'a AtomicBool,
+    threads: &'a Mutex<Vec<Thread>>,
+    signal_terminate: bool,
+}
+
+impl Drop for SignalOnExit<'_> {
+    fn drop(&mut self) {
+        if self.signal_terminate || thread::panicking() {
+            raise(self.flag, self.threads);
+        }
+    }
+}
+
+/// Run closures on their own threads with a shared termination flag, joining them into a tuple
+///
+/// Each closure receives the flag and may return a different type; the result
+/// is a tuple with the `Result` of joining each thread, in the order given.
+/// Closures may borrow from the enclosing stack frame, as in `scope`.
+///
+/// If any thread panics, the others are signalled to terminate.
+#[macro_export]
+macro_rules! terminable_join {
+    ($($func:expr),+ $(,)?) => {
+        $crate::scope(false, |scope| {
+            $crate::Join::join(($(scope.spawn_critical($func),)+))
+        })
+    };
+}
@@ -0,0 +1,209 @@
+use std::io;
+use std::sync::{Mutex, OnceLock};
+
+use crate::pool::lock;
+use crate::FlagSet;
+
+/// Raised once a signal arrives, linked to every container passed to `terminate_on_signal`
+static SIGNALLED: OnceLock<FlagSet> = OnceLock::new();
+
+/// Whether the handler has been installed, serialising installation
+static INSTALLED: Mutex<bool> = Mutex::new(false);
+
+/// Terminate `container` when the process is asked to stop, e.g. by Ctrl-C
+///
+/// On Linux this handles SIGINT and SIGTERM, on Windows the console's Ctrl-C,
+/// Ctrl-Break and close events.
+///
+/// Takes any container built on a `FlagSet`, pools and supervisors included,
+/// and terminates it just as its own `terminate` would, so parked and
+/// sleeping workers wake straight away.
+///
+/// The handler is installed on the first call and shared by every container
+/// registered afterwards. A container registered after a signal has already
+/// arrived is terminated straight away.
+///
+/// Only the first signal is turned into termination: the default handling is
+/// then restored, so a second Ctrl-C kills a process whose workers don't stop.
+///
+/// ## Note
+///
+/// On Linux, this replaces any handlers for SIGINT and SIGTERM installed
+/// before. On Windows, a console close still ends the process shortly after
+/// the handler returns, so workers only get a brief window to stop.
+///
+/// # Errors
+///
+/// The OS error if the handler couldn't be installed
+pub fn terminate_on_signal<C>(container: &C) -> io::Result<()>
+where
+    C: AsRef<FlagSet> + ?Sized,
+{
+    let mut installed = lock(&INSTALLED);
+
+    if !*installed {
+        os::install()?;
+        *installed = true;
+    }
+
+    drop(installed);
+    signalled().link(container.as_ref());
+
+    Ok(())
+}
+
+fn signalled() -> &'static FlagSet {
+    SIGNALLED.get_or_init(FlagSet::new)
+}
+
+/// SIGINT and SIGTERM, through `signal` and a self-pipe
+#[cfg(all(feature = "unix", target_os = "linux"))]
+mod os {
+    use std::io;
+    use std::os::raw::{c_int, c_void};
+    use std::sync::atomic::{self, AtomicI32};
+    use std::thread;
+
+    const SIGINT: c_int = 2;
+    const SIGTERM: c_int = 15;
+
+    /// `SIG_DFL`, restoring a signal's default disposition
+    const DEFAULT_HANDLER: usize = 0;
+    /// `SIG_ERR`, returned by `signal` on failure
+    const HANDLER_ERROR: usize = usize::MAX;
+
+    extern "C" {
+        fn signal(signum: c_int, handler: usize) -> usize;
+        fn pipe(fds: *mut c_int) -> c_int;
+        fn read(fd: c_int, buf: *mut c_void, count: usize) -> isize;
+        fn write(fd: c_int, buf: *const c_void, count: usize) -> isize;
+        fn __errno_location() -> *mut c_int;
+    }
+
+    /// Write end of the pipe the handler wakes the watcher thread through, `-1` until installed
+    static WAKE_FD: AtomicI32 = AtomicI32::new(-1);
+
+    pub(super) fn install() -> io::Result<()> {
+        let mut fds = [0; 2];
+
+        // SAFETY: `fds` has room for the two descriptors `pipe` writes
+        if unsafe { pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let [read_fd, write_fd] = fds;
+        WAKE_FD.store(write_fd, atomic::Ordering::SeqCst);
+
+        thread::Builder::new()
+            .name(String::from("terminate-on-signal"))
+            .spawn(move || watch(read_fd))?;
+
+        for signum in [SIGINT, SIGTERM] {
+            let handler = on_signal as extern "C" fn(c_int) as usize;
+
+            // SAFETY: `on_signal` only does async-signal-safe work
+            if unsafe { signal(signum, handler) } == HANDLER_ERROR {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Wait for the handler to write to the pipe, then terminate every registered container
+    ///
+    /// Runs on its own thread, as hardly anything may be done inside a
+    /// signal handler itself.
+    fn watch(read_fd: c_int) {
+        let mut byte = 0u8;
+
+        loop {
+            // SAFETY: `byte` is a valid buffer of the one byte read into it
+            let result = unsafe { read(read_fd, (&mut byte as *mut u8).cast(), 1) };
+
+            if result == 1 {
+                break;
+            }
+
+            if result == 0 || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                return;
+            }
+        }
+
+        for signum in [SIGINT, SIGTERM] {
+            // SAFETY: restoring the default disposition has no preconditions
+            unsafe { signal(signum, DEFAULT_HANDLER) };
+        }
+
+        super::signalled().terminate();
+    }
+
+    extern "C" fn on_signal(_signum: c_int) {
+        let byte = 1u8;
+
+        // SAFETY: `__errno_location` is async-signal-safe and always returns
+        // the calling thread's `errno`
+        let errno = unsafe { __errno_location() };
+
+        // SAFETY: `errno` is valid for the calling thread. `write` is
+        // async-signal-safe and `byte` outlives the call. A failed write can
+        // only mean the pipe is full, i.e. a wakeup is pending. The `errno` it
+        // may set is restored, so the interrupted code doesn't see it change.
+        unsafe {
+            let saved = *errno;
+
+            write(
+                WAKE_FD.load(atomic::Ordering::SeqCst),
+                (&byte as *const u8).cast(),
+                1,
+            );
+
+            *errno = saved;
+        }
+    }
+}
+
+/// Console control events, through `SetConsoleCtrlHandler`
+#[cfg(all(feature = "windows", windows))]
+mod os {
+    use std::io;
+
+    type Bool = i32;
+    type Handler = unsafe extern "system" fn(u32) -> Bool;
+
+    const FALSE: Bool = 0;
+    const TRUE: Bool = 1;
+
+    const CTRL_C_EVENT: u32 = 0;
+    const CTRL_BREAK_EVENT: u32 = 1;
+    const CTRL_CLOSE_EVENT: u32 = 2;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetConsoleCtrlHandler(handler: Option<Handler>, add: Bool) -> Bool;
+    }
+
+    pub(super) fn install() -> io::Result<()> {
+        // SAFETY: `on_event` has the signature the console expects of a handler
+        if unsafe { SetConsoleCtrlHandler(Some(on_event), TRUE) } == FALSE {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    /// Runs on a thread the console creates for the event, so may terminate directly
+    unsafe extern "system" fn on_event(event: u32) -> Bool {
+        match event {
+            CTRL_C_EVENT | CTRL_BREAK_EVENT | CTRL_CLOSE_EVENT => {
+                // SAFETY: removing this handler has no preconditions, and
+                // restores the default handling of the next event
+                unsafe { SetConsoleCtrlHandler(Some(on_event), FALSE) };
+
+                super::signalled().terminate();
+                TRUE
+            }
+            _ => FALSE,
+        }
+    }
+}
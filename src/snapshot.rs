@@ -0,0 +1,126 @@
+use std::fmt;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::{FlagSet, ThreadMetrics, ThreadStatus};
+
+/// Point-in-time view of a container and its threads, e.g. for an admin endpoint
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Snapshot {
+    /// Whether the container has been signalled to terminate as a whole
+    pub terminated: bool,
+    /// Every thread, in index order
+    pub threads: Vec<ThreadSnapshot>,
+}
+
+/// Point-in-time view of one thread, as part of a `Snapshot`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ThreadSnapshot {
+    /// Index of the thread within its container
+    pub index: usize,
+    /// Name of the thread, if it has one
+    pub name: Option<String>,
+    /// Lifecycle state of the thread
+    pub status: ThreadStatus,
+    /// Whether the thread's own flag is raised
+    pub flag_raised: bool,
+    /// Time the thread has been running for, or ran for if it has finished
+    pub uptime: Option<Duration>,
+    /// Recorded milestones of the thread
+    pub metrics: ThreadMetrics,
+}
+
+impl Snapshot {
+    /// Take a snapshot of `threads`, whose members are looked up in `flags` by index
+    pub(crate) fn of<T>(threads: &[JoinHandle<T>], flags: &FlagSet) -> Self {
+        Self::of_indexed(threads.iter().enumerate(), flags)
+    }
+
+    /// Take a snapshot of `threads`, each paired with the index of its member in `flags`
+    pub(crate) fn of_indexed<'a, T: 'a, I>(threads: I, flags: &FlagSet) -> Self
+    where
+        I: IntoIterator<Item = (usize, &'a JoinHandle<T>)>,
+    {
+        let threads = threads
+            .into_iter()
+            .filter_map(|(index, thread)| {
+                let metrics = flags.metrics(index)?;
+                let uptime = metrics.spawned.map(|spawned| {
+                    metrics
+                        .finished
+                        .unwrap_or_else(Instant::now)
+                        .saturating_duration_since(spawned)
+                });
+
+                Some(ThreadSnapshot {
+                    index,
+                    name: thread.thread().name().map(String::from),
+                    status: flags.status(index, thread.is_finished())?,
+                    flag_raised: flags.is_member_terminated(index),
+                    uptime,
+                    metrics,
+                })
+            })
+            .collect();
+
+        Snapshot {
+            terminated: flags.is_terminated(),
+            threads,
+        }
+    }
+
+    /// Write the `Debug` representation of the container named `name` it was taken of
+    pub(crate) fn debug_container(&self, f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+        f.debug_struct(name)
+            .field("len", &self.threads.len())
+            .field("terminated", &self.terminated)
+            .field("threads", &Summaries(&self.threads))
+            .finish()
+    }
+}
+
+/// Summarises the container as its thread count, whether it was terminated, and each thread's status
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let len = self.threads.len();
+        write!(f, "{len} thread{}", if len == 1 { "" } else { "s" })?;
+
+        if self.terminated {
+            write!(f, ", terminated")?;
+        }
+
+        for (position, thread) in self.threads.iter().enumerate() {
+            write!(f, "{}{thread}", if position == 0 { ": " } else { ", " })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Summarises the thread as its index, name and status, e.g. `#0 "reader" running`
+impl fmt::Display for ThreadSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.index)?;
+
+        if let Some(name) = &self.name {
+            write!(f, " {name:?}")?;
+        }
+
+        write!(f, " {}", self.status)
+    }
+}
+
+/// Lists thread summaries in `Debug` output without the full snapshot fields
+struct Summaries<'a>(&'a [ThreadSnapshot]);
+
+impl fmt::Debug for Summaries<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut list = f.debug_list();
+
+        for thread in self.0 {
+            list.entry(&format_args!("{thread}"));
+        }
+
+        list.finish()
+    }
+}
@@ -0,0 +1,342 @@
+use std::fmt;
+use std::sync::atomic::{self, AtomicBool};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::{token, FlagSet};
+
+/// Lifecycle state of a managed thread
+///
+/// A thread moves from `Spawned` through `Running` and `Terminating` to
+/// `Finished`, skipping the states it never passes through, e.g. a thread
+/// returning on its own goes straight from `Running` to `Finished`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ThreadStatus {
+    /// The thread exists but hasn't started its function yet, e.g. while
+    /// waiting out a start delay or for its dependencies to be ready
+    Spawned,
+    /// The thread is running and hasn't been signalled to terminate
+    Running,
+    /// The thread has been signalled to terminate but hasn't finished yet
+    Terminating,
+    /// The thread has finished, with the given outcome
+    Finished(ThreadOutcome),
+    /// The thread was signalled to terminate for exceeding its maximum runtime
+    /// or CPU time, and hasn't finished yet
+    ///
+    /// Takes the place of `Terminating`. Once the thread finishes it is
+    /// reported as `Finished` like any other, and whether it timed out stays
+    /// available from `FlagSet::is_member_timed_out`.
+    TimedOut,
+}
+
+impl ThreadStatus {
+    pub(crate) fn of(finished: bool, flag: &AtomicBool) -> Self {
+        if finished {
+            ThreadStatus::Finished(ThreadOutcome::Unknown)
+        } else if flag.load(atomic::Ordering::SeqCst) {
+            ThreadStatus::Terminating
+        } else {
+            ThreadStatus::Running
+        }
+    }
+
+    /// Check whether the thread has finished, whether or not it timed out first
+    pub fn is_finished(&self) -> bool {
+        matches!(self, ThreadStatus::Finished(_))
+    }
+
+    /// Report `TimedOut` instead if `timed_out` is set and the thread hasn't finished
+    pub(crate) fn or_timed_out(self, timed_out: bool) -> Self {
+        if timed_out && !self.is_finished() {
+            ThreadStatus::TimedOut
+        } else {
+            self
+        }
+    }
+}
+
+impl fmt::Display for ThreadStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThreadStatus::Spawned => write!(f, "spawned"),
+            ThreadStatus::Running => write!(f, "running"),
+            ThreadStatus::Terminating => write!(f, "terminating"),
+            ThreadStatus::Finished(ThreadOutcome::Panicked) => write!(f, "panicked"),
+            ThreadStatus::Finished(_) => write!(f, "finished"),
+            ThreadStatus::TimedOut => write!(f, "timed out"),
+        }
+    }
+}
+
+/// How a finished thread ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ThreadOutcome {
+    /// The thread's function returned
+    Returned,
+    /// The thread panicked
+    Panicked,
+    /// The thread wasn't spawned by the crate, so how it ended is only known once it is joined
+    Unknown,
+}
+
+/// A snapshot of a group's threads, returned by `stats` on the thread containers
+///
+/// The counterpart of `PoolStats` for containers running one function per
+/// thread rather than a queue of jobs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupStats {
+    /// Threads that haven't finished yet, including ones still starting up or terminating
+    pub running: usize,
+    /// Threads that have finished, however they ended
+    pub finished: usize,
+    /// Finished threads that panicked
+    ///
+    /// Threads passed in already running are only known to have panicked
+    /// once joined, so aren't counted.
+    pub panicked: usize,
+    /// Time since termination was first signalled, if it has been
+    pub since_terminate: Option<Duration>,
+}
+
+/// A thread that hadn't finished by the time a watchdog checked on it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UnfinishedThread {
+    /// Index of the thread within its container
+    pub index: usize,
+    /// Name of the thread, if it has one
+    pub name: Option<String>,
+}
+
+/// How often threads the crate didn't spawn are re-checked, as nothing signals their finishing
+const FINISH_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Whether the thread at `index`, whose member is looked up in `flags`, has finished
+fn has_finished<T>(index: usize, thread: &JoinHandle<T>, flags: &FlagSet) -> bool {
+    flags.is_member_finished(index) || thread.is_finished()
+}
+
+/// Have the current thread unparked as each unfinished thread finishes
+///
+/// # Returns
+///
+/// `false` if some of them aren't tracked, so have to be polled for
+fn watch<T>(threads: &[JoinHandle<T>], flags: &FlagSet) -> bool {
+    threads
+        .iter()
+        .enumerate()
+        .filter(|(index, thread)| !has_finished(*index, thread, flags))
+        .all(|(index, _)| flags.unpark_on_finish(index))
+}
+
+/// Block until every thread has finished, or `timeout` passes
+///
+/// Threads spawned by the crate wake the caller as they finish, others are
+/// polled for.
+///
+/// # Returns
+///
+/// `true` if every thread finished in time
+pub(crate) fn wait_finished<T>(
+    threads: &[JoinHandle<T>],
+    flags: &FlagSet,
+    timeout: Duration,
+) -> bool {
+    let deadline = Instant::now() + timeout;
+    let tracked = watch(threads, flags);
+
+    loop {
+        let finished = threads
+            .iter()
+            .enumerate()
+            .all(|(index, thread)| has_finished(index, thread, flags));
+
+        if finished {
+            return true;
+        }
+
+        match deadline.checked_duration_since(Instant::now()) {
+            Some(remaining) if !remaining.is_zero() => wait(tracked, remaining),
+            _ => return false,
+        }
+    }
+}
+
+/// Block until every thread has finished, giving them `grace` to do so
+///
+/// Threads inside a `Token::uninterruptible` section once `grace` has passed
+/// are waited for until they leave it, which is polled for.
+///
+/// # Returns
+///
+/// `true` if every thread finished, `false` if some were still running
+/// outside a critical section after `grace`
+pub(crate) fn wait_shutdown<T>(
+    threads: &[JoinHandle<T>],
+    flags: &FlagSet,
+    grace: Duration,
+) -> bool {
+    let deadline = Instant::now() + grace;
+    let tracked = watch(threads, flags);
+
+    loop {
+        let running: Vec<_> = threads
+            .iter()
+            .enumerate()
+            .filter(|(index, thread)| !has_finished(*index, thread, flags))
+            .map(|(_, thread)| thread)
+            .collect();
+
+        if running.is_empty() {
+            return true;
+        }
+
+        match deadline.checked_duration_since(Instant::now()) {
+            Some(remaining) if !remaining.is_zero() => wait(tracked, remaining),
+            _ if running
+                .iter()
+                .any(|thread| token::is_uninterruptible(thread.thread().id())) =>
+            {
+                thread::sleep(FINISH_POLL_INTERVAL)
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// Wait up to `remaining` for a watched thread to finish, or a poll interval if some aren't `tracked`
+fn wait(tracked: bool, remaining: Duration) {
+    if tracked {
+        thread::park_timeout(remaining);
+    } else {
+        thread::sleep(remaining.min(FINISH_POLL_INTERVAL));
+    }
+}
+
+/// Indices of the unfinished threads whose member heartbeat in `flags` is older than `max_age`
+pub(crate) fn stale_threads<T>(
+    threads: &[JoinHandle<T>],
+    flags: &FlagSet,
+    max_age: Duration,
+) -> Vec<usize> {
+    flags
+        .stale_members(max_age)
+        .into_iter()
+        .filter(|&index| {
+            threads
+                .get(index)
+                .is_some_and(|thread| !thread.is_finished())
+        })
+        .collect()
+}
+
+/// Wait up to `patience` for every thread to finish, passing the ones that didn't to `report`
+///
+/// `report` is only called if some threads are still running after `patience`.
+pub(crate) fn report_unfinished<T, R>(
+    threads: &[JoinHandle<T>],
+    flags: &FlagSet,
+    patience: Duration,
+    report: R,
+) where
+    R: FnOnce(Vec<UnfinishedThread>),
+{
+    if wait_finished(threads, flags, patience) {
+        return;
+    }
+
+    let unfinished: Vec<_> = threads
+        .iter()
+        .enumerate()
+        .filter(|(index, thread)| !has_finished(*index, thread, flags))
+        .map(|(index, thread)| UnfinishedThread {
+            index,
+            name: thread.thread().name().map(String::from),
+        })
+        .collect();
+
+    if !unfinished.is_empty() {
+        report(unfinished);
+    }
+}
+
+/// Counts of running, finished and panicked threads, looked up in `flags` by index
+pub(crate) fn stats<T>(threads: &[JoinHandle<T>], flags: &FlagSet) -> GroupStats {
+    let statuses = snapshot(threads, flags);
+    let count = |matches: fn(&ThreadStatus) -> bool| statuses.iter().filter(|s| matches(s)).count();
+
+    GroupStats {
+        running: count(|status| !status.is_finished()),
+        finished: count(ThreadStatus::is_finished),
+        panicked: count(|status| *status == ThreadStatus::Finished(ThreadOutcome::Panicked)),
+        since_terminate: flags.terminated_at().map(|at| at.elapsed()),
+    }
+}
+
+/// Status of every thread, looked up in `flags` by index
+pub(crate) fn snapshot<T>(threads: &[JoinHandle<T>], flags: &FlagSet) -> Vec<ThreadStatus> {
+    threads
+        .iter()
+        .enumerate()
+        .filter_map(|(index, thread)| flags.status(index, thread.is_finished()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use crate::{TerminableThreadGroup, Token};
+
+    /// Far longer than any thread here should take to finish
+    const PATIENCE: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn group_stats_count_running_finished_and_panicked_threads() {
+        let mut group = TerminableThreadGroup::default();
+        let (done, finished) = mpsc::channel();
+
+        for outcome in ["return", "panic", "wait"] {
+            let done = done.clone();
+
+            group.push(move |flag| {
+                let _done = SendOnDrop(done);
+
+                match outcome {
+                    "panic" => panic!("expected in this test"),
+                    "wait" => {
+                        flag.sleep(PATIENCE * 6);
+                    }
+                    _ => {}
+                }
+            });
+        }
+
+        for _ in 0..2 {
+            finished.recv_timeout(PATIENCE).unwrap();
+        }
+
+        // The panicking thread's handle may take a moment to report it finished
+        while group.stats().finished < 2 {
+            std::thread::yield_now();
+        }
+
+        let stats = group.stats();
+        assert_eq!((stats.running, stats.finished, stats.panicked), (1, 2, 1));
+        assert_eq!(stats.since_terminate, None);
+
+        group.terminate();
+        assert!(group.stats().since_terminate.is_some());
+        group.join(false);
+    }
+
+    /// Sends on the channel once dropped, whether its thread returned or panicked
+    struct SendOnDrop(mpsc::Sender<()>);
+
+    impl Drop for SendOnDrop {
+        fn drop(&mut self) {
+            let _ = self.0.send(());
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::AsFd;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::{spawn_terminable, CancelPipe, TerminableThreadHandle, Token};
+
+/// Size of each read from stdin
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Spawn a thread reading lines from stdin and sending them down the returned channel
+///
+/// Lines are sent without their trailing newline, with invalid UTF-8
+/// replaced. A final line without a newline is sent once stdin is closed.
+///
+/// Unlike a thread blocked in `read_line`, terminating the handle stops the
+/// thread straight away: it waits on stdin alongside a `CancelPipe`, and only
+/// reads once input is available. The thread also stops once stdin is closed
+/// or the receiver is dropped.
+///
+/// Only available on Linux, with the `unix` feature enabled.
+///
+/// ## Note
+///
+/// The thread reads stdin directly rather than through `std::io::stdin()`,
+/// so nothing else in the process should read stdin while it runs.
+///
+/// # Errors
+///
+/// Joining the thread gives the OS error if stdin couldn't be waited on or
+/// read
+pub fn spawn_stdin_reader() -> (TerminableThreadHandle<io::Result<()>>, Receiver<String>) {
+    let (sender, receiver) = mpsc::channel();
+
+    let handle = spawn_terminable(move |flag| {
+        let pipe = CancelPipe::new()?;
+        let _registration = flag.cancel_io_on_terminate(pipe.clone());
+
+        // A duplicate of the descriptor, so reads bypass the buffer of `Stdin`
+        let mut stdin = File::from(io::stdin().as_fd().try_clone_to_owned()?);
+        let mut buffer = [0; CHUNK_SIZE];
+        let mut line = Vec::new();
+
+        loop {
+            if flag.should_stop() || !pipe.wait_readable(&stdin)? {
+                return Ok(());
+            }
+
+            let read = match stdin.read(&mut buffer) {
+                Ok(0) => {
+                    if !line.is_empty() {
+                        // The receiver hanging up is no reason to report an error
+                        let _ = send_line(&sender, &line);
+                    }
+
+                    return Ok(());
+                }
+                Ok(read) => read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            };
+
+            for &byte in &buffer[..read] {
+                if byte != b'\n' {
+                    line.push(byte);
+                    continue;
+                }
+
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+
+                if send_line(&sender, &line).is_err() {
+                    return Ok(());
+                }
+
+                line.clear();
+            }
+        }
+    });
+
+    (handle, receiver)
+}
+
+/// Send `line`, failing if the receiver hung up
+fn send_line(sender: &Sender<String>, line: &[u8]) -> Result<(), mpsc::SendError<String>> {
+    sender.send(String::from_utf8_lossy(line).into_owned())
+}
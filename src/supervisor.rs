@@ -0,0 +1,352 @@
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle, Thread};
+use std::time::{Duration, Instant};
+
+use crate::pool::lock;
+use crate::{FlagSet, HealthSource, Join, JoinError, Terminate};
+
+/// Exponential backoff applied between consecutive restarts of a supervised worker
+///
+/// The n-th restart inside the current window waits `initial * multiplier^(n - 1)`,
+/// scaled by a random factor in `1 ± jitter`, and capped at `max`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub multiplier: f64,
+    pub jitter: f64,
+}
+
+impl Backoff {
+    /// Compute the delay before the restart with the given (1-based) attempt number
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let base = self.initial.as_secs_f64() * self.multiplier.powi(exponent);
+        let capped = base.min(self.max.as_secs_f64());
+
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = 1.0 + jitter * (2.0 * random_unit() - 1.0);
+
+        // Out of range or not a number only if the result would exceed `max` anyway
+        Duration::try_from_secs_f64((capped * factor).max(0.0))
+            .unwrap_or(self.max)
+            .min(self.max)
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.1,
+        }
+    }
+}
+
+/// Limits on how often a supervised worker may be restarted
+///
+/// At most `max_restarts` restarts are allowed within any `window`; once that is
+/// exceeded the supervisor gives up and reports the last failure.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RestartPolicy {
+    pub max_restarts: usize,
+    pub window: Duration,
+    pub backoff: Backoff,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            window: Duration::from_secs(60),
+            backoff: Backoff::default(),
+        }
+    }
+}
+
+/// A thread that watches a single worker and restarts it whenever it panics
+///
+/// The worker and the supervisor share one termination flag, so terminating the
+/// supervisor also stops the worker and prevents any further restarts.
+#[derive(Debug)]
+pub struct Supervisor<T> {
+    pub(crate) _thread: JoinHandle<Result<T, JoinError>>,
+    pub(crate) _flags: FlagSet,
+    pub(crate) _restarts: Arc<AtomicUsize>,
+    pub(crate) _worker: Arc<Mutex<Option<Thread>>>,
+}
+
+impl<T> Supervisor<T> {
+    /// Signal the worker to terminate and stop restarting it
+    ///
+    /// ## Note
+    ///
+    /// A pending backoff sleep is interrupted immediately, but the worker itself
+    /// only stops if it checks the flag passed to it. The worker is unparked
+    /// too, so one waiting in `std::thread::park` notices the flag straight away.
+    pub fn terminate(&self) {
+        self._flags.terminate();
+    }
+
+    /// Number of times the worker has been restarted so far
+    pub fn restarts(&self) -> usize {
+        self._restarts.load(atomic::Ordering::SeqCst)
+    }
+
+    /// Join the supervisor, optionally signalling termination
+    ///
+    /// # Returns
+    ///
+    /// The result of the last worker run, which is an error if the worker kept
+    /// panicking until the restart policy was exhausted
+    pub fn join(self, signal_terminate: bool) -> Result<T, JoinError> {
+        if signal_terminate {
+            self.terminate();
+        }
+
+        self._thread.join().and_then(|result| result)
+    }
+}
+
+impl<T> Terminate for Supervisor<T> {
+    fn terminate(&self) {
+        Supervisor::terminate(self);
+    }
+}
+
+impl<T> Join for Supervisor<T> {
+    type Output = Result<T, JoinError>;
+
+    fn join(self) -> Self::Output {
+        Supervisor::join(self, false)
+    }
+}
+
+impl<T> HealthSource for Supervisor<T> {
+    fn healthy(&self) -> bool {
+        !self.shutting_down() && !self._thread.is_finished()
+    }
+
+    fn shutting_down(&self) -> bool {
+        self._flags.is_terminated()
+    }
+}
+
+impl<T> AsRef<FlagSet> for Supervisor<T> {
+    /// A set without members, whose termination terminates the supervisor
+    fn as_ref(&self) -> &FlagSet {
+        &self._flags
+    }
+}
+
+/// Builder for a `Supervisor`, used to configure its `RestartPolicy`
+#[derive(Debug, Clone, Default)]
+pub struct SupervisorBuilder {
+    policy: RestartPolicy,
+}
+
+impl SupervisorBuilder {
+    /// Create a new `SupervisorBuilder` with the default restart policy
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the policy deciding how often and how quickly the worker is restarted
+    pub fn restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Start supervising a worker running `func`
+    pub fn spawn<T, F>(self, func: F) -> Supervisor<T>
+    where
+        T: Send + 'static,
+        F: Fn(Arc<AtomicBool>) -> T + Clone + Send + 'static,
+    {
+        let flags = FlagSet::new();
+        let flag = Arc::clone(flags.group_flag());
+        let restarts = Arc::new(AtomicUsize::new(0));
+        let worker = Arc::new(Mutex::new(None));
+
+        let thread = {
+            let flag = Arc::clone(&flag);
+            let restarts = Arc::clone(&restarts);
+            let worker = Arc::clone(&worker);
+            let policy = self.policy;
+
+            thread::spawn(move || supervise(policy, func, flag, restarts, worker))
+        };
+
+        flags.on_terminate({
+            let supervisor = thread.thread().clone();
+            let worker = Arc::clone(&worker);
+
+            move || {
+                supervisor.unpark();
+
+                if let Some(worker) = &*lock(&worker) {
+                    worker.unpark();
+                }
+            }
+        });
+
+        Supervisor {
+            _thread: thread,
+            _flags: flags,
+            _restarts: restarts,
+            _worker: worker,
+        }
+    }
+}
+
+fn supervise<T, F>(
+    policy: RestartPolicy,
+    func: F,
+    flag: Arc<AtomicBool>,
+    restarts: Arc<AtomicUsize>,
+    current: Arc<Mutex<Option<Thread>>>,
+) -> Result<T, JoinError>
+where
+    T: Send + 'static,
+    F: Fn(Arc<AtomicBool>) -> T + Clone + Send + 'static,
+{
+    let mut recent = VecDeque::new();
+
+    loop {
+        let worker = {
+            let func = func.clone();
+            let flag = Arc::clone(&flag);
+
+            thread::spawn(move || func(flag))
+        };
+
+        *lock(&current) = Some(worker.thread().clone());
+
+        // Termination may have been signalled before the new worker was
+        // recorded, in which case it was never unparked
+        if flag.load(atomic::Ordering::SeqCst) {
+            worker.thread().unpark();
+        }
+
+        let result = worker.join();
+
+        if result.is_ok() || flag.load(atomic::Ordering::SeqCst) {
+            return result;
+        }
+
+        let now = Instant::now();
+
+        while recent
+            .front()
+            .is_some_and(|&restart| now.duration_since(restart) > policy.window)
+        {
+            recent.pop_front();
+        }
+
+        if recent.len() >= policy.max_restarts {
+            return result;
+        }
+
+        recent.push_back(now);
+
+        // A delay too long to represent is waited out until terminated
+        let deadline = now.checked_add(policy.backoff.delay(recent.len() as u32));
+
+        while !flag.load(atomic::Ordering::SeqCst) {
+            let Some(deadline) = deadline else {
+                thread::park();
+                continue;
+            };
+
+            match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) if !remaining.is_zero() => thread::park_timeout(remaining),
+                _ => break,
+            }
+        }
+
+        if flag.load(atomic::Ordering::SeqCst) {
+            return result;
+        }
+
+        restarts.fetch_add(1, atomic::Ordering::SeqCst);
+    }
+}
+
+/// A pseudo-random number in `[0, 1)`, good enough to spread restarts and ticks apart
+pub(crate) fn random_unit() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn without_jitter(initial: Duration, max: Duration) -> Backoff {
+        Backoff {
+            initial,
+            max,
+            multiplier: 2.0,
+            jitter: 0.0,
+        }
+    }
+
+    #[test]
+    fn backoff_grows_until_capped() {
+        let backoff = without_jitter(Duration::from_millis(100), Duration::from_secs(1));
+        let delays: Vec<_> = (1..=6).map(|attempt| backoff.delay(attempt)).collect();
+
+        assert_eq!(
+            delays,
+            [100, 200, 400, 800, 1000, 1000].map(Duration::from_millis)
+        );
+    }
+
+    #[test]
+    fn backoff_treats_attempt_zero_as_first() {
+        let backoff = without_jitter(Duration::from_millis(100), Duration::from_secs(1));
+
+        assert_eq!(backoff.delay(0), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn backoff_jitter_stays_within_bounds() {
+        let backoff = Backoff {
+            jitter: 0.5,
+            ..without_jitter(Duration::from_secs(1), Duration::from_secs(10))
+        };
+
+        for _ in 0..100 {
+            let delay = backoff.delay(1);
+            assert!(delay >= Duration::from_millis(500), "{delay:?}");
+            assert!(delay <= Duration::from_millis(1500), "{delay:?}");
+        }
+    }
+
+    #[test]
+    fn backoff_saturates_at_an_unbounded_max() {
+        let backoff = without_jitter(Duration::from_secs(1), Duration::MAX);
+
+        assert_eq!(backoff.delay(u32::MAX), Duration::MAX);
+    }
+
+    #[test]
+    fn backoff_caps_a_nan_multiplier() {
+        let backoff = Backoff {
+            multiplier: f64::NAN,
+            jitter: 1.0,
+            ..without_jitter(Duration::from_secs(1), Duration::from_secs(5))
+        };
+
+        assert!(backoff.delay(3) <= Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,318 @@
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+
+use crate::cancel::{self, CancelIo};
+use crate::pool::lock;
+
+/// How a wait that can be cut short by termination ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WaitOutcome {
+    /// The awaited condition was met
+    Completed,
+    /// Termination was signalled first
+    Terminated,
+}
+
+/// Counts outstanding sub-tasks and lets a worker wait for all of them
+///
+/// Cheap to clone; all clones refer to the same counter. Sub-tasks are
+/// registered with `add` and each calls `done` when finished. Unlike a plain
+/// latch, `wait` also gives up once the worker's termination flag is raised.
+#[derive(Debug, Clone, Default)]
+pub struct WaitGroup {
+    inner: Arc<Counter>,
+}
+
+#[derive(Debug, Default)]
+struct Counter {
+    pending: Mutex<usize>,
+    zero: TerminableCondvar,
+}
+
+impl WaitGroup {
+    /// Create a wait group with no outstanding sub-tasks
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `n` more outstanding sub-tasks
+    pub fn add(&self, n: usize) {
+        *lock(&self.inner.pending) += n;
+    }
+
+    /// Mark one sub-task as finished
+    ///
+    /// # Panics
+    ///
+    /// If there are no outstanding sub-tasks
+    pub fn done(&self) {
+        let mut pending = lock(&self.inner.pending);
+
+        *pending = pending
+            .checked_sub(1)
+            .expect("`done` called more often than `add`");
+
+        if *pending == 0 {
+            self.inner.zero.notify_all();
+        }
+    }
+
+    /// Number of outstanding sub-tasks
+    pub fn pending(&self) -> usize {
+        *lock(&self.inner.pending)
+    }
+
+    /// Block until every sub-task is done, or until `flag` is raised
+    ///
+    /// Wakes as soon as the last sub-task is done, or as soon as the flag is
+    /// raised through a `FlagSet`, see `TerminableCondvar`.
+    pub fn wait(&self, flag: &AtomicBool) -> WaitOutcome {
+        self.inner
+            .zero
+            .wait_while(&self.inner.pending, flag, |pending| *pending > 0)
+            .1
+    }
+}
+
+/// A `Condvar` whose waits also end once a termination flag is raised
+///
+/// Workers blocked on a plain `Condvar` sleep through termination until
+/// someone happens to notify them. Waiting through this wrapper instead wakes
+/// them as soon as the flag is raised through a `FlagSet`, e.g. by
+/// terminating the worker's container.
+///
+/// ## Note
+///
+/// A flag stored to directly, rather than raised through a `FlagSet`, is
+/// only noticed at the next notification.
+#[derive(Debug, Default)]
+pub struct TerminableCondvar {
+    inner: Arc<Signal>,
+}
+
+/// Counts notifications, so a waiter can tell whether any arrived while it released its lock
+#[derive(Debug, Default)]
+struct Signal {
+    generation: Mutex<u64>,
+    changed: Condvar,
+}
+
+impl Signal {
+    fn notify(&self, all: bool) {
+        let mut generation = lock(&self.generation);
+        *generation = generation.wrapping_add(1);
+
+        if all {
+            self.changed.notify_all();
+        } else {
+            self.changed.notify_one();
+        }
+    }
+}
+
+/// Wakes every waiter once the flag it was registered with is raised
+impl CancelIo for Signal {
+    fn cancel_io(&self) {
+        self.notify(true);
+    }
+}
+
+impl TerminableCondvar {
+    /// Create a condition variable
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wake up one thread blocked on this condition variable
+    pub fn notify_one(&self) {
+        self.inner.notify(false);
+    }
+
+    /// Wake up all threads blocked on this condition variable
+    pub fn notify_all(&self) {
+        self.inner.notify(true);
+    }
+
+    /// Lock `mutex` and block while `condition` holds, or until `flag` is raised
+    ///
+    /// Like `Condvar::wait_while`, `condition` is checked with the lock held,
+    /// both before waiting and after every wakeup. A poisoned mutex is used
+    /// regardless, as elsewhere in this crate.
+    ///
+    /// # Returns
+    ///
+    /// The guard of `mutex`, along with `Completed` if `condition` stopped
+    /// holding or `Terminated` if `flag` was raised first
+    pub fn wait_while<'a, T, F>(
+        &self,
+        mutex: &'a Mutex<T>,
+        flag: &AtomicBool,
+        condition: F,
+    ) -> (MutexGuard<'a, T>, WaitOutcome)
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        self.wait_while_any(mutex, &[flag], condition)
+    }
+
+    /// Like `wait_while`, but ending once any of `flags` is raised
+    pub(crate) fn wait_while_any<'a, T, F>(
+        &self,
+        mutex: &'a Mutex<T>,
+        flags: &[&AtomicBool],
+        mut condition: F,
+    ) -> (MutexGuard<'a, T>, WaitOutcome)
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        let mut guard = lock(mutex);
+        let mut registrations = Vec::new();
+
+        loop {
+            if !condition(&mut guard) {
+                return (guard, WaitOutcome::Completed);
+            }
+
+            // Only registered once there is something to wait for, as it takes a global lock
+            if registrations.is_empty() {
+                registrations = flags
+                    .iter()
+                    .map(|flag| cancel::register(flag, Box::new(Arc::clone(&self.inner))))
+                    .collect();
+            }
+
+            // Taken before releasing `mutex`, so every notification and raise
+            // from here on moves the generation on
+            let generation = lock(&self.inner.generation);
+
+            if flags.iter().any(|flag| flag.load(atomic::Ordering::SeqCst)) {
+                return (guard, WaitOutcome::Terminated);
+            }
+
+            let seen = *generation;
+            drop(guard);
+
+            drop(
+                self.inner
+                    .changed
+                    .wait_while(generation, |generation| *generation == seen)
+                    .unwrap_or_else(PoisonError::into_inner),
+            );
+
+            guard = lock(mutex);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::FlagSet;
+
+    /// Far longer than any wakeup should take, so a missed one fails the test
+    const PATIENCE: Duration = Duration::from_secs(10);
+
+    /// Wait on `condvar` for `ready` from another thread, reporting how the wait ended
+    fn waiter(
+        condvar: &Arc<TerminableCondvar>,
+        ready: &Arc<Mutex<bool>>,
+        flag: &Arc<AtomicBool>,
+    ) -> mpsc::Receiver<WaitOutcome> {
+        let (condvar, ready, flag) = (Arc::clone(condvar), Arc::clone(ready), Arc::clone(flag));
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let outcome = condvar.wait_while(&ready, &flag, |ready| !*ready).1;
+            sender.send(outcome).unwrap();
+        });
+
+        receiver
+    }
+
+    #[test]
+    fn notify_completes_a_wait() {
+        let flags = FlagSet::with_len(1);
+        let condvar = Arc::new(TerminableCondvar::new());
+        let ready = Arc::new(Mutex::new(false));
+        let outcome = waiter(&condvar, &ready, flags.flag(0).unwrap());
+
+        *lock(&ready) = true;
+        condvar.notify_all();
+
+        assert_eq!(outcome.recv_timeout(PATIENCE), Ok(WaitOutcome::Completed));
+    }
+
+    #[test]
+    fn terminate_wakes_a_wait() {
+        let flags = FlagSet::with_len(1);
+        let condvar = Arc::new(TerminableCondvar::new());
+        let ready = Arc::new(Mutex::new(false));
+        let outcome = waiter(&condvar, &ready, flags.flag(0).unwrap());
+
+        thread::sleep(Duration::from_millis(50));
+        flags.terminate();
+
+        assert_eq!(outcome.recv_timeout(PATIENCE), Ok(WaitOutcome::Terminated));
+    }
+
+    #[test]
+    fn wait_group_waits_for_every_sub_task() {
+        let flags = FlagSet::with_len(1);
+        let group = WaitGroup::new();
+        group.add(3);
+
+        for _ in 0..3 {
+            let group = group.clone();
+            thread::spawn(move || group.done());
+        }
+
+        assert_eq!(group.wait(flags.flag(0).unwrap()), WaitOutcome::Completed);
+        assert_eq!(group.pending(), 0);
+    }
+
+    #[test]
+    fn terminate_wakes_a_wait_group() {
+        let flags = FlagSet::with_len(1);
+        let group = WaitGroup::new();
+        group.add(1);
+
+        let (sender, outcome) = mpsc::channel();
+        let flag = Arc::clone(flags.flag(0).unwrap());
+        let waiter = group.clone();
+        thread::spawn(move || sender.send(waiter.wait(&flag)).unwrap());
+
+        thread::sleep(Duration::from_millis(50));
+        flags.terminate();
+
+        assert_eq!(outcome.recv_timeout(PATIENCE), Ok(WaitOutcome::Terminated));
+        assert_eq!(group.pending(), 1);
+    }
+
+    #[test]
+    fn raised_flag_ends_a_wait_straight_away() {
+        let flag = AtomicBool::new(true);
+        let ready = Mutex::new(false);
+
+        let outcome = TerminableCondvar::new()
+            .wait_while(&ready, &flag, |ready| !*ready)
+            .1;
+
+        assert_eq!(outcome, WaitOutcome::Terminated);
+    }
+
+    #[test]
+    fn met_condition_wins_over_a_raised_flag() {
+        let flag = AtomicBool::new(true);
+        let ready = Mutex::new(true);
+
+        let outcome = TerminableCondvar::new()
+            .wait_while(&ready, &flag, |ready| !*ready)
+            .1;
+
+        assert_eq!(outcome, WaitOutcome::Completed);
+    }
+}
@@ -0,0 +1,229 @@
+use std::ops::ControlFlow;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+use crate::cancel;
+use crate::interval::park_until;
+use crate::metrics;
+use crate::pool::lock;
+use crate::{CancelIo, IoRegistration};
+
+/// Helpers for workers polling their termination flag
+///
+/// Implemented for `AtomicBool`, so the methods can be called directly on the
+/// `Arc<AtomicBool>` passed to each managed thread.
+pub trait Token {
+    /// Check whether termination has been signalled
+    ///
+    /// Meant to be called regularly from the worker's hot loop, see also
+    /// `checkpoint!` for returning early. The first time it reports
+    /// termination is recorded in the thread's `ThreadMetrics`.
+    fn should_stop(&self) -> bool;
+
+    /// Call `step` repeatedly until it returns `Break` or termination is signalled
+    ///
+    /// The flag is checked before each call, so `step` is never called once
+    /// termination has been signalled.
+    fn run_until_terminated<T, F>(&self, step: F) -> StopReason<T>
+    where
+        F: FnMut() -> ControlFlow<T>;
+
+    /// Run `f` on each item until the iterator is exhausted or termination is signalled
+    ///
+    /// Items whose processing took longer than `budget` are recorded in the
+    /// returned report, so callers don't have to time each item themselves.
+    ///
+    /// ## Note
+    ///
+    /// The flag is checked between items, so an item that is already being
+    /// processed always runs to completion.
+    fn for_each_timed<I, F>(&self, iter: I, budget: Duration, f: F) -> TimedReport
+    where
+        I: IntoIterator,
+        F: FnMut(I::Item);
+
+    /// Sleep for `duration`, waking early if termination is signalled
+    ///
+    /// A drop-in replacement for `std::thread::sleep` between polls. The
+    /// thread is parked rather than put to sleep, and since terminating a
+    /// container also unparks its threads, the worker wakes straight away.
+    fn sleep(&self, duration: Duration) -> SleepOutcome;
+
+    /// Run `f` as a critical section that shouldn't be abandoned half way
+    ///
+    /// Meant for non-atomic side effects, e.g. writing several related files.
+    /// While the section runs, `shutdown` on the worker's container doesn't
+    /// count the worker as hung, however long its grace period has passed.
+    /// Sections may be nested.
+    fn uninterruptible<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R;
+
+    /// Cancel `io` as soon as termination is signalled, so blocking calls on it return
+    ///
+    /// Polling the flag can't stop a worker blocked in e.g. `read` or
+    /// `accept`, so the I/O itself is cancelled instead: sockets are shut
+    /// down, and a `CancelPipe` wakes whoever waits on it. Pass a clone, e.g.
+    /// from `TcpStream::try_clone`, and keep using the original. If
+    /// termination has already been signalled, `io` is cancelled straight
+    /// away.
+    ///
+    /// ## Note
+    ///
+    /// Only flags raised through a `FlagSet`, e.g. by a container's
+    /// `terminate`, or by a runtime or CPU limit, cancel the I/O, not ones
+    /// stored to directly.
+    fn cancel_io_on_terminate<I>(&self, io: I) -> IoRegistration<'_>
+    where
+        I: CancelIo + 'static;
+}
+
+impl Token for AtomicBool {
+    fn should_stop(&self) -> bool {
+        let stop = self.load(atomic::Ordering::SeqCst);
+
+        if stop {
+            metrics::observed();
+        }
+
+        stop
+    }
+
+    fn run_until_terminated<T, F>(&self, mut step: F) -> StopReason<T>
+    where
+        F: FnMut() -> ControlFlow<T>,
+    {
+        while !self.should_stop() {
+            if let ControlFlow::Break(value) = step() {
+                return StopReason::Finished(value);
+            }
+        }
+
+        StopReason::Terminated
+    }
+
+    fn for_each_timed<I, F>(&self, iter: I, budget: Duration, mut f: F) -> TimedReport
+    where
+        I: IntoIterator,
+        F: FnMut(I::Item),
+    {
+        let mut report = TimedReport::default();
+
+        for (index, item) in iter.into_iter().enumerate() {
+            if self.should_stop() {
+                report.terminated = true;
+                break;
+            }
+
+            let start = Instant::now();
+            f(item);
+
+            if start.elapsed() > budget {
+                report.overran.push(index);
+            }
+
+            report.completed += 1;
+        }
+
+        report
+    }
+
+    fn uninterruptible<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let id = thread::current().id();
+        lock(&CRITICAL_SECTIONS).push(id);
+
+        let _exit = ExitCriticalSection(id);
+        f()
+    }
+
+    fn cancel_io_on_terminate<I>(&self, io: I) -> IoRegistration<'_>
+    where
+        I: CancelIo + 'static,
+    {
+        cancel::register(self, Box::new(io))
+    }
+
+    fn sleep(&self, duration: Duration) -> SleepOutcome {
+        if park_until(self, Instant::now() + duration) {
+            SleepOutcome::Terminated
+        } else {
+            SleepOutcome::Elapsed
+        }
+    }
+}
+
+/// Why a `Token::run_until_terminated` loop stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StopReason<T> {
+    /// The step function returned `Break` with this value
+    Finished(T),
+    /// Termination was signalled
+    Terminated,
+}
+
+/// Threads currently inside `Token::uninterruptible`, once per nested section
+static CRITICAL_SECTIONS: Mutex<Vec<ThreadId>> = Mutex::new(Vec::new());
+
+/// Check whether the thread is inside a `Token::uninterruptible` section
+pub(crate) fn is_uninterruptible(id: ThreadId) -> bool {
+    lock(&CRITICAL_SECTIONS).contains(&id)
+}
+
+/// Leaves a critical section when dropped, including by panicking
+struct ExitCriticalSection(ThreadId);
+
+impl Drop for ExitCriticalSection {
+    fn drop(&mut self) {
+        let mut sections = lock(&CRITICAL_SECTIONS);
+
+        if let Some(position) = sections.iter().rposition(|id| *id == self.0) {
+            sections.swap_remove(position);
+        }
+    }
+}
+
+/// How a `Token::sleep` ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SleepOutcome {
+    /// The full duration passed
+    Elapsed,
+    /// Termination was signalled before the duration passed
+    Terminated,
+}
+
+/// Summary of a `Token::for_each_timed` run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimedReport {
+    /// Number of items that were processed
+    pub completed: usize,
+    /// Indices of the items that took longer than the per-item budget
+    pub overran: Vec<usize>,
+    /// Whether iteration stopped early because termination was signalled
+    pub terminated: bool,
+}
+
+/// Return from the enclosing function if termination has been signalled
+///
+/// `checkpoint!(flag)` returns `Default::default()`, while
+/// `checkpoint!(flag, value)` returns `value`, e.g. a `ControlFlow::Break`.
+/// Works with the `Arc<AtomicBool>` passed to each managed thread, or any
+/// reference to it.
+#[macro_export]
+macro_rules! checkpoint {
+    ($token:expr) => {
+        $crate::checkpoint!($token, ::core::default::Default::default())
+    };
+    ($token:expr, $value:expr) => {
+        if {
+            use $crate::Token as _;
+            $token.should_stop()
+        } {
+            return $value;
+        }
+    };
+}
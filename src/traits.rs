@@ -0,0 +1,166 @@
+use std::sync::Arc;
+use std::thread::{JoinHandle, ScopedJoinHandle};
+
+use crate::JoinError;
+
+/// Anything that can be signalled to terminate its threads
+///
+/// Implemented for every container in this crate, and for shared references,
+/// `Arc`s and boxes of them, so termination can be triggered without owning
+/// or uniquely borrowing the container.
+pub trait Terminate {
+    /// Signal termination, see the `terminate` method of the implementing container
+    fn terminate(&self);
+}
+
+impl<T: Terminate + ?Sized> Terminate for &T {
+    fn terminate(&self) {
+        (**self).terminate();
+    }
+}
+
+impl<T: Terminate + ?Sized> Terminate for Arc<T> {
+    fn terminate(&self) {
+        (**self).terminate();
+    }
+}
+
+impl<T: Terminate + ?Sized> Terminate for Box<T> {
+    fn terminate(&self) {
+        (**self).terminate();
+    }
+}
+
+/// Health of a thread manager, for backing liveness and readiness endpoints
+///
+/// Implemented for every container in this crate, so health checks can be
+/// answered from the manager's actual state rather than a separately kept bool.
+pub trait HealthSource {
+    /// Whether every managed thread is still running and termination has not been signalled
+    fn healthy(&self) -> bool;
+
+    /// Whether the manager is able to take on work
+    ///
+    /// Defaults to `healthy()`
+    fn ready(&self) -> bool {
+        self.healthy()
+    }
+
+    /// Whether termination has been signalled
+    fn shutting_down(&self) -> bool;
+}
+
+impl<T: HealthSource + ?Sized> HealthSource for &T {
+    fn healthy(&self) -> bool {
+        (**self).healthy()
+    }
+
+    fn ready(&self) -> bool {
+        (**self).ready()
+    }
+
+    fn shutting_down(&self) -> bool {
+        (**self).shutting_down()
+    }
+}
+
+impl<T: HealthSource + ?Sized> HealthSource for Arc<T> {
+    fn healthy(&self) -> bool {
+        (**self).healthy()
+    }
+
+    fn ready(&self) -> bool {
+        (**self).ready()
+    }
+
+    fn shutting_down(&self) -> bool {
+        (**self).shutting_down()
+    }
+}
+
+impl<T: HealthSource + ?Sized> HealthSource for Box<T> {
+    fn healthy(&self) -> bool {
+        (**self).healthy()
+    }
+
+    fn ready(&self) -> bool {
+        (**self).ready()
+    }
+
+    fn shutting_down(&self) -> bool {
+        (**self).shutting_down()
+    }
+}
+
+/// Anything whose threads can be waited on until they finish
+///
+/// Implemented for every container in this crate as well as for the standard
+/// library's join handles, so they can all be joined through the same interface.
+pub trait Join {
+    /// What joining produces, usually one `Result` per thread
+    type Output;
+
+    /// Wait for the threads to finish naturally, without signalling termination
+    fn join(self) -> Self::Output;
+}
+
+impl<T> Join for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn join(self) -> Self::Output {
+        JoinHandle::join(self)
+    }
+}
+
+impl<T> Join for ScopedJoinHandle<'_, T> {
+    type Output = Result<T, JoinError>;
+
+    fn join(self) -> Self::Output {
+        ScopedJoinHandle::join(self)
+    }
+}
+
+/// Join everything in `joinables`, collecting the outputs in order
+///
+/// Accepts anything implementing `Join`, e.g. a `Vec` of `JoinHandle`s,
+/// `TerminableThreadHandle`s or whole containers. Items of differing types can
+/// be joined together as a tuple, which also implements `Join`.
+pub fn join_all<I>(joinables: I) -> Vec<<I::Item as Join>::Output>
+where
+    I: IntoIterator,
+    I::Item: Join,
+{
+    joinables.into_iter().map(Join::join).collect()
+}
+
+/// Implement `Join` and `Terminate` for tuples, element by element
+macro_rules! impl_tuples {
+    ($($name:ident),+) => {
+        impl<$($name: Join),+> Join for ($($name,)+) {
+            type Output = ($($name::Output,)+);
+
+            #[allow(non_snake_case)]
+            fn join(self) -> Self::Output {
+                let ($($name,)+) = self;
+                ($($name.join(),)+)
+            }
+        }
+
+        impl<$($name: Terminate),+> Terminate for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn terminate(&self) {
+                let ($($name,)+) = self;
+                $($name.terminate();)+
+            }
+        }
+    };
+}
+
+impl_tuples!(A);
+impl_tuples!(A, B);
+impl_tuples!(A, B, C);
+impl_tuples!(A, B, C, D);
+impl_tuples!(A, B, C, D, E);
+impl_tuples!(A, B, C, D, E, F);
+impl_tuples!(A, B, C, D, E, F, G);
+impl_tuples!(A, B, C, D, E, F, G, H);
@@ -0,0 +1,267 @@
+use std::fmt;
+use std::fs;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::Arc;
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+use crate::cancel;
+use crate::interval::park_until;
+use crate::{spawn_terminable, FlagSet, TerminableThreadHandle};
+
+/// What a monitor does after checking its worker
+enum Check {
+    /// Check again after waiting this long
+    WaitFor(Duration),
+    /// The worker went over its limit
+    Exceeded,
+    /// The limit can't be checked anymore
+    #[cfg(all(feature = "unix", target_os = "linux"))]
+    Stop,
+}
+
+/// Start timing the current thread, raising `flag` and `timed_out` if it runs longer than `limit`
+pub(crate) fn limit_runtime(
+    flag: Arc<AtomicBool>,
+    timed_out: Arc<AtomicBool>,
+    limit: Duration,
+) -> MonitorGuard {
+    let deadline = Instant::now() + limit;
+
+    monitor(flag, timed_out, move || {
+        match deadline.checked_duration_since(Instant::now()) {
+            Some(remaining) if !remaining.is_zero() => Check::WaitFor(remaining),
+            _ => Check::Exceeded,
+        }
+    })
+}
+
+/// How often the CPU time of a thread with a CPU budget is sampled
+#[cfg(all(feature = "unix", target_os = "linux"))]
+const CPU_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Start measuring the current thread's CPU time, raising `flag` and `timed_out` once it uses more than `budget`
+///
+/// Unlike a wall-clock limit, this only catches threads actually spinning,
+/// not ones waiting on I/O or locks.
+#[cfg(all(feature = "unix", target_os = "linux"))]
+pub(crate) fn limit_cpu_time(
+    flag: Arc<AtomicBool>,
+    timed_out: Arc<AtomicBool>,
+    budget: Duration,
+) -> MonitorGuard {
+    let clock = cpu::current_thread_clock();
+
+    monitor(flag, timed_out, move || {
+        match clock.and_then(cpu::elapsed) {
+            Some(used) if used >= budget => Check::Exceeded,
+            Some(used) => Check::WaitFor((budget - used).min(CPU_POLL_INTERVAL)),
+            None => Check::Stop,
+        }
+    })
+}
+
+/// Spawn a thread running `check` against the current thread until it exits or is terminated
+///
+/// When `check` reports the limit exceeded, `timed_out` and `flag` are raised
+/// and the worker is unparked. The monitor is woken to stop early once the
+/// returned guard is dropped, i.e. when the worker returns or unwinds.
+fn monitor<C>(flag: Arc<AtomicBool>, timed_out: Arc<AtomicBool>, mut check: C) -> MonitorGuard
+where
+    C: FnMut() -> Check + Send + 'static,
+{
+    let finished = Arc::new(AtomicBool::new(false));
+    let worker = thread::current();
+
+    let monitor = {
+        let finished = Arc::clone(&finished);
+
+        thread::spawn(move || loop {
+            if finished.load(atomic::Ordering::SeqCst) || flag.load(atomic::Ordering::SeqCst) {
+                return;
+            }
+
+            match check() {
+                Check::WaitFor(duration) => thread::park_timeout(duration),
+                Check::Exceeded => {
+                    timed_out.store(true, atomic::Ordering::SeqCst);
+                    flag.store(true, atomic::Ordering::SeqCst);
+                    cancel::raised(&flag);
+                    worker.unpark();
+                    return;
+                }
+                #[cfg(all(feature = "unix", target_os = "linux"))]
+                Check::Stop => return,
+            }
+        })
+    };
+
+    MonitorGuard {
+        finished,
+        monitor: monitor.thread().clone(),
+    }
+}
+
+/// Stops a monitor started by `limit_runtime` or `limit_cpu_time` when dropped
+pub(crate) struct MonitorGuard {
+    finished: Arc<AtomicBool>,
+    monitor: Thread,
+}
+
+impl Drop for MonitorGuard {
+    fn drop(&mut self) {
+        self.finished.store(true, atomic::Ordering::SeqCst);
+        self.monitor.unpark();
+    }
+}
+
+/// Emergency brake terminating a container once the process uses too much memory
+///
+/// A monitor thread samples the process's resident set size (RSS) and, the
+/// first time it reaches the threshold, reports the measurement to the
+/// callback and terminates the watched container.
+pub struct MemoryWatchdog {
+    threshold: u64,
+    interval: Duration,
+    on_exceeded: Option<Box<dyn FnOnce(u64) + Send>>,
+}
+
+impl MemoryWatchdog {
+    /// Create a watchdog tripping once the process's RSS reaches `threshold` bytes
+    pub fn new(threshold: u64) -> Self {
+        Self {
+            threshold,
+            interval: Duration::from_secs(1),
+            on_exceeded: None,
+        }
+    }
+
+    /// Set how often the RSS is sampled, once a second by default
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Call `f` with the RSS in bytes when the threshold is reached, before terminating
+    pub fn on_exceeded<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(u64) + Send + 'static,
+    {
+        self.on_exceeded = Some(Box::new(f));
+        self
+    }
+
+    /// Start watching, terminating `target` once the threshold is reached
+    ///
+    /// Works with any container built on a `FlagSet`. Terminating the returned
+    /// handle stops the watchdog without touching `target`.
+    ///
+    /// ## Note
+    ///
+    /// The RSS is read from `/proc/self/status`, so on platforms without it
+    /// the watchdog never trips.
+    ///
+    /// # Returns
+    ///
+    /// The handle of the monitor thread, which gives the RSS that tripped the
+    /// watchdog, or `None` if it was stopped first
+    pub fn watch<C>(self, target: &C) -> TerminableThreadHandle<Option<u64>>
+    where
+        C: AsRef<FlagSet>,
+    {
+        let trigger = FlagSet::new();
+        trigger.link(target.as_ref());
+
+        spawn_terminable(move |flag| loop {
+            if let Some(rss) = resident_memory().filter(|&rss| rss >= self.threshold) {
+                if let Some(on_exceeded) = self.on_exceeded {
+                    on_exceeded(rss);
+                }
+
+                trigger.terminate();
+                return Some(rss);
+            }
+
+            if park_until(&flag, Instant::now() + self.interval) {
+                return None;
+            }
+        })
+    }
+}
+
+impl fmt::Debug for MemoryWatchdog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryWatchdog")
+            .field("threshold", &self.threshold)
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Resident set size of the current process in bytes, `None` where it can't be read
+pub fn resident_memory() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+
+    kilobytes.checked_mul(1024)
+}
+
+/// Per-thread CPU clocks, through the POSIX thread CPU-time API
+#[cfg(all(feature = "unix", target_os = "linux"))]
+mod cpu {
+    use std::os::raw::{c_int, c_long};
+    use std::os::unix::thread::RawPthread;
+    use std::time::Duration;
+
+    #[repr(C)]
+    struct Timespec {
+        tv_sec: c_long,
+        tv_nsec: c_long,
+    }
+
+    extern "C" {
+        fn pthread_self() -> RawPthread;
+        fn pthread_getcpuclockid(thread: RawPthread, clock_id: *mut c_int) -> c_int;
+        fn clock_gettime(clock_id: c_int, tp: *mut Timespec) -> c_int;
+    }
+
+    /// The CPU-time clock of the calling thread, readable from other threads
+    ///
+    /// Equivalent to `CLOCK_THREAD_CPUTIME_ID`, which only ever refers to
+    /// whichever thread reads it.
+    pub(super) fn current_thread_clock() -> Option<c_int> {
+        let mut clock = 0;
+
+        // SAFETY: `pthread_self` always returns the calling thread's valid
+        // handle, and `clock` is a valid place to write the clock id to
+        let result = unsafe { pthread_getcpuclockid(pthread_self(), &mut clock) };
+
+        (result == 0).then_some(clock)
+    }
+
+    /// CPU time used so far by the thread owning `clock`
+    ///
+    /// # Returns
+    ///
+    /// `None` if the clock can't be read, e.g. because the thread has exited
+    pub(super) fn elapsed(clock: c_int) -> Option<Duration> {
+        let mut time = Timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+
+        // SAFETY: `time` is a valid `timespec` to write the reading to, and an
+        // invalid clock id is reported as an error rather than being used
+        let result = unsafe { clock_gettime(clock, &mut time) };
+
+        if result != 0 {
+            return None;
+        }
+
+        Some(Duration::new(
+            u64::try_from(time.tv_sec).ok()?,
+            u32::try_from(time.tv_nsec).ok()?,
+        ))
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::{spawn_terminable, SleepOutcome, TerminableThreadHandle, Token};
+
+/// A change found by a watcher spawned with `spawn_fs_watcher`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FsEvent {
+    /// The path didn't exist at the previous poll
+    Created(PathBuf),
+    /// The file's size or modification time changed since the previous poll
+    Modified(PathBuf),
+    /// The path no longer exists
+    Removed(PathBuf),
+}
+
+impl FsEvent {
+    /// The path that changed
+    pub fn path(&self) -> &Path {
+        match self {
+            FsEvent::Created(path) | FsEvent::Modified(path) | FsEvent::Removed(path) => path,
+        }
+    }
+}
+
+/// Spawn a thread polling `path` every `interval`, passing each change to `callback`
+///
+/// If `path` is a directory, everything below it is watched, without
+/// following symbolic links. What exists when the thread starts is taken as
+/// the baseline, so `callback` is only called for later changes, in path
+/// order. Changes that cancel out between two polls, e.g. a file created then
+/// removed, go unnoticed.
+///
+/// The thread stops once the handle is terminated, without waiting out the
+/// rest of the interval, or once `token` is raised, e.g. the group flag of
+/// the container the watcher serves. `token` is only checked once per poll.
+///
+/// # Errors
+///
+/// Joining the thread gives the OS error if `path` couldn't be read, other
+/// than it not existing, which counts as it being empty
+pub fn spawn_fs_watcher<P, F>(
+    path: P,
+    interval: Duration,
+    token: Arc<AtomicBool>,
+    mut callback: F,
+) -> TerminableThreadHandle<io::Result<()>>
+where
+    P: Into<PathBuf>,
+    F: FnMut(FsEvent) + Send + 'static,
+{
+    let path = path.into();
+
+    spawn_terminable(move |flag| {
+        let mut previous = snapshot(&path)?;
+
+        loop {
+            if flag.sleep(interval) == SleepOutcome::Terminated || token.should_stop() {
+                return Ok(());
+            }
+
+            let current = snapshot(&path)?;
+
+            for event in changes(&previous, &current) {
+                callback(event);
+            }
+
+            previous = current;
+        }
+    })
+}
+
+/// What a poll records about each path, to tell whether it changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    is_dir: bool,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+/// Stamp `root` and everything below it
+fn snapshot(root: &Path) -> io::Result<BTreeMap<PathBuf, Stamp>> {
+    let mut stamps = BTreeMap::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(path) = pending.pop() {
+        // Anything removed mid-walk is reported at the next poll
+        let metadata = match fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+            Err(error) => return Err(error),
+        };
+
+        if metadata.is_dir() {
+            match fs::read_dir(&path) {
+                Ok(entries) => {
+                    for entry in entries {
+                        pending.push(entry?.path());
+                    }
+                }
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(error),
+            }
+        }
+
+        stamps.insert(
+            path,
+            Stamp {
+                is_dir: metadata.is_dir(),
+                len: metadata.len(),
+                modified: metadata.modified().ok(),
+            },
+        );
+    }
+
+    Ok(stamps)
+}
+
+/// The changes between two snapshots, in path order
+fn changes(
+    previous: &BTreeMap<PathBuf, Stamp>,
+    current: &BTreeMap<PathBuf, Stamp>,
+) -> Vec<FsEvent> {
+    let mut events: Vec<FsEvent> = previous
+        .keys()
+        .filter(|path| !current.contains_key(*path))
+        .map(|path| FsEvent::Removed(path.clone()))
+        .collect();
+
+    for (path, stamp) in current {
+        match previous.get(path) {
+            None => events.push(FsEvent::Created(path.clone())),
+            // A directory's own stamp changes with its entries, which are reported themselves
+            Some(old) if old.is_dir && stamp.is_dir => {}
+            Some(old) if old != stamp => events.push(FsEvent::Modified(path.clone())),
+            Some(_) => {}
+        }
+    }
+
+    events.sort_by(|a, b| a.path().cmp(b.path()));
+    events
+}